@@ -0,0 +1,613 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use rusqlite::{
+    types::{Value as SqlValue, ValueRef},
+    vtab::array::load_module,
+    Connection, OptionalExtension, Transaction,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tauri::api::path::BaseDirectory;
+
+use crate::{
+    bookmarks::BookmarkDB,
+    categories::CategoryDB,
+    db::{rebuild_metadata_index, ChapterDB, MangaDB},
+    duplicates::LinkedSourcesDB,
+    errors::{AppError, Context},
+    get_db_path,
+    profiles::ProfileDB,
+    progress::ProgressDB,
+    readerdb::ReaderDB,
+    savedsearch::SavedSearchDB,
+    settings::{collect_changes, AppSettings, Settings, SettingsChanged},
+    sourcedb::SourcePreferencesDB,
+    tachiyomi::TachiyomiSourceMapDB,
+};
+
+pub const BACKUP_EXTENSION: &str = "swrsbackup";
+
+/// Every table that makes up the user's library, in restore order.
+pub const BACKUP_TABLES: [&str; 13] = [
+    "Library",
+    "MangaOverrides",
+    "Chapters",
+    "ChapterProgress",
+    "Bookmark",
+    "ReaderSettings",
+    "ReaderOverrides",
+    "MangaCategories",
+    "LinkedSources",
+    "SourcePreferences",
+    "SavedSearches",
+    "SettingsProfiles",
+    "TachiyomiSourceMap",
+];
+
+/// Tables keyed on a row id that only means something in the database it came
+/// from, with the unique columns their rows are matched on when restoring
+/// instead. The id is left for the restoring database to assign.
+const LOCAL_ID_TABLES: [(&str, &str, &[&str]); 1] = [(
+    "Bookmark",
+    "id",
+    &["source", "manga_id", "chapter_id", "page"],
+)];
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub app_version: String,
+    pub schema_version: i64,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Backup {
+    pub manifest: BackupManifest,
+    pub settings: Value,
+    pub tables: BTreeMap<String, Vec<Map<String, Value>>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RestoreMode {
+    /// Keep existing rows, replacing only those the backup also has.
+    Merge,
+    /// Also remove every row the backup doesn't have.
+    Replace,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct TableReport {
+    pub added: usize,
+    pub replaced: usize,
+    pub removed: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RestoreReport {
+    pub dry_run: bool,
+    pub manifest: BackupManifest,
+    pub tables: BTreeMap<String, TableReport>,
+    pub settings_changed: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BackupFile {
+    pub path: PathBuf,
+    pub created_at: i64,
+    pub size: u64,
+}
+
+#[must_use]
+pub fn get_backups_path() -> Option<PathBuf> {
+    let app_context = tauri::generate_context!();
+    tauri::api::path::resolve_path(
+        app_context.config(),
+        app_context.package_info(),
+        &tauri::Env::default(),
+        Path::new("com.suwariyomirs.swrs\\backups")
+            .as_os_str()
+            .to_str()
+            .unwrap(),
+        Some(BaseDirectory::Config),
+    )
+    .ok()
+}
+
+/// Opening each handler once makes sure every backed up table exists.
+fn create_tables(path: &Option<PathBuf>) -> Result<(), AppError> {
+    MangaDB::new(path)?;
+    ChapterDB::new(path)?;
+    ReaderDB::new(path)?;
+    CategoryDB::new(path)?;
+    LinkedSourcesDB::new(path)?;
+    SourcePreferencesDB::new(path)?;
+    SavedSearchDB::new(path)?;
+    ProfileDB::new(path)?;
+    ProgressDB::new(path)?;
+    BookmarkDB::new(path)?;
+    TachiyomiSourceMapDB::new(path)?;
+
+    Ok(())
+}
+
+fn open_db() -> Result<Connection, AppError> {
+    create_tables(&get_db_path())?;
+
+    let path = get_db_path().ok_or_else(|| AppError::internal("unable to get db path"))?;
+    let db = Connection::open(path)?;
+    load_module(&db)?;
+    Ok(db)
+}
+
+/// Column names of `table`, and the subset making up its primary key. Tables
+/// without a primary key are matched on every column.
+fn table_columns(
+    db: &Connection,
+    table: &str,
+) -> Result<(Vec<String>, Vec<String>), rusqlite::Error> {
+    let mut prepared_rows = db.prepare(&format!("PRAGMA table_info({table})"))?;
+    let columns = prepared_rows
+        .query_map([], |row| {
+            Ok((
+                row.get::<&str, String>("name")?,
+                row.get::<&str, i64>("pk")?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let names: Vec<String> = columns.iter().map(|(name, _)| name.clone()).collect();
+    let mut keys: Vec<(i64, String)> = columns
+        .into_iter()
+        .filter(|(_, pk)| *pk > 0)
+        .map(|(name, pk)| (pk, name))
+        .collect();
+    keys.sort();
+
+    if keys.is_empty() {
+        return Ok((names.clone(), names));
+    }
+
+    Ok((names, keys.into_iter().map(|(_, name)| name).collect()))
+}
+
+pub fn sql_to_json(value: ValueRef) -> Value {
+    match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(i) => Value::from(i),
+        ValueRef::Real(f) => Value::from(f),
+        ValueRef::Text(t) => Value::from(String::from_utf8_lossy(t).to_string()),
+        ValueRef::Blob(b) => Value::from(b.to_vec()),
+    }
+}
+
+fn json_to_sql(value: &Value) -> SqlValue {
+    match value {
+        Value::Null => SqlValue::Null,
+        Value::Bool(b) => SqlValue::Integer(i64::from(*b)),
+        Value::Number(n) => n.as_i64().map_or_else(
+            || SqlValue::Real(n.as_f64().unwrap_or_default()),
+            SqlValue::Integer,
+        ),
+        Value::String(s) => SqlValue::Text(s.clone()),
+        Value::Array(bytes) if bytes.iter().all(Value::is_u64) => SqlValue::Blob(
+            bytes
+                .iter()
+                .filter_map(|b| b.as_u64().and_then(|b| u8::try_from(b).ok()))
+                .collect(),
+        ),
+        other => SqlValue::Text(other.to_string()),
+    }
+}
+
+fn dump_table(db: &Connection, table: &str) -> Result<Vec<Map<String, Value>>, rusqlite::Error> {
+    let (columns, _) = table_columns(db, table)?;
+    let mut prepared_rows = db.prepare(&format!("SELECT * FROM {table}"))?;
+    let iter = prepared_rows.query_map([], |row| {
+        let mut object = Map::new();
+        for column in &columns {
+            object.insert(column.clone(), sql_to_json(row.get_ref(column.as_str())?));
+        }
+
+        Ok(object)
+    })?;
+
+    iter.collect()
+}
+
+/// Snapshots every library table and the app settings inside one read
+/// transaction, so the backup is consistent even while the app is writing.
+pub fn create() -> Result<Backup, AppError> {
+    let mut db = open_db()?;
+    let transaction = db.transaction()?;
+
+    let mut tables = BTreeMap::new();
+    for table in BACKUP_TABLES {
+        tables.insert(table.to_string(), dump_table(&transaction, table)?);
+    }
+
+    let schema_version: i64 = transaction.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    transaction.commit()?;
+
+    Ok(Backup {
+        manifest: BackupManifest {
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            schema_version,
+            created_at: chrono::Utc::now().timestamp(),
+        },
+        settings: serde_json::to_value(Settings {}.get()?)?,
+        tables,
+    })
+}
+
+/// Writes a new backup to `path`, or to a timestamped file in the backups
+/// directory if no path is given.
+pub fn create_file(path: Option<PathBuf>) -> Result<PathBuf, AppError> {
+    let path = match path {
+        Some(path) => path,
+        None => {
+            let dir = get_backups_path()
+                .ok_or_else(|| AppError::internal("unable to get backups dir"))?;
+            fs::create_dir_all(&dir).map_err(AppError::from)?;
+
+            dir.join(format!(
+                "suwariyomi-{}.{BACKUP_EXTENSION}",
+                chrono::Utc::now().format("%Y%m%d-%H%M%S")
+            ))
+        }
+    };
+
+    fs::write(&path, serde_json::to_vec(&create()?)?).context("unable to write backup")?;
+
+    Ok(path)
+}
+
+pub fn read_file(path: &Path) -> Result<Backup, AppError> {
+    let contents = fs::read(path).context("unable to read backup")?;
+
+    Ok(serde_json::from_slice(&contents)?)
+}
+
+/// The rowid of the row in `table` with the same `keys` as `row`, if any.
+fn find_row(
+    transaction: &Transaction,
+    table: &str,
+    keys: &[String],
+    row: &Map<String, Value>,
+) -> Result<Option<i64>, rusqlite::Error> {
+    let clause = keys
+        .iter()
+        .enumerate()
+        .map(|(i, key)| format!("{key} IS ?{}", i + 1))
+        .collect::<Vec<String>>()
+        .join(" AND ");
+    let params: Vec<SqlValue> = keys
+        .iter()
+        .map(|key| json_to_sql(row.get(key).unwrap_or(&Value::Null)))
+        .collect();
+
+    transaction
+        .query_row(
+            &format!("SELECT rowid FROM {table} WHERE {clause}"),
+            rusqlite::params_from_iter(params),
+            |row| row.get(0),
+        )
+        .optional()
+}
+
+/// Writes `rows` into `table`, counting each one as added or replaced by
+/// whether a row with its key was already there. Returns the report along with
+/// the rowids of every restored row.
+fn restore_rows(
+    transaction: &Transaction,
+    table: &str,
+    rows: &[Map<String, Value>],
+) -> Result<(TableReport, Vec<i64>), rusqlite::Error> {
+    let (mut columns, mut keys) = table_columns(transaction, table)?;
+    if let Some((_, id, unique)) = LOCAL_ID_TABLES.iter().find(|(name, ..)| *name == table) {
+        columns.retain(|column| column != id);
+        keys = unique.iter().map(ToString::to_string).collect();
+    }
+
+    let mut report = TableReport::default();
+    let mut restored = vec![];
+    for row in rows {
+        let existing = find_row(transaction, table, &keys, row)?;
+        if let Some(rowid) = existing {
+            report.replaced += 1;
+            restored.push(rowid);
+            // Rows of tables keyed on every column have nothing to update.
+            if keys.len() == columns.len() {
+                continue;
+            }
+        }
+
+        // Only columns the table actually has, so a backup can't smuggle in SQL.
+        let present: Vec<&str> = columns
+            .iter()
+            .filter(|c| row.contains_key(*c))
+            .map(String::as_str)
+            .collect();
+        let placeholders: Vec<String> = (1..=present.len()).map(|i| format!("?{i}")).collect();
+        let params: Vec<SqlValue> = present.iter().map(|c| json_to_sql(&row[*c])).collect();
+
+        // An upsert rather than `REPLACE`, which would delete the existing
+        // row and cascade to everything referencing it.
+        let updates: Vec<String> = present
+            .iter()
+            .filter(|c| !keys.iter().any(|key| key == *c))
+            .map(|c| format!("{c} = excluded.{c}"))
+            .collect();
+        let conflict = if keys.len() == columns.len() {
+            String::new()
+        } else if updates.is_empty() {
+            format!("ON CONFLICT ({}) DO NOTHING", keys.join(", "))
+        } else {
+            format!(
+                "ON CONFLICT ({}) DO UPDATE SET {}",
+                keys.join(", "),
+                updates.join(", ")
+            )
+        };
+
+        let changes = transaction.execute(
+            &format!(
+                "INSERT INTO {table} ({}) VALUES ({}) {conflict}",
+                present.join(", "),
+                placeholders.join(", ")
+            ),
+            rusqlite::params_from_iter(params),
+        )?;
+        if existing.is_none() && changes > 0 {
+            report.added += 1;
+            restored.push(transaction.last_insert_rowid());
+        }
+    }
+
+    Ok((report, restored))
+}
+
+/// Deletes every row of `table` that wasn't just restored.
+fn remove_unrestored(
+    transaction: &Transaction,
+    table: &str,
+    restored: &[i64],
+) -> Result<usize, rusqlite::Error> {
+    let restored: Vec<SqlValue> = restored.iter().copied().map(SqlValue::from).collect();
+
+    transaction.execute(
+        &format!("DELETE FROM {table} WHERE rowid NOT IN rarray(?1)"),
+        [Rc::new(restored)],
+    )
+}
+
+/// Restores every backed up table, parents before the rows referencing them.
+/// In replace mode the rows the backup doesn't have are removed afterwards,
+/// children first, so each table's count is only its own deletes and never
+/// those cascading from a parent.
+fn restore_tables(
+    transaction: &Transaction,
+    backup_tables: &BTreeMap<String, Vec<Map<String, Value>>>,
+    mode: RestoreMode,
+) -> Result<BTreeMap<String, TableReport>, rusqlite::Error> {
+    let mut restored = vec![];
+    for table in BACKUP_TABLES {
+        let rows = backup_tables.get(table).map_or(&[][..], Vec::as_slice);
+        restored.push((table, restore_rows(transaction, table, rows)?));
+    }
+
+    if mode == RestoreMode::Replace {
+        for (table, (report, rowids)) in restored.iter_mut().rev() {
+            report.removed = remove_unrestored(transaction, table, rowids)?;
+        }
+    }
+
+    Ok(restored
+        .into_iter()
+        .map(|(table, (report, _))| (table.to_string(), report))
+        .collect())
+}
+
+/// Restores `backup`. With `dry_run` every change is rolled back and only the
+/// report of what would have happened is returned.
+pub fn restore(
+    backup: Backup,
+    mode: RestoreMode,
+    dry_run: bool,
+) -> Result<(RestoreReport, Option<SettingsChanged>), AppError> {
+    let mut db = open_db()?;
+    let schema_version: i64 = db.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    // Rows are restored as they were saved, without the conversions a
+    // database goes through when its schema is upgraded, so only backups of
+    // this schema can be restored as-is.
+    if backup.manifest.schema_version != schema_version {
+        return Err(AppError::validation(format!(
+            "backup schema version {} does not match this build's ({schema_version}); \
+            restore it with the version that made it",
+            backup.manifest.schema_version
+        )));
+    }
+
+    let transaction = db.transaction()?;
+    let tables = restore_tables(&transaction, &backup.tables, mode)?;
+
+    let current_settings = serde_json::to_value(Settings {}.get()?)?;
+    let mut report = RestoreReport {
+        dry_run,
+        manifest: backup.manifest,
+        tables,
+        settings_changed: vec![],
+    };
+
+    if dry_run {
+        transaction.rollback()?;
+        let restored = serde_json::to_value(AppSettings::from_value(backup.settings)?)?;
+        collect_changes(
+            &current_settings,
+            &restored,
+            "",
+            &mut report.settings_changed,
+        );
+
+        return Ok((report, None));
+    }
+
+    transaction.commit()?;
+    rebuild_metadata_index(&db)?;
+    let changed = Settings {}.set(backup.settings)?;
+    report.settings_changed = changed.changed.clone();
+
+    Ok((report, Some(changed)))
+}
+
+pub fn list() -> Result<Vec<BackupFile>, AppError> {
+    let dir = get_backups_path().ok_or_else(|| AppError::internal("unable to get backups dir"))?;
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut backups: Vec<BackupFile> = fs::read_dir(dir)
+        .map_err(AppError::from)?
+        .filter_map(std::result::Result::ok)
+        .filter(|entry| {
+            entry.path().extension().and_then(std::ffi::OsStr::to_str) == Some(BACKUP_EXTENSION)
+        })
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            let created_at = metadata
+                .modified()
+                .ok()?
+                .duration_since(std::time::UNIX_EPOCH)
+                .ok()?
+                .as_secs();
+
+            Some(BackupFile {
+                path: entry.path(),
+                created_at: i64::try_from(created_at).unwrap_or(i64::MAX),
+                size: metadata.len(),
+            })
+        })
+        .collect();
+
+    backups.sort_by_key(|backup| std::cmp::Reverse(backup.created_at));
+    Ok(backups)
+}
+
+/// Deletes all but the newest `keep` backups in the backups directory.
+pub fn rotate(keep: usize) -> Result<usize, AppError> {
+    let backups = list()?;
+    let mut removed = 0;
+    for backup in backups.iter().skip(keep) {
+        fs::remove_file(&backup.path).map_err(AppError::from)?;
+        removed += 1;
+    }
+
+    Ok(removed)
+}
+
+/// Makes a backup and rotates old ones if automatic backups are enabled and
+/// the newest backup is older than the configured interval.
+pub fn run_scheduled() -> Result<Option<PathBuf>, AppError> {
+    let settings = Settings {}.get()?.backup;
+    if !settings.automatic_backups {
+        return Ok(None);
+    }
+
+    let newest = list()?.first().map_or(0, |backup| backup.created_at);
+    if chrono::Utc::now().timestamp() - newest < settings.backup_interval.saturating_mul(60 * 60) {
+        return Ok(None);
+    }
+
+    let path = create_file(None)?;
+    rotate(settings.backups_to_keep)?;
+
+    Ok(Some(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{open, TestDb};
+
+    fn test_db() -> (TestDb, Connection) {
+        let test_db = TestDb::default();
+        create_tables(&test_db.path).unwrap();
+        let db = open(&test_db.path).unwrap();
+        load_module(&db).unwrap();
+        db.execute_batch(
+            "INSERT INTO Library (source, id, name, description, covers, chapters, authors,
+                tags, uploaded, added)
+            VALUES ('source', 'kept', 'Kept', '', '[]', '[]', '[]', '[]', 0, 1000),
+                ('source', 'gone', 'Gone', '', '[]', '[]', '[]', '[]', 0, 1000);
+            INSERT INTO Chapters (source, manga_id, id, title, chapter, pages, total,
+                last_read, last_updated, time_spent_reading, date_uploaded, scanlators, lang)
+            VALUES ('source', 'kept', '1', '', 1, 0, 0, 0, 0, 0, 0, '[]', 'en'),
+                ('source', 'gone', '1', '', 1, 0, 0, 0, 0, 0, 0, '[]', 'en');
+            INSERT INTO ReaderSettings (source, id, data) VALUES ('source', 'kept', '{\"zoom\":1}');",
+        )
+        .unwrap();
+
+        (test_db, db)
+    }
+
+    fn kept_rows(db: &Connection, table: &str) -> Vec<Map<String, Value>> {
+        dump_table(db, table)
+            .unwrap()
+            .into_iter()
+            .filter(|row| !row.values().any(|value| value == "gone"))
+            .collect()
+    }
+
+    #[test]
+    fn replace_reports_what_was_removed() {
+        let (_test_db, mut db) = test_db();
+        let mut backup_tables = BTreeMap::new();
+        for table in ["Library", "Chapters"] {
+            backup_tables.insert(table.to_string(), kept_rows(&db, table));
+        }
+
+        let transaction = db.transaction().unwrap();
+        let report = restore_tables(&transaction, &backup_tables, RestoreMode::Replace).unwrap();
+
+        for table in ["Library", "Chapters"] {
+            let TableReport {
+                added,
+                replaced,
+                removed,
+            } = report[table];
+            assert_eq!((added, replaced, removed), (0, 1, 1), "{table}");
+        }
+        assert_eq!(report["ReaderSettings"].removed, 1);
+        let chapters: i64 = transaction
+            .query_row("SELECT COUNT(*) FROM Chapters", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(chapters, 1);
+    }
+
+    #[test]
+    fn merge_replaces_reader_settings_by_manga() {
+        let (_test_db, mut db) = test_db();
+        let mut settings = kept_rows(&db, "ReaderSettings");
+        settings[0].insert("data".to_string(), "{\"zoom\":2}".into());
+        let backup_tables = BTreeMap::from([("ReaderSettings".to_string(), settings)]);
+
+        let transaction = db.transaction().unwrap();
+        let report = restore_tables(&transaction, &backup_tables, RestoreMode::Merge).unwrap();
+
+        assert_eq!(report["ReaderSettings"].replaced, 1);
+        assert_eq!(report["ReaderSettings"].added, 0);
+        let data: Vec<String> = transaction
+            .prepare("SELECT data FROM ReaderSettings")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(data, vec!["{\"zoom\":2}".to_string()]);
+    }
+}
@@ -0,0 +1,227 @@
+use rusqlite::{Connection, OptionalExtension, Row};
+use serde::Serialize;
+
+use crate::{
+    db::{open, ChapterDB, NameCount},
+    errors::{AppError, Context},
+    progress::ChapterProgress,
+};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Bookmark {
+    pub id: i64,
+    pub source: String,
+    pub manga_id: String,
+    pub chapter_id: String,
+
+    /// The bookmarked page, starting at 1.
+    pub page: i32,
+    pub note: Option<String>,
+    pub tag: Option<String>,
+    pub created_at: i64,
+
+    /// Taken from the library so bookmarks can be listed on their own.
+    pub manga_name: String,
+    pub chapter: Option<f64>,
+    pub label: Option<String>,
+}
+
+impl Bookmark {
+    /// Where the reader should open to show this bookmark.
+    #[must_use]
+    pub fn position(&self) -> ChapterProgress {
+        ChapterProgress {
+            source: self.source.clone(),
+            manga_id: self.manga_id.clone(),
+            chapter_id: self.chapter_id.clone(),
+            page: self.page,
+            scroll_offset: 0.0,
+            updated_at: self.created_at,
+        }
+    }
+}
+
+pub struct BookmarkDB {
+    db: Connection,
+}
+
+const BOOKMARK_SELECT: &str = "SELECT Bookmark.*, Library.name AS manga_name,
+        Chapters.chapter AS chapter, Chapters.label AS label
+    FROM Bookmark
+    JOIN EffectiveLibrary AS Library
+        ON Library.source = Bookmark.source AND Library.id = Bookmark.manga_id
+    JOIN Chapters ON Chapters.source = Bookmark.source
+        AND Chapters.manga_id = Bookmark.manga_id AND Chapters.id = Bookmark.chapter_id";
+
+const BOOKMARK_ORDER: &str = "ORDER BY Chapters.chapter IS NULL, Chapters.chapter,
+    Chapters.id, Bookmark.page";
+
+fn generate_bookmark_from_row(row: &Row) -> Result<Bookmark, rusqlite::Error> {
+    Ok(Bookmark {
+        id: row.get("id")?,
+        source: row.get("source")?,
+        manga_id: row.get("manga_id")?,
+        chapter_id: row.get("chapter_id")?,
+        page: row.get("page")?,
+        note: row.get("note")?,
+        tag: row.get("tag")?,
+        created_at: row.get("created_at")?,
+        manga_name: row.get("manga_name")?,
+        chapter: row.get("chapter")?,
+        label: row.get("label")?,
+    })
+}
+
+/// Treats blank notes and tags as not given.
+fn non_empty(value: Option<String>) -> Option<String> {
+    value
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+impl BookmarkDB {
+    pub fn new(path: &Option<std::path::PathBuf>) -> Result<Self, AppError> {
+        // Bookmarks reference `Chapters`, so make sure it exists first.
+        ChapterDB::new(path)?;
+        let db = open(path)?;
+
+        db.execute_batch(
+            "CREATE TABLE IF NOT EXISTS Bookmark
+            (
+               id INTEGER PRIMARY KEY,
+               source TEXT NOT NULL,
+               manga_id TEXT NOT NULL,
+               chapter_id TEXT NOT NULL,
+               page INT NOT NULL,
+               note TEXT,
+               tag TEXT COLLATE NOCASE,
+               created_at INT NOT NULL,
+
+               UNIQUE (source, manga_id, chapter_id, page),
+               FOREIGN KEY (source, manga_id, chapter_id)
+                   REFERENCES Chapters (source, manga_id, id) ON DELETE CASCADE
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_bookmark_tag ON Bookmark (tag);
+            ",
+        )
+        .context("unable to create Bookmark table")?;
+
+        Ok(Self { db })
+    }
+
+    /// Bookmarks a page, or updates the note and tag if it's already
+    /// bookmarked.
+    pub fn add(
+        &self,
+        source: String,
+        manga_id: String,
+        chapter_id: String,
+        page: i32,
+        note: Option<String>,
+        tag: Option<String>,
+    ) -> Result<Bookmark, AppError> {
+        if page < 1 {
+            return Err(AppError::validation("page numbers start at 1"));
+        }
+
+        let id: i64 = self
+            .db
+            .query_row(
+                "INSERT INTO Bookmark (source, manga_id, chapter_id, page, note, tag, created_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                ON CONFLICT (source, manga_id, chapter_id, page)
+                    DO UPDATE SET note = excluded.note, tag = excluded.tag
+                RETURNING id",
+                (
+                    &source,
+                    &manga_id,
+                    &chapter_id,
+                    page,
+                    non_empty(note),
+                    non_empty(tag),
+                    chrono::Utc::now().timestamp_millis(),
+                ),
+                |row| row.get("id"),
+            )
+            .map_err(|err| match err {
+                rusqlite::Error::SqliteFailure(failure, _)
+                    if failure.extended_code == rusqlite::ffi::SQLITE_CONSTRAINT_FOREIGNKEY =>
+                {
+                    AppError::not_found("chapter is not in the library")
+                }
+                err => err.into(),
+            })?;
+
+        self.get(id)?
+            .ok_or_else(|| AppError::internal("bookmark was not saved"))
+    }
+
+    pub fn update(
+        &self,
+        id: i64,
+        note: Option<String>,
+        tag: Option<String>,
+    ) -> Result<usize, rusqlite::Error> {
+        self.db.execute(
+            "UPDATE Bookmark SET note = ?2, tag = ?3 WHERE id = ?1",
+            (id, non_empty(note), non_empty(tag)),
+        )
+    }
+
+    pub fn remove(&self, id: i64) -> Result<usize, rusqlite::Error> {
+        self.db.execute("DELETE FROM Bookmark WHERE id = ?1", [id])
+    }
+
+    pub fn get(&self, id: i64) -> Result<Option<Bookmark>, rusqlite::Error> {
+        self.db
+            .query_row(
+                &format!("{BOOKMARK_SELECT} WHERE Bookmark.id = ?1"),
+                [id],
+                generate_bookmark_from_row,
+            )
+            .optional()
+    }
+
+    /// Every bookmark in a manga, in reading order.
+    pub fn get_for_manga(
+        &self,
+        source: &str,
+        manga_id: &str,
+    ) -> Result<Vec<Bookmark>, rusqlite::Error> {
+        let mut prepared_rows = self.db.prepare(&format!(
+            "{BOOKMARK_SELECT} WHERE Bookmark.source = ?1 AND Bookmark.manga_id = ?2
+            {BOOKMARK_ORDER}"
+        ))?;
+        let iter = prepared_rows.query_map([source, manga_id], generate_bookmark_from_row)?;
+
+        iter.collect()
+    }
+
+    /// Every bookmark in the library, newest first, optionally only those
+    /// with `tag`.
+    pub fn get_all(&self, tag: Option<String>) -> Result<Vec<Bookmark>, rusqlite::Error> {
+        let mut prepared_rows = self.db.prepare(&format!(
+            "{BOOKMARK_SELECT} WHERE ?1 IS NULL OR Bookmark.tag = ?1
+            ORDER BY Bookmark.created_at DESC"
+        ))?;
+        let iter = prepared_rows.query_map([tag], generate_bookmark_from_row)?;
+
+        iter.collect()
+    }
+
+    pub fn get_tags(&self) -> Result<Vec<NameCount>, rusqlite::Error> {
+        let mut prepared_rows = self.db.prepare(
+            "SELECT tag AS name, COUNT(*) AS count FROM Bookmark
+            WHERE tag IS NOT NULL GROUP BY tag ORDER BY tag",
+        )?;
+        let iter = prepared_rows.query_map([], |row| {
+            Ok(NameCount {
+                name: row.get("name")?,
+                count: row.get("count")?,
+            })
+        })?;
+
+        iter.collect()
+    }
+}
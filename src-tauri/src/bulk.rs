@@ -0,0 +1,296 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use rusqlite::{
+    types::Value, vtab::array::load_module, Connection, OptionalExtension, Transaction,
+};
+use serde::Deserialize;
+
+use crate::{
+    db::open,
+    downloadqueue::DownloadQueueDB,
+    errors::{AppError, Context},
+    get_db_path,
+    maintenance::chapter_download_paths,
+    progress::ProgressDB,
+    settings::Settings,
+};
+
+/// Which chapters of a manga a bulk operation applies to.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChapterSelection {
+    Ids(Vec<String>),
+    /// Every numbered chapter from `from` to `to`, inclusive.
+    Range {
+        from: f64,
+        to: f64,
+    },
+}
+
+fn connect() -> Result<Connection, AppError> {
+    // Bulk operations touch progress and the download queue as well as
+    // `Chapters`, so make sure they all exist first.
+    ProgressDB::new(&get_db_path())?;
+    DownloadQueueDB::new(&get_db_path())?;
+
+    let db = open(&get_db_path())?;
+    load_module(&db)?;
+    Ok(db)
+}
+
+fn id_array(ids: &[String]) -> Rc<Vec<Value>> {
+    Rc::new(ids.iter().cloned().map(Value::from).collect())
+}
+
+/// The ids of the selected chapters that are in the library.
+fn select(
+    transaction: &Transaction,
+    source: &str,
+    manga_id: &str,
+    selection: &ChapterSelection,
+) -> Result<Vec<String>, AppError> {
+    let ids = match selection {
+        ChapterSelection::Ids(ids) => {
+            let mut prepared_rows = transaction.prepare(
+                "SELECT id FROM Chapters WHERE source = ?1 AND manga_id = ?2 AND id IN rarray(?3)",
+            )?;
+            let iter =
+                prepared_rows.query_map((source, manga_id, id_array(ids)), |row| row.get("id"))?;
+            iter.collect::<Result<_, _>>()?
+        }
+        ChapterSelection::Range { from, to } => {
+            if from > to {
+                return Err(AppError::validation(
+                    "the start of a chapter range can't be after its end",
+                ));
+            }
+
+            let mut prepared_rows = transaction.prepare(
+                "SELECT id FROM Chapters
+                WHERE source = ?1 AND manga_id = ?2 AND chapter BETWEEN ?3 AND ?4",
+            )?;
+            let iter =
+                prepared_rows.query_map((source, manga_id, from, to), |row| row.get("id"))?;
+            iter.collect::<Result<_, _>>()?
+        }
+    };
+
+    Ok(ids)
+}
+
+fn delete_progress(
+    transaction: &Transaction,
+    source: &str,
+    manga_id: &str,
+    ids: &[String],
+) -> Result<(), rusqlite::Error> {
+    transaction.execute(
+        "DELETE FROM ChapterProgress
+        WHERE source = ?1 AND manga_id = ?2 AND chapter_id IN rarray(?3)",
+        (source, manga_id, id_array(ids)),
+    )?;
+    Ok(())
+}
+
+/// Drops `ids` from the download queue and the downloaded chapters, and
+/// returns the files they were downloaded to, to be removed once the transaction commits.
+fn dequeue_downloads(
+    transaction: &Transaction,
+    source: &str,
+    manga_id: &str,
+    ids: &[String],
+) -> Result<Vec<PathBuf>, AppError> {
+    for table in ["DownloadQueue", "DownloadedChapters"] {
+        transaction.execute(
+            &format!(
+                "DELETE FROM {table}
+                WHERE source = ?1 AND manga_id = ?2 AND chapter_id IN rarray(?3)"
+            ),
+            (source, manga_id, id_array(ids)),
+        )?;
+    }
+
+    let location = Settings {}.get()?.downloads.location;
+    if location.is_empty() {
+        return Ok(vec![]);
+    }
+
+    Ok(ids
+        .iter()
+        .flat_map(|id| chapter_download_paths(Path::new(&location), source, manga_id, id))
+        .filter(|path| path.exists())
+        .collect())
+}
+
+fn remove_files(paths: &[PathBuf]) -> Result<(), AppError> {
+    for path in paths {
+        if path.is_dir() {
+            fs::remove_dir_all(path)
+        } else {
+            fs::remove_file(path)
+        }
+        .context(format!("unable to remove {}", path.display()))?;
+    }
+
+    Ok(())
+}
+
+fn set_read_ids(
+    transaction: &Transaction,
+    source: &str,
+    manga_id: &str,
+    ids: &[String],
+    read: bool,
+) -> Result<Vec<PathBuf>, AppError> {
+    let mut downloads = vec![];
+    if read {
+        // Chapters whose page count isn't known yet are counted as one page.
+        transaction.execute(
+            "UPDATE Chapters SET total = MAX(total, 1), pages = MAX(total, 1),
+                last_read = CASE WHEN last_read = 0 THEN ?4 ELSE last_read END
+            WHERE source = ?1 AND manga_id = ?2 AND id IN rarray(?3)",
+            (
+                source,
+                manga_id,
+                id_array(ids),
+                chrono::Utc::now().timestamp_millis(),
+            ),
+        )?;
+
+        let remove_downloads = Settings {}.get()?.downloads.remove_when_marked_read;
+        if remove_downloads {
+            downloads = dequeue_downloads(transaction, source, manga_id, ids)?;
+        }
+    } else {
+        transaction.execute(
+            "UPDATE Chapters SET pages = 0
+            WHERE source = ?1 AND manga_id = ?2 AND id IN rarray(?3)",
+            (source, manga_id, id_array(ids)),
+        )?;
+        delete_progress(transaction, source, manga_id, ids)?;
+    }
+
+    Ok(downloads)
+}
+
+/// Marks the selected chapters read or unread, returning the ids that were
+/// changed.
+pub fn set_read(
+    source: String,
+    manga_id: String,
+    selection: ChapterSelection,
+    read: bool,
+) -> Result<Vec<String>, AppError> {
+    let mut db = connect()?;
+    let transaction = db.transaction()?;
+
+    let ids = select(&transaction, &source, &manga_id, &selection)?;
+    let downloads = set_read_ids(&transaction, &source, &manga_id, &ids, read)?;
+
+    transaction.commit()?;
+    remove_files(&downloads)?;
+    Ok(ids)
+}
+
+/// Marks every chapter numbered below `chapter_id` as read.
+pub fn mark_previous_read(
+    source: String,
+    manga_id: String,
+    chapter_id: String,
+) -> Result<Vec<String>, AppError> {
+    let mut db = connect()?;
+    let transaction = db.transaction()?;
+
+    let number: Option<f64> = transaction
+        .query_row(
+            "SELECT chapter FROM Chapters WHERE source = ?1 AND manga_id = ?2 AND id = ?3",
+            [&source, &manga_id, &chapter_id],
+            |row| row.get("chapter"),
+        )
+        .optional()?
+        .ok_or_else(|| AppError::not_found("chapter is not in the library"))?;
+    let number = number.ok_or_else(|| {
+        AppError::validation("chapters without a number have no previous chapters")
+    })?;
+
+    let mut prepared_rows = transaction
+        .prepare("SELECT id FROM Chapters WHERE source = ?1 AND manga_id = ?2 AND chapter < ?3")?;
+    let ids = prepared_rows
+        .query_map((&source, &manga_id, number), |row| row.get("id"))?
+        .collect::<Result<Vec<String>, _>>()?;
+    drop(prepared_rows);
+
+    let downloads = set_read_ids(&transaction, &source, &manga_id, &ids, true)?;
+
+    transaction.commit()?;
+    remove_files(&downloads)?;
+    Ok(ids)
+}
+
+/// Clears all reading progress of the selected chapters, including the time
+/// spent reading them.
+pub fn reset_progress(
+    source: String,
+    manga_id: String,
+    selection: ChapterSelection,
+) -> Result<Vec<String>, AppError> {
+    let mut db = connect()?;
+    let transaction = db.transaction()?;
+
+    let ids = select(&transaction, &source, &manga_id, &selection)?;
+    transaction.execute(
+        "UPDATE Chapters SET pages = 0, last_read = 0, time_spent_reading = 0
+        WHERE source = ?1 AND manga_id = ?2 AND id IN rarray(?3)",
+        (&source, &manga_id, id_array(&ids)),
+    )?;
+    delete_progress(&transaction, &source, &manga_id, &ids)?;
+
+    transaction.commit()?;
+    Ok(ids)
+}
+
+/// Adds the selected chapters to the download queue. Chapters that are
+/// already queued keep their place.
+pub fn queue_downloads(
+    source: String,
+    manga_id: String,
+    selection: ChapterSelection,
+) -> Result<Vec<String>, AppError> {
+    let mut db = connect()?;
+    let transaction = db.transaction()?;
+
+    let ids = select(&transaction, &source, &manga_id, &selection)?;
+    let queued_at = chrono::Utc::now().timestamp_millis();
+    for id in &ids {
+        transaction.execute(
+            "INSERT OR IGNORE INTO DownloadQueue (source, manga_id, chapter_id, queued_at)
+            VALUES (?1, ?2, ?3, ?4)",
+            (&source, &manga_id, id, queued_at),
+        )?;
+    }
+
+    transaction.commit()?;
+    Ok(ids)
+}
+
+/// Deletes the downloaded files of the selected chapters, returning the paths
+/// that were removed.
+pub fn delete_downloads(
+    source: String,
+    manga_id: String,
+    selection: ChapterSelection,
+) -> Result<Vec<PathBuf>, AppError> {
+    let mut db = connect()?;
+    let transaction = db.transaction()?;
+
+    let ids = select(&transaction, &source, &manga_id, &selection)?;
+    let downloads = dequeue_downloads(&transaction, &source, &manga_id, &ids)?;
+
+    transaction.commit()?;
+    remove_files(&downloads)?;
+    Ok(downloads)
+}
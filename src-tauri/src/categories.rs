@@ -0,0 +1,96 @@
+use rusqlite::Connection;
+
+use crate::{
+    db::open,
+    errors::{AppError, Context},
+};
+
+/// Puts a manga in a category. Doesn't start a transaction of its own, so
+/// callers can save it along with other changes.
+pub fn add_to_category(
+    db: &Connection,
+    source: &str,
+    id: &str,
+    category: &str,
+) -> Result<usize, rusqlite::Error> {
+    db.execute(
+        "REPLACE INTO MangaCategories (source, id, category) VALUES (?1, ?2, ?3)",
+        [source, id, category],
+    )
+}
+
+pub struct CategoryDB {
+    db: Connection,
+}
+
+impl CategoryDB {
+    pub fn new(path: &Option<std::path::PathBuf>) -> Result<Self, AppError> {
+        let db = open(path)?;
+
+        db.execute(
+            "CREATE TABLE IF NOT EXISTS MangaCategories
+            (
+               source TEXT NOT NULL,
+               id TEXT NOT NULL,
+               category TEXT NOT NULL,
+               PRIMARY KEY (source, id, category)
+            )
+            ",
+            (),
+        )
+        .context("unable to create MangaCategories table")?;
+
+        Ok(Self { db })
+    }
+
+    pub fn add(
+        &self,
+        source: String,
+        id: String,
+        category: String,
+    ) -> Result<usize, rusqlite::Error> {
+        add_to_category(&self.db, &source, &id, &category)
+    }
+
+    pub fn remove(
+        &self,
+        source: String,
+        id: String,
+        category: String,
+    ) -> Result<usize, rusqlite::Error> {
+        self.db.execute(
+            "DELETE FROM MangaCategories WHERE source = ?1 AND id = ?2 AND category = ?3",
+            [source, id, category],
+        )
+    }
+
+    /// The categories `(source, id)` belongs to, in name order.
+    pub fn get_for_manga(
+        &self,
+        source: String,
+        id: String,
+    ) -> Result<Vec<String>, rusqlite::Error> {
+        let mut prepared_rows = self.db.prepare(
+            "SELECT category FROM MangaCategories WHERE source = ?1 AND id = ?2 ORDER BY category",
+        )?;
+        let iter = prepared_rows.query_map([source, id], |row| row.get("category"))?;
+
+        iter.collect()
+    }
+
+    pub fn get_all(&self) -> Result<Vec<String>, rusqlite::Error> {
+        let mut prepared_rows = self
+            .db
+            .prepare("SELECT DISTINCT category FROM MangaCategories ORDER BY category")?;
+        let iter = prepared_rows.query_map([], |row| row.get("category"))?;
+
+        iter.collect()
+    }
+
+    pub fn delete_for_manga(&self, source: String, id: String) -> Result<usize, rusqlite::Error> {
+        self.db.execute(
+            "DELETE FROM MangaCategories WHERE source = ?1 AND id = ?2",
+            [source, id],
+        )
+    }
+}
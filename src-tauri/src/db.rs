@@ -1,8 +1,13 @@
-use std::rc::Rc;
+use std::{cmp::Ordering, rc::Rc};
 
-use rusqlite::{self, vtab::array::load_module, Connection, OptionalExtension, Row};
+use rusqlite::{self, vtab::array::load_module, Connection, OptionalExtension, Params, Row};
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    duplicates::LinkedSourcesDB,
+    errors::{AppError, Context},
+};
+
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(transparent)]
 pub struct Scanlators {
@@ -49,7 +54,62 @@ impl std::fmt::Display for Cover {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+/// Where a series is in its publication.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MangaStatus {
+    #[default]
+    Unknown,
+    Ongoing,
+    Completed,
+    Hiatus,
+    Cancelled,
+}
+
+impl MangaStatus {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Unknown => "unknown",
+            Self::Ongoing => "ongoing",
+            Self::Completed => "completed",
+            Self::Hiatus => "hiatus",
+            Self::Cancelled => "cancelled",
+        }
+    }
+
+    /// Reads a status the way sources and other apps tend to write it.
+    #[must_use]
+    pub fn parse(text: &str) -> Self {
+        match text.trim().to_lowercase().as_str() {
+            "ongoing" | "publishing" | "releasing" => Self::Ongoing,
+            "completed" | "complete" | "finished" => Self::Completed,
+            "hiatus" | "on hiatus" => Self::Hiatus,
+            "cancelled" | "canceled" | "discontinued" => Self::Cancelled,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+impl rusqlite::ToSql for MangaStatus {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        Ok(self.as_str().into())
+    }
+}
+
+impl rusqlite::types::FromSql for MangaStatus {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        value.as_str().map(Self::parse)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MangaLink {
+    pub name: String,
+    pub url: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Manga {
     pub id: String,
     pub name: String,
@@ -63,6 +123,22 @@ pub struct Manga {
 
     pub uploaded: i64,
     pub added: i64,
+
+    #[serde(default)]
+    pub status: MangaStatus,
+    #[serde(default)]
+    pub alt_titles: Vec<String>,
+    #[serde(default)]
+    pub artists: Vec<String>,
+    #[serde(default)]
+    pub demographic: Option<String>,
+    #[serde(default)]
+    pub content_rating: Option<String>,
+    /// The language the series was first published in, e.g. "ja".
+    #[serde(default)]
+    pub original_language: Option<String>,
+    #[serde(default)]
+    pub links: Vec<MangaLink>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -84,10 +160,41 @@ impl std::fmt::Display for Manga {
             tags,
             uploaded,
             added,
+            status,
+            alt_titles,
+            artists,
+            demographic,
+            content_rating,
+            original_language,
+            links,
         } = self;
 
         // TODO: Make this multiline
-        write!(f, "Manga {{\n\tid: {id}\n\tname: {name}\n\tdescription: {description}\n\tsource: {source}\n\tcovers: {covers:?}\n\n\tchapters: {chapters:?}\n\tauthors: {authors:?}\n\ttags: {tags:?}\n\tuploaded: {uploaded}\n\tadded: {added}\n}}")
+        write!(f, "Manga {{\n\tid: {id}\n\tname: {name}\n\tdescription: {description}\n\tsource: {source}\n\tcovers: {covers:?}\n\n\tchapters: {chapters:?}\n\tauthors: {authors:?}\n\ttags: {tags:?}\n\tuploaded: {uploaded}\n\tadded: {added}\n\tstatus: {status:?}\n\talt_titles: {alt_titles:?}\n\tartists: {artists:?}\n\tdemographic: {demographic:?}\n\tcontent_rating: {content_rating:?}\n\toriginal_language: {original_language:?}\n\tlinks: {links:?}\n}}")
+    }
+}
+
+/// Metadata the user set for a library entry in place of what its source
+/// says. Fields left as `None` show the source's value.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MangaOverride {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub authors: Option<Vec<String>>,
+    pub tags: Option<Vec<String>>,
+    pub cover: Option<String>,
+    pub status: Option<MangaStatus>,
+}
+
+impl MangaOverride {
+    const fn is_empty(&self) -> bool {
+        self.name.is_none()
+            && self.description.is_none()
+            && self.authors.is_none()
+            && self.tags.is_none()
+            && self.cover.is_none()
+            && self.status.is_none()
     }
 }
 
@@ -97,13 +204,16 @@ pub struct Chapters {
     pub chapters: Vec<Chapter>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Chapter {
     pub id: String,
     pub manga_id: String,
     pub source: String,
-    pub chapter: i32,
-    pub volume: i32,
+    /// `None` for releases without a number, such as extras and oneshots.
+    pub chapter: Option<f64>,
+    /// What to call the chapter when the number alone doesn't say it, e.g. "Extra".
+    pub label: Option<String>,
+    pub volume: Option<i32>,
 
     pub title: String,
 
@@ -124,6 +234,7 @@ impl std::fmt::Display for Chapter {
             id,
             manga_id,
             chapter,
+            label,
             volume,
             title,
             last_read,
@@ -141,8 +252,9 @@ impl std::fmt::Display for Chapter {
         writeln!(f, "\tid: {id}")?;
         writeln!(f, "\tmanga_id: {manga_id}")?;
         writeln!(f, "\tsource: {source}")?;
-        writeln!(f, "\tchapter: {chapter}")?;
-        writeln!(f, "\tvolume: {volume}")?;
+        writeln!(f, "\tchapter: {chapter:?}")?;
+        writeln!(f, "\tlabel: {label:?}")?;
+        writeln!(f, "\tvolume: {volume:?}")?;
         writeln!(f, "\ttitle: {title}")?;
         writeln!(f, "\tlast_read: {last_read}")?;
         writeln!(f, "\tlast_updated: {last_updated}")?;
@@ -156,6 +268,57 @@ impl std::fmt::Display for Chapter {
     }
 }
 
+impl Chapter {
+    /// Splits a chapter number as sources and older data write it, such as
+    /// "10.5" or "Extra", into a number and a label.
+    #[must_use]
+    pub fn parse_number(text: &str) -> (Option<f64>, Option<String>) {
+        let text = text.trim();
+        match text.parse::<f64>() {
+            Ok(number) if number.is_finite() && number >= 0.0 => (Some(number), None),
+            _ if text.is_empty() => (None, None),
+            _ => (None, Some(text.to_string())),
+        }
+    }
+
+    /// Whether `other` is the same release, e.g. chapter 10.5 from another
+    /// source. Unnumbered chapters only match on their label.
+    #[must_use]
+    pub fn same_number(&self, other: &Self) -> bool {
+        match (self.chapter, other.chapter) {
+            (Some(a), Some(b)) => (a - b).abs() < 1e-6,
+            (None, None) => self.label.as_ref().is_some_and(|label| {
+                other
+                    .label
+                    .as_ref()
+                    .is_some_and(|other| label.eq_ignore_ascii_case(other))
+            }),
+            _ => false,
+        }
+    }
+
+    /// The same order as `CHAPTER_ORDER`.
+    #[must_use]
+    pub fn natural_cmp(&self, other: &Self) -> Ordering {
+        let number = match (self.chapter, other.chapter) {
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        };
+
+        number
+            .then_with(|| {
+                (self.volume.is_none(), self.volume).cmp(&(other.volume.is_none(), other.volume))
+            })
+            .then_with(|| {
+                let label = |chapter: &Self| chapter.label.as_deref().map(str::to_lowercase);
+                label(self).cmp(&label(other))
+            })
+            .then_with(|| self.id.cmp(&other.id))
+    }
+}
+
 pub struct MangaDB {
     db: Connection,
 }
@@ -169,24 +332,179 @@ pub struct DBHandler {
     pub chapter_db: ChapterDB,
 }
 
-fn generate_manga_from_row(row: &Row) -> Result<Manga, rusqlite::Error> {
+/// A row that failed to decode, with the column at fault when rusqlite can
+/// tell which one it was.
+#[derive(Debug, Clone, Serialize)]
+pub struct CorruptRow {
+    pub table: String,
+    pub rowid: i64,
+    pub column: Option<String>,
+    pub reason: String,
+}
+
+impl CorruptRow {
+    #[must_use]
+    pub fn from_error(table: &str, row: &Row, why: &rusqlite::Error) -> Self {
+        let column = match why {
+            rusqlite::Error::FromSqlConversionFailure(index, ..)
+            | rusqlite::Error::IntegralValueOutOfRange(index, _)
+            | rusqlite::Error::InvalidColumnType(index, ..) => row
+                .as_ref()
+                .column_name(*index)
+                .ok()
+                .map(ToString::to_string),
+            _ => None,
+        };
+
+        let reason = match why {
+            rusqlite::Error::FromSqlConversionFailure(_, _, inner) => inner.to_string(),
+            why => why.to_string(),
+        };
+
+        Self {
+            table: table.to_string(),
+            rowid: row.get("row_id").unwrap_or_default(),
+            column,
+            reason,
+        }
+    }
+}
+
+impl std::fmt::Display for CorruptRow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "corrupt row {} in {}", self.rowid, self.table)?;
+        if let Some(column) = &self.column {
+            write!(f, ", column '{column}'")?;
+        }
+
+        write!(f, ": {}", self.reason)
+    }
+}
+
+/// What a listing does when it meets a row it can't decode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeMode {
+    /// Fail the whole listing, naming the row and column.
+    Strict,
+    /// Leave the row out and report it alongside the rows that did decode.
+    SkipCorrupt,
+}
+
+impl DecodeMode {
+    #[must_use]
+    pub fn skip_corrupt(skip: Option<bool>) -> Self {
+        if skip.unwrap_or(false) {
+            Self::SkipCorrupt
+        } else {
+            Self::Strict
+        }
+    }
+}
+
+pub struct Decoded<T> {
+    pub rows: Vec<T>,
+    pub corrupt: Vec<CorruptRow>,
+}
+
+/// Runs `sql` and decodes every row with `decode`. `sql` must select the
+/// table's `rowid` as `row_id` so a failing row can be pointed at.
+pub fn decode_rows<T, P: Params>(
+    db: &Connection,
+    table: &str,
+    sql: &str,
+    params: P,
+    decode: fn(&Row) -> Result<T, rusqlite::Error>,
+    mode: DecodeMode,
+) -> Result<Decoded<T>, AppError> {
+    let mut statement = db.prepare(sql)?;
+    let mut rows = statement.query(params)?;
+    let mut decoded = Decoded {
+        rows: vec![],
+        corrupt: vec![],
+    };
+
+    while let Some(row) = rows.next()? {
+        match decode(row) {
+            Ok(value) => decoded.rows.push(value),
+            Err(why) => {
+                let corrupt = CorruptRow::from_error(table, row, &why);
+                if mode == DecodeMode::Strict {
+                    return Err(AppError::parse(corrupt.to_string()));
+                }
+
+                log::warn!("skipping {corrupt}");
+                decoded.corrupt.push(corrupt);
+            }
+        }
+    }
+
+    Ok(decoded)
+}
+
+/// Opens the database at `path`, or an in-memory one if there is no path,
+/// with foreign key enforcement on.
+pub fn open(path: &Option<std::path::PathBuf>) -> Result<Connection, AppError> {
+    let db = path
+        .as_ref()
+        .map_or_else(Connection::open_in_memory, Connection::open)
+        .context("unable to open database")?;
+    db.execute_batch("PRAGMA foreign_keys = ON")?;
+
+    Ok(db)
+}
+
+/// Reads a column holding JSON, failing the row instead of panicking when the
+/// stored JSON doesn't decode.
+pub fn json_column<T: serde::de::DeserializeOwned>(
+    row: &Row,
+    column: &str,
+) -> Result<T, rusqlite::Error> {
+    serde_json::from_str(row.get::<&str, String>(column)?.as_str()).map_err(|why| {
+        rusqlite::Error::FromSqlConversionFailure(
+            row.as_ref().column_index(column).unwrap_or_default(),
+            rusqlite::types::Type::Text,
+            Box::new(why),
+        )
+    })
+}
+
+/// `json_column` for a column that may be `NULL`.
+pub fn optional_json_column<T: serde::de::DeserializeOwned>(
+    row: &Row,
+    column: &str,
+) -> Result<Option<T>, rusqlite::Error> {
+    match row.get::<&str, Option<String>>(column)? {
+        Some(_) => json_column(row, column).map(Some),
+        None => Ok(None),
+    }
+}
+
+pub fn generate_manga_from_row(row: &Row) -> Result<Manga, rusqlite::Error> {
     Ok(Manga {
         id: row.get("id")?,
         name: row.get("name")?,
         description: row.get("description")?,
         source: row.get("source")?,
 
-        covers: serde_json::from_str(row.get::<&str, String>("covers")?.as_str()).unwrap(),
-        authors: serde_json::from_str(row.get::<&str, String>("authors")?.as_str()).unwrap(),
-        chapters: serde_json::from_str(row.get::<&str, String>("chapters")?.as_str()).unwrap(),
-        tags: serde_json::from_str(row.get::<&str, String>("tags")?.as_str()).unwrap(),
+        covers: json_column(row, "covers")?,
+        authors: json_column(row, "authors")?,
+        chapters: json_column(row, "chapters")?,
+        tags: json_column(row, "tags")?,
 
         uploaded: row.get("uploaded")?,
         added: row.get("added")?,
+
+        status: row.get("status")?,
+        alt_titles: json_column(row, "alt_titles")?,
+        artists: json_column(row, "artists")?,
+        demographic: row.get("demographic")?,
+        content_rating: row.get("content_rating")?,
+        original_language: row.get("original_language")?,
+        links: json_column(row, "links")?,
     })
 }
 
-fn generate_chapter_from_row(row: &Row) -> Result<Chapter, rusqlite::Error> {
+pub fn generate_chapter_from_row(row: &Row) -> Result<Chapter, rusqlite::Error> {
     Ok(Chapter {
         id: row.get("id")?,
         manga_id: row.get("manga_id")?,
@@ -194,8 +512,9 @@ fn generate_chapter_from_row(row: &Row) -> Result<Chapter, rusqlite::Error> {
 
         title: row.get::<&str, String>("title")?,
 
-        chapter: row.get::<&str, i32>("chapter")?,
-        volume: row.get::<&str, i32>("volume")?,
+        chapter: row.get::<&str, Option<f64>>("chapter")?,
+        label: row.get::<&str, Option<String>>("label")?,
+        volume: row.get::<&str, Option<i32>>("volume")?,
 
         last_read: row.get::<&str, i64>("last_read")?,
         date_uploaded: row.get::<&str, i64>("date_uploaded")?,
@@ -206,95 +525,635 @@ fn generate_chapter_from_row(row: &Row) -> Result<Chapter, rusqlite::Error> {
         total: row.get::<&str, i32>("total")?,
 
         lang: row.get::<&str, String>("lang")?,
-        scanlators: serde_json::from_str(row.get::<&str, String>("scanlators")?.as_str())
-            .unwrap_or_default(),
+        scanlators: json_column(row, "scanlators")?,
     })
 }
 
-impl MangaDB {
-    #[must_use]
-    pub fn new(path: &Option<std::path::PathBuf>) -> Self {
-        // Make tables if not present.
-        // let db = if path.is_none() { Connection::open_in_memory().expect("unable to open in-memory database") }
-        //        else { Connection::open(path.unwrap()).expect("unable to open database") };
-
-        let db = path.as_ref().map_or_else(
-            || Connection::open_in_memory().expect("unable to open in-memory database"),
-            |p| {
-                let p2 = &p.clone();
-                Connection::open(p2).unwrap_or_else(|_| {
-                    panic!(
-                        "unable to open database from path {}",
-                        &p2.to_str().unwrap()
-                    )
-                })
-            },
-        );
-
-        match db.execute(
-            "
-                         CREATE TABLE IF NOT EXISTS Library
-                         (
-                             id TEXT NOT NULL,
-                             name TEXT NOT NULL,
-                             source TEXT NOT NULL,
-                             covers TEXT NOT NULL,
-
-                             chapters TEXT NOT NULL,
-                             description TEXT NOT NULL,
-                             authors TEXT NOT NULL,
-                             tags TEXT NOT NULL,
-
-                             uploaded INT NOT NULL,
-                             added INT NOT NULL
-                        )
-                         ",
-            [],
-        ) {
-            Ok(_) => println!("Table was created."),
-            Err(why) => println!("Failed: {why}"),
+/// The schema version `create_library_schema` brings the database up to,
+/// stored in `PRAGMA user_version`.
+pub const SCHEMA_VERSION: i64 = 6;
+
+const LIBRARY_SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS Library
+    (
+        id TEXT NOT NULL,
+        name TEXT NOT NULL,
+        source TEXT NOT NULL,
+        covers TEXT NOT NULL,
+
+        chapters TEXT NOT NULL,
+        description TEXT NOT NULL,
+        authors TEXT NOT NULL,
+        tags TEXT NOT NULL,
+
+        uploaded INT NOT NULL,
+        added INT NOT NULL,
+        last_checked INT NOT NULL DEFAULT 0,
+
+        status TEXT NOT NULL DEFAULT 'unknown',
+        alt_titles TEXT NOT NULL DEFAULT '[]',
+        artists TEXT NOT NULL DEFAULT '[]',
+        demographic TEXT,
+        content_rating TEXT,
+        original_language TEXT,
+        links TEXT NOT NULL DEFAULT '[]',
+
+        PRIMARY KEY (source, id)
+    );
+
+    CREATE TABLE IF NOT EXISTS Chapters
+    (
+        id                 TEXT NOT NULL,
+        manga_id           TEXT NOT NULL,
+        source             TEXT NOT NULL,
+        chapter            REAL,
+        label              TEXT,
+        volume             INT,
+
+        title              TEXT NOT NULL,
+
+        last_updated       INT NOT NULL,
+        last_read          INT NOT NULL,
+        time_spent_reading INT NOT NULL,
+        date_uploaded      INT NOT NULL,
+
+        pages              INT NOT NULL,
+        total              INT NOT NULL,
+        scanlators         TEXT NOT NULL,
+        lang               TEXT NOT NULL,
+        added              INT NOT NULL DEFAULT 0,
+
+        PRIMARY KEY (source, manga_id, id),
+        FOREIGN KEY (source, manga_id) REFERENCES Library (source, id) ON DELETE CASCADE
+    );
+
+    CREATE TABLE IF NOT EXISTS Author
+    (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL UNIQUE COLLATE NOCASE
+    );
+
+    CREATE TABLE IF NOT EXISTS MangaAuthor
+    (
+        source TEXT NOT NULL,
+        manga_id TEXT NOT NULL,
+        author_id INT NOT NULL,
+
+        PRIMARY KEY (source, manga_id, author_id),
+        FOREIGN KEY (source, manga_id) REFERENCES Library (source, id) ON DELETE CASCADE,
+        FOREIGN KEY (author_id) REFERENCES Author (id) ON DELETE CASCADE
+    );
+
+    CREATE TABLE IF NOT EXISTS Tag
+    (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL UNIQUE COLLATE NOCASE
+    );
+
+    CREATE TABLE IF NOT EXISTS MangaTag
+    (
+        source TEXT NOT NULL,
+        manga_id TEXT NOT NULL,
+        tag_id INT NOT NULL,
+
+        PRIMARY KEY (source, manga_id, tag_id),
+        FOREIGN KEY (source, manga_id) REFERENCES Library (source, id) ON DELETE CASCADE,
+        FOREIGN KEY (tag_id) REFERENCES Tag (id) ON DELETE CASCADE
+    );
+
+    CREATE TABLE IF NOT EXISTS Scanlator
+    (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL UNIQUE COLLATE NOCASE
+    );
+
+    CREATE TABLE IF NOT EXISTS ChapterScanlator
+    (
+        source TEXT NOT NULL,
+        manga_id TEXT NOT NULL,
+        chapter_id TEXT NOT NULL,
+        scanlator_id INT NOT NULL,
+
+        PRIMARY KEY (source, manga_id, chapter_id, scanlator_id),
+        FOREIGN KEY (source, manga_id, chapter_id)
+            REFERENCES Chapters (source, manga_id, id) ON DELETE CASCADE,
+        FOREIGN KEY (scanlator_id) REFERENCES Scanlator (id) ON DELETE CASCADE
+    );
+
+    CREATE TABLE IF NOT EXISTS MangaOverrides
+    (
+        source TEXT NOT NULL,
+        id TEXT NOT NULL,
+
+        name TEXT,
+        description TEXT,
+        authors TEXT,
+        tags TEXT,
+        cover TEXT,
+        status TEXT,
+
+        PRIMARY KEY (source, id),
+        FOREIGN KEY (source, id) REFERENCES Library (source, id) ON DELETE CASCADE
+    );
+
+    CREATE VIEW IF NOT EXISTS EffectiveLibrary AS
+    SELECT Library.rowid AS row_id, Library.id, Library.source,
+        COALESCE(MangaOverrides.name, Library.name) AS name,
+        COALESCE(MangaOverrides.description, Library.description) AS description,
+        CASE WHEN MangaOverrides.cover IS NULL THEN Library.covers
+            ELSE json_array(MangaOverrides.cover) END AS covers,
+        Library.chapters,
+        COALESCE(MangaOverrides.authors, Library.authors) AS authors,
+        COALESCE(MangaOverrides.tags, Library.tags) AS tags,
+        Library.uploaded, Library.added, Library.last_checked,
+        COALESCE(MangaOverrides.status, Library.status) AS status,
+        Library.alt_titles, Library.artists, Library.demographic, Library.content_rating,
+        Library.original_language, Library.links
+    FROM Library LEFT JOIN MangaOverrides
+        ON MangaOverrides.source = Library.source AND MangaOverrides.id = Library.id;
+
+    CREATE INDEX IF NOT EXISTS idx_library_name ON Library (name);
+    CREATE INDEX IF NOT EXISTS idx_library_added ON Library (added);
+    CREATE INDEX IF NOT EXISTS idx_chapters_manga_id ON Chapters (manga_id, id);
+    CREATE INDEX IF NOT EXISTS idx_chapters_last_read ON Chapters (last_read);
+    CREATE INDEX IF NOT EXISTS idx_manga_author_author ON MangaAuthor (author_id);
+    CREATE INDEX IF NOT EXISTS idx_manga_tag_tag ON MangaTag (tag_id);
+";
+
+/// Leaves out manga merged into another entry, which only stay in `Library`
+/// as the parents of their chapters.
+pub const VISIBLE_MANGA: &str = "NOT EXISTS (SELECT 1 FROM LinkedSources
+    WHERE linked_source = Library.source AND linked_id = Library.id)";
+
+const LIBRARY_COLUMNS: &str =
+    "id, name, source, covers, chapters, description, authors, tags, uploaded, added";
+
+/// Every `Chapters` column that predates version 3, apart from the numbers.
+const CHAPTER_COLUMNS: &str = "id, manga_id, source, title, last_updated, last_read, \
+     time_spent_reading, date_uploaded, pages, total, scanlators, lang";
+
+/// Reads the integer numbers of a table from before version 3, where unknown
+/// chapters were negative and a volume of 0 meant there was none.
+const INTEGER_CHAPTER_NUMBERS: &str =
+    "CASE WHEN chapter >= 0 THEN chapter END, CASE WHEN volume > 0 THEN volume END";
+
+/// Natural reading order: by number then volume, with unnumbered chapters
+/// such as extras last by label.
+const CHAPTER_ORDER: &str = "ORDER BY chapter IS NULL, chapter, volume IS NULL, volume, \
+     label COLLATE NOCASE, id";
+
+fn table_exists(db: &Connection, table: &str) -> Result<bool, rusqlite::Error> {
+    db.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        [table],
+        |row| row.get::<usize, i64>(0),
+    )
+    .map(|count| count > 0)
+}
+
+/// Version 1 gives `Library` and `Chapters` real primary keys. Before it,
+/// `REPLACE INTO Library` appended a new row on every save, so only the most
+/// recently written row for each key is kept.
+fn migrate_keys(db: &Connection) -> Result<(), AppError> {
+    let mut renamed = vec![];
+    for table in ["Library", "Chapters"] {
+        if table_exists(db, table)? {
+            db.execute(&format!("ALTER TABLE {table} RENAME TO {table}Unkeyed"), [])?;
+            renamed.push(table);
         }
+    }
+
+    db.execute_batch(LIBRARY_SCHEMA)?;
 
-        Self { db }
+    if renamed.contains(&"Library") {
+        db.execute(
+            &format!(
+                "INSERT INTO Library ({LIBRARY_COLUMNS}) SELECT {LIBRARY_COLUMNS} FROM LibraryUnkeyed
+                WHERE rowid IN (SELECT MAX(rowid) FROM LibraryUnkeyed GROUP BY source, id)"
+            ),
+            [],
+        )?;
     }
 
-    pub fn insert(&self, manga: Manga) -> Result<usize, rusqlite::Error> {
-        let Manga {
-            id,
+    if renamed.contains(&"Chapters") {
+        db.execute(
+            &format!(
+                "INSERT INTO Chapters ({CHAPTER_COLUMNS}, chapter, volume)
+                SELECT {CHAPTER_COLUMNS}, {INTEGER_CHAPTER_NUMBERS} FROM ChaptersUnkeyed
+                WHERE rowid IN (SELECT MAX(rowid) FROM ChaptersUnkeyed GROUP BY source, manga_id, id)"
+            ),
+            [],
+        )?;
+    }
+
+    for table in renamed {
+        db.execute(&format!("DROP TABLE {table}Unkeyed"), [])?;
+    }
+
+    Ok(())
+}
+
+/// Author, tag or scanlator name with the number of manga or chapters using it.
+#[derive(Debug, Serialize)]
+pub struct NameCount {
+    pub name: String,
+    pub count: i64,
+}
+
+/// Returns the id of `name` in `table`, adding it if it isn't there yet.
+fn name_id(db: &Connection, table: &str, name: &str) -> Result<i64, rusqlite::Error> {
+    db.execute(
+        &format!("INSERT INTO {table} (name) VALUES (?1) ON CONFLICT (name) DO NOTHING"),
+        [name],
+    )?;
+    db.query_row(
+        &format!("SELECT id FROM {table} WHERE name = ?1"),
+        [name],
+        |row| row.get(0),
+    )
+}
+
+fn clean_names(names: &[String]) -> impl Iterator<Item = &str> {
+    names
+        .iter()
+        .map(|name| name.trim())
+        .filter(|name| !name.is_empty())
+}
+
+/// Indexes the authors and tags a manga shows, overrides included.
+pub fn index_effective_manga(
+    db: &Connection,
+    source: &str,
+    id: &str,
+) -> Result<(), rusqlite::Error> {
+    let (authors, tags): (Vec<String>, Vec<String>) = db.query_row(
+        "SELECT authors, tags FROM EffectiveLibrary WHERE source = ?1 AND id = ?2",
+        [source, id],
+        |row| Ok((json_column(row, "authors")?, json_column(row, "tags")?)),
+    )?;
+
+    index_manga(db, source, id, &authors, &tags)
+}
+
+/// Replaces the author and tag links of a manga with `authors` and `tags`.
+fn index_manga(
+    db: &Connection,
+    source: &str,
+    id: &str,
+    authors: &[String],
+    tags: &[String],
+) -> Result<(), rusqlite::Error> {
+    db.execute(
+        "DELETE FROM MangaAuthor WHERE source = ?1 AND manga_id = ?2",
+        [source, id],
+    )?;
+    for author in clean_names(authors) {
+        db.execute(
+            "INSERT OR IGNORE INTO MangaAuthor (source, manga_id, author_id) VALUES (?1, ?2, ?3)",
+            (source, id, name_id(db, "Author", author)?),
+        )?;
+    }
+
+    db.execute(
+        "DELETE FROM MangaTag WHERE source = ?1 AND manga_id = ?2",
+        [source, id],
+    )?;
+    for tag in clean_names(tags) {
+        db.execute(
+            "INSERT OR IGNORE INTO MangaTag (source, manga_id, tag_id) VALUES (?1, ?2, ?3)",
+            (source, id, name_id(db, "Tag", tag)?),
+        )?;
+    }
+
+    Ok(())
+}
+
+fn index_chapter(
+    db: &Connection,
+    source: &str,
+    manga_id: &str,
+    id: &str,
+    scanlators: &[String],
+) -> Result<(), rusqlite::Error> {
+    db.execute(
+        "DELETE FROM ChapterScanlator WHERE source = ?1 AND manga_id = ?2 AND chapter_id = ?3",
+        [source, manga_id, id],
+    )?;
+    for scanlator in clean_names(scanlators) {
+        db.execute(
+            "INSERT OR IGNORE INTO ChapterScanlator
+                (source, manga_id, chapter_id, scanlator_id) VALUES (?1, ?2, ?3, ?4)",
+            (source, manga_id, id, name_id(db, "Scanlator", scanlator)?),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Rebuilds every author, tag and scanlator link from the JSON columns.
+/// Columns that don't decode are indexed as empty.
+fn index_all_metadata(db: &Connection) -> Result<(), rusqlite::Error> {
+    db.execute_batch(
+        "DELETE FROM MangaAuthor;
+        DELETE FROM MangaTag;
+        DELETE FROM ChapterScanlator;",
+    )?;
+
+    let list = |row: &Row, column: &str| -> Result<Vec<String>, rusqlite::Error> {
+        Ok(serde_json::from_str(&row.get::<&str, String>(column)?).unwrap_or_default())
+    };
+
+    let mut prepared_rows = db.prepare("SELECT source, id, authors, tags FROM EffectiveLibrary")?;
+    let manga = prepared_rows
+        .query_map([], |row| {
+            Ok((
+                row.get::<&str, String>("source")?,
+                row.get::<&str, String>("id")?,
+                list(row, "authors")?,
+                list(row, "tags")?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    for (source, id, authors, tags) in manga {
+        index_manga(db, &source, &id, &authors, &tags)?;
+    }
+
+    let mut prepared_rows = db.prepare("SELECT source, manga_id, id, scanlators FROM Chapters")?;
+    let chapters = prepared_rows
+        .query_map([], |row| {
+            Ok((
+                row.get::<&str, String>("source")?,
+                row.get::<&str, String>("manga_id")?,
+                row.get::<&str, String>("id")?,
+                list(row, "scanlators")?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    for (source, manga_id, id, scanlators) in chapters {
+        index_chapter(db, &source, &manga_id, &id, &scanlators)?;
+    }
+
+    Ok(())
+}
+
+/// Rebuilds the author, tag and scanlator tables after rows were written
+/// without going through `MangaDB::insert` or `ChapterDB::insert`.
+pub fn rebuild_metadata_index(db: &Connection) -> Result<(), AppError> {
+    let transaction = db.unchecked_transaction()?;
+    index_all_metadata(&transaction).context("unable to rebuild the metadata index")?;
+    transaction.commit()?;
+
+    Ok(())
+}
+
+/// Version 3 stores chapter numbers as decimals with an optional label, and
+/// makes volumes optional. Relies on `legacy_alter_table` so the rename
+/// leaves `ChapterScanlator` pointing at `Chapters`.
+fn migrate_chapter_numbers(db: &Connection) -> Result<(), AppError> {
+    db.execute_batch(
+        "DROP INDEX IF EXISTS idx_chapters_manga_id;
+        DROP INDEX IF EXISTS idx_chapters_last_read;
+        ALTER TABLE Chapters RENAME TO ChaptersUnnumbered;",
+    )?;
+    db.execute_batch(LIBRARY_SCHEMA)?;
+    db.execute(
+        &format!(
+            "INSERT INTO Chapters ({CHAPTER_COLUMNS}, chapter, volume)
+            SELECT {CHAPTER_COLUMNS}, {INTEGER_CHAPTER_NUMBERS} FROM ChaptersUnnumbered"
+        ),
+        [],
+    )?;
+    db.execute("DROP TABLE ChaptersUnnumbered", [])?;
+
+    Ok(())
+}
+
+/// Adds the columns version 4 uses to tell which chapters are new. Chapters
+/// that were already stored count as old.
+fn migrate_new_chapter_columns(db: &Connection, version: i64) -> Result<(), AppError> {
+    db.execute(
+        "ALTER TABLE Library ADD COLUMN last_checked INT NOT NULL DEFAULT 0",
+        [],
+    )?;
+    // Before version 3 `Chapters` was just rebuilt with the column.
+    if version >= 3 {
+        db.execute(
+            "ALTER TABLE Chapters ADD COLUMN added INT NOT NULL DEFAULT 0",
+            [],
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Adds the publication status and the rest of the metadata version 6
+/// stores for each manga.
+fn migrate_extended_metadata(db: &Connection) -> Result<(), AppError> {
+    db.execute_batch(
+        "ALTER TABLE Library ADD COLUMN status TEXT NOT NULL DEFAULT 'unknown';
+        ALTER TABLE Library ADD COLUMN alt_titles TEXT NOT NULL DEFAULT '[]';
+        ALTER TABLE Library ADD COLUMN artists TEXT NOT NULL DEFAULT '[]';
+        ALTER TABLE Library ADD COLUMN demographic TEXT;
+        ALTER TABLE Library ADD COLUMN content_rating TEXT;
+        ALTER TABLE Library ADD COLUMN original_language TEXT;
+        ALTER TABLE Library ADD COLUMN links TEXT NOT NULL DEFAULT '[]';",
+    )?;
+
+    Ok(())
+}
+
+/// Creates `Library` and `Chapters`, migrating an older schema first.
+fn create_library_schema(db: &Connection) -> Result<(), AppError> {
+    let version: i64 = db.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version >= SCHEMA_VERSION {
+        return Ok(());
+    }
+
+    // Foreign keys have to be off while tables are rebuilt, and the pragma is
+    // a no-op inside a transaction.
+    db.execute_batch(
+        "PRAGMA foreign_keys = OFF;
+        PRAGMA legacy_alter_table = ON;",
+    )?;
+    let result: Result<(), AppError> = (|| {
+        let transaction =
+            rusqlite::Transaction::new_unchecked(db, rusqlite::TransactionBehavior::Immediate)?;
+
+        // Another connection may have migrated while this one waited.
+        let version: i64 = transaction.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        // Recreated by `LIBRARY_SCHEMA` with whatever columns `Library` ends
+        // up with.
+        transaction.execute("DROP VIEW IF EXISTS EffectiveLibrary", [])?;
+        if version < 1 {
+            migrate_keys(&transaction)?;
+        } else {
+            if version < 3 {
+                migrate_chapter_numbers(&transaction)?;
+            }
+            if version < 4 {
+                migrate_new_chapter_columns(&transaction, version)?;
+            }
+            if version < 6 {
+                migrate_extended_metadata(&transaction)?;
+            }
+        }
+
+        transaction.execute_batch(LIBRARY_SCHEMA)?;
+        if version < 2 {
+            index_all_metadata(&transaction)?;
+        }
+
+        transaction.execute_batch(&format!("PRAGMA user_version = {SCHEMA_VERSION}"))?;
+        transaction.commit()?;
+
+        Ok(())
+    })();
+    db.execute_batch(
+        "PRAGMA legacy_alter_table = OFF;
+        PRAGMA foreign_keys = ON;",
+    )?;
+
+    result.context("unable to migrate the library schema")
+}
+
+/// A library entry as stored from its source, without the user's overrides.
+pub fn get_source_manga(
+    db: &Connection,
+    source: &str,
+    id: &str,
+) -> Result<Option<Manga>, rusqlite::Error> {
+    db.query_row(
+        "SELECT * FROM Library WHERE source = ?1 AND id = ?2",
+        [source, id],
+        generate_manga_from_row,
+    )
+    .optional()
+}
+
+fn get_override(
+    db: &Connection,
+    source: &str,
+    id: &str,
+) -> Result<Option<MangaOverride>, rusqlite::Error> {
+    db.query_row(
+        "SELECT * FROM MangaOverrides WHERE source = ?1 AND id = ?2",
+        [source, id],
+        |row| {
+            Ok(MangaOverride {
+                name: row.get("name")?,
+                description: row.get("description")?,
+                authors: optional_json_column(row, "authors")?,
+                tags: optional_json_column(row, "tags")?,
+                cover: row.get("cover")?,
+                status: row.get("status")?,
+            })
+        },
+    )
+    .optional()
+}
+
+/// Saves a library entry and indexes its metadata. Doesn't start a
+/// transaction of its own, so callers can save it along with other changes.
+///
+/// `manga` is metadata as the source provides it. Entries read through
+/// `MangaDB::get` and friends have their overrides applied and must not be
+/// saved back; overrides are changed with `MangaDB::set_override` and library
+/// membership with `MangaDB::set_added`.
+pub fn upsert_manga(db: &Connection, manga: Manga) -> Result<usize, rusqlite::Error> {
+    let Manga {
+        id,
+        name,
+        source,
+        description,
+        authors,
+        tags,
+        covers,
+        chapters,
+        uploaded,
+        added,
+        status,
+        alt_titles,
+        artists,
+        demographic,
+        content_rating,
+        original_language,
+        links,
+    } = manga;
+
+    // An upsert rather than `REPLACE`, which would delete the row and
+    // cascade to its chapters.
+    let changed = db.execute(
+        "INSERT INTO Library
+                (id, name, source, covers, chapters, uploaded, added, description, authors, tags,
+                status, alt_titles, artists, demographic, content_rating, original_language, links,
+                last_checked)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17,
+                ?18)
+            ON CONFLICT (source, id) DO UPDATE SET
+                name = excluded.name,
+                covers = excluded.covers,
+                chapters = excluded.chapters,
+                uploaded = excluded.uploaded,
+                added = excluded.added,
+                description = excluded.description,
+                authors = excluded.authors,
+                tags = excluded.tags,
+                status = excluded.status,
+                alt_titles = excluded.alt_titles,
+                artists = excluded.artists,
+                demographic = excluded.demographic,
+                content_rating = excluded.content_rating,
+                original_language = excluded.original_language,
+                links = excluded.links",
+        rusqlite::params![
+            &id,
             name,
-            source,
-            description,
-            authors,
-            tags,
-            covers,
-            chapters,
+            &source,
+            serde_json::to_string(&covers).unwrap(),
+            serde_json::to_string(&chapters).unwrap(),
             uploaded,
             added,
-        } = manga;
+            description,
+            serde_json::to_string(&authors).unwrap(),
+            serde_json::to_string(&tags).unwrap(),
+            status,
+            serde_json::to_string(&alt_titles).unwrap(),
+            serde_json::to_string(&artists).unwrap(),
+            demographic,
+            content_rating,
+            original_language,
+            serde_json::to_string(&links).unwrap(),
+            // The chapters a new entry is saved with aren't new to the user.
+            chrono::Utc::now().timestamp_millis(),
+        ],
+    )?;
+    index_effective_manga(db, &source, &id)?;
+
+    Ok(changed)
+}
 
-        self.db.execute(
-            "REPLACE INTO Library
-                    (id, name, source, covers, chapters, uploaded, added, description, authors, tags)
-                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
-            (
-                id,
-                name,
-                source,
-                serde_json::to_string(&covers).unwrap(),
-                serde_json::to_string(&chapters).unwrap(),
-                uploaded,
-                added,
-                description,
-                serde_json::to_string(&authors).unwrap(),
-                serde_json::to_string(&tags).unwrap(),
-            ),
-        )
+impl MangaDB {
+    pub fn new(path: &Option<std::path::PathBuf>) -> Result<Self, AppError> {
+        // Listings hide manga merged into another entry, so the links have to exist.
+        LinkedSourcesDB::new(path)?;
+        let db = open(path)?;
+        create_library_schema(&db)?;
+
+        Ok(Self { db })
+    }
+
+    pub fn insert(&self, manga: Manga) -> Result<usize, rusqlite::Error> {
+        let transaction = self.db.unchecked_transaction()?;
+        let changed = upsert_manga(&transaction, manga)?;
+        transaction.commit()?;
+
+        Ok(changed)
     }
 
     pub fn delete(&self, id: String, source: String) -> Result<Option<usize>, rusqlite::Error> {
         self.db
             .execute(
-                "DELETE * FROM LIBRARY WHERE id = ?1 AND source = ?2 ",
+                "DELETE FROM Library WHERE id = ?1 AND source = ?2",
                 [id, source],
             )
             .optional()
@@ -303,7 +1162,7 @@ impl MangaDB {
     pub fn get(&self, id: String, source: String) -> Result<Option<Manga>, rusqlite::Error> {
         self.db
             .query_row(
-                "SELECT * FROM Library WHERE id = ?1 AND source = ?2",
+                "SELECT * FROM EffectiveLibrary WHERE id = ?1 AND source = ?2",
                 [id, source],
                 generate_manga_from_row,
             )
@@ -314,41 +1173,204 @@ impl MangaDB {
         &self,
         source: String,
         ids: Vec<String>,
-    ) -> Result<std::vec::Vec<Manga>, rusqlite::Error> {
-        load_module(&self.db).unwrap();
-        let mut prepared_rows = self
-            .db
-            .prepare("SELECT * FROM Library where source = ?1 AND id IN rarray(?2)")?;
+        mode: DecodeMode,
+    ) -> Result<Decoded<Manga>, AppError> {
+        load_module(&self.db)?;
         let values_iter: Vec<rusqlite::types::Value> =
             ids.into_iter().map(rusqlite::types::Value::from).collect();
-        let iter = prepared_rows.query_map((source, Rc::new(values_iter)), |row| {
-            generate_manga_from_row(row)
-        })?;
 
-        Ok(iter
-            .map(std::result::Result::unwrap)
-            .collect::<Vec<Manga>>())
+        decode_rows(
+            &self.db,
+            "Library",
+            "SELECT * FROM EffectiveLibrary WHERE source = ?1 AND id IN rarray(?2)",
+            (source, Rc::new(values_iter)),
+            generate_manga_from_row,
+            mode,
+        )
+    }
+
+    /// Manga merged into another entry are left out; they only stay around as
+    /// the parents of their chapters.
+    pub fn get_all(
+        &self,
+        source: Option<String>,
+        mode: DecodeMode,
+    ) -> Result<Decoded<Manga>, AppError> {
+        decode_rows(
+            &self.db,
+            "Library",
+            &format!(
+                "SELECT * FROM EffectiveLibrary AS Library
+                WHERE (?1 IS NULL OR source = ?1) AND {VISIBLE_MANGA}"
+            ),
+            [source],
+            generate_manga_from_row,
+            mode,
+        )
+    }
+
+    /// Adds a manga to the library at `added`, or takes it out with -1,
+    /// without touching its stored metadata.
+    pub fn set_added(&self, source: &str, id: &str, added: i64) -> Result<usize, rusqlite::Error> {
+        self.db.execute(
+            "UPDATE Library SET added = ?3 WHERE source = ?1 AND id = ?2",
+            (source, id, added),
+        )
     }
 
-    pub fn get_all(&self, source: Option<String>) -> Result<Vec<Manga>, rusqlite::Error> {
-        // FIXME: this shit
-        // oh lord. here we go again.
+    /// Records that the chapters of a manga were looked at, so the ones
+    /// stored until now stop counting as new.
+    pub fn set_checked(&self, source: &str, id: &str) -> Result<usize, rusqlite::Error> {
+        self.db.execute(
+            "UPDATE Library SET last_checked = ?3 WHERE source = ?1 AND id = ?2",
+            (source, id, chrono::Utc::now().timestamp_millis()),
+        )
+    }
 
-        if let Some(source) = source {
-            let mut prepared_rows = self.db.prepare("SELECT * FROM Library WHERE source = ?1")?;
-            let iter = prepared_rows.query_map([source], generate_manga_from_row)?;
+    pub fn get_override(
+        &self,
+        source: &str,
+        id: &str,
+    ) -> Result<Option<MangaOverride>, rusqlite::Error> {
+        get_override(&self.db, source, id)
+    }
 
-            Ok(iter
-                .map(std::result::Result::unwrap)
-                .collect::<Vec<Manga>>())
-        } else {
-            let mut prepared_rows = self.db.prepare("SELECT * FROM Library")?;
-            let iter = prepared_rows.query_map([], generate_manga_from_row)?;
+    /// Replaces the metadata overrides of an entry. Overriding nothing is the
+    /// same as resetting it to what its source says.
+    pub fn set_override(
+        &self,
+        source: &str,
+        id: &str,
+        overrides: MangaOverride,
+    ) -> Result<(), AppError> {
+        if overrides.is_empty() {
+            return self.reset_override(source, id);
+        }
 
-            Ok(iter
-                .map(std::result::Result::unwrap)
-                .collect::<Vec<Manga>>())
+        let transaction = self.db.unchecked_transaction()?;
+        transaction
+            .execute(
+                "REPLACE INTO MangaOverrides
+                    (source, id, name, description, authors, tags, cover, status)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                (
+                    source,
+                    id,
+                    overrides.name,
+                    overrides.description,
+                    overrides
+                        .authors
+                        .map(|authors| serde_json::to_string(&authors).unwrap()),
+                    overrides
+                        .tags
+                        .map(|tags| serde_json::to_string(&tags).unwrap()),
+                    overrides.cover,
+                    overrides.status,
+                ),
+            )
+            .map_err(|err| match err {
+                rusqlite::Error::SqliteFailure(failure, _)
+                    if failure.extended_code == rusqlite::ffi::SQLITE_CONSTRAINT_FOREIGNKEY =>
+                {
+                    AppError::not_found("manga is not in the library")
+                }
+                err => err.into(),
+            })?;
+        index_effective_manga(&transaction, source, id)?;
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    /// Drops every override of an entry so it shows its source's metadata.
+    pub fn reset_override(&self, source: &str, id: &str) -> Result<(), AppError> {
+        let transaction = self.db.unchecked_transaction()?;
+        transaction.execute(
+            "DELETE FROM MangaOverrides WHERE source = ?1 AND id = ?2",
+            [source, id],
+        )?;
+        if transaction
+            .query_row(
+                "SELECT 1 FROM Library WHERE source = ?1 AND id = ?2",
+                [source, id],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some()
+        {
+            index_effective_manga(&transaction, source, id)?;
         }
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    fn count_names(
+        &self,
+        table: &str,
+        link: &str,
+        key: &str,
+    ) -> Result<Vec<NameCount>, rusqlite::Error> {
+        let mut prepared_rows = self.db.prepare(&format!(
+            "SELECT {table}.name AS name, COUNT(*) AS count FROM {table}
+                JOIN {link} ON {link}.{key} = {table}.id
+                JOIN Library ON Library.source = {link}.source AND Library.id = {link}.manga_id
+            WHERE {VISIBLE_MANGA}
+            GROUP BY {table}.id
+            ORDER BY count DESC, {table}.name"
+        ))?;
+        let iter = prepared_rows.query_map([], |row| {
+            Ok(NameCount {
+                name: row.get("name")?,
+                count: row.get("count")?,
+            })
+        })?;
+
+        iter.collect()
+    }
+
+    fn get_by_name(
+        &self,
+        table: &str,
+        link: &str,
+        key: &str,
+        name: String,
+        mode: DecodeMode,
+    ) -> Result<Decoded<Manga>, AppError> {
+        decode_rows(
+            &self.db,
+            "Library",
+            &format!(
+                "SELECT Library.* FROM EffectiveLibrary AS Library
+                    JOIN {link} ON {link}.source = Library.source AND {link}.manga_id = Library.id
+                    JOIN {table} ON {table}.id = {link}.{key}
+                WHERE {table}.name = ?1 AND {VISIBLE_MANGA}
+                ORDER BY Library.name"
+            ),
+            [name],
+            generate_manga_from_row,
+            mode,
+        )
+    }
+
+    pub fn get_tags(&self) -> Result<Vec<NameCount>, rusqlite::Error> {
+        self.count_names("Tag", "MangaTag", "tag_id")
+    }
+
+    pub fn get_authors(&self) -> Result<Vec<NameCount>, rusqlite::Error> {
+        self.count_names("Author", "MangaAuthor", "author_id")
+    }
+
+    pub fn get_by_tag(&self, tag: String, mode: DecodeMode) -> Result<Decoded<Manga>, AppError> {
+        self.get_by_name("Tag", "MangaTag", "tag_id", tag, mode)
+    }
+
+    pub fn get_by_author(
+        &self,
+        author: String,
+        mode: DecodeMode,
+    ) -> Result<Decoded<Manga>, AppError> {
+        self.get_by_name("Author", "MangaAuthor", "author_id", author, mode)
     }
 
     pub fn clear(&self) -> Result<(), rusqlite::Error> {
@@ -359,90 +1381,110 @@ impl MangaDB {
     }
 }
 
+/// Saves a chapter and indexes its scanlators. Like `upsert_manga`, it runs in
+/// the caller's transaction.
+pub fn upsert_chapter(db: &Connection, chapter: Chapter) -> Result<usize, rusqlite::Error> {
+    let Chapter {
+        id,
+        manga_id,
+        title,
+        chapter,
+        label,
+        volume,
+        last_read,
+        date_uploaded,
+        last_updated,
+        time_spent_reading,
+        pages,
+        total,
+        lang,
+        scanlators,
+        source,
+    } = chapter;
+
+    // An upsert for the same reason as `MangaDB::insert`: reading
+    // progress and scanlator links hang off the row. `added` is only
+    // set the first time so new chapters can be told apart.
+    let changed = db.execute(
+        "INSERT INTO Chapters
+            (id, manga_id, title, chapter, volume, last_read, date_uploaded, last_updated, time_spent_reading, pages, total, scanlators, lang, source, label, added)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)
+         ON CONFLICT (source, manga_id, id) DO UPDATE SET
+            title = excluded.title,
+            chapter = excluded.chapter,
+            volume = excluded.volume,
+            last_read = excluded.last_read,
+            date_uploaded = excluded.date_uploaded,
+            last_updated = excluded.last_updated,
+            time_spent_reading = excluded.time_spent_reading,
+            pages = excluded.pages,
+            total = excluded.total,
+            scanlators = excluded.scanlators,
+            lang = excluded.lang,
+            label = excluded.label",
+         (
+             &id,
+             &manga_id,
+             title,
+             chapter,
+             volume,
+             last_read,
+             date_uploaded,
+             last_updated,
+             time_spent_reading,
+             pages,
+             total,
+             serde_json::to_string(&scanlators).unwrap(),
+             lang,
+             &source,
+             label,
+             chrono::Utc::now().timestamp_millis(),
+        )
+    )?;
+    index_chapter(db, &source, &manga_id, &id, &scanlators)?;
+
+    Ok(changed)
+}
+
 impl ChapterDB {
-    #[must_use]
-    pub fn new(path: &Option<std::path::PathBuf>) -> Self {
-        let db: Connection = path.as_ref().map_or_else(
-            || Connection::open_in_memory().expect("unable to open database"),
-            |path| {
-                let p2 = path.clone();
-                Connection::open(path).unwrap_or_else(|_| {
-                    panic!("unable to open database with path {}", p2.to_str().unwrap())
-                })
-            },
-        );
-
-        match db.execute(
-            "
-                         CREATE TABLE IF NOT EXISTS Chapters
-                         (
-                            id                 TEXT NOT NULL PRIMARY KEY,
-                            manga_id           TEXT NOT NULL,
-                            source             TEXT NOT NULL,
-                            chapter            INT NOT NULL,
-                            volume             INT NOT NULL,
-
-                            title              TEXT NOT NULL,
-
-                            last_updated       INT NOT NULL,
-                            last_read          INT NOT NULL,
-                            time_spent_reading INT NOT NULL,
-                            date_uploaded      INT NOT NULL,
-
-                            pages              INT NOT NULL,
-                            total              INT NOT NULL,
-                            scanlators         TEXT NOT NULL,
-                            lang               TEXT NOT NULL
-                        )
-                         ",
-            [],
-        ) {
-            Ok(_) => println!("Chapters table was created."),
-            Err(why) => panic!("Loading chapters failed: {why}"),
-        }
+    pub fn new(path: &Option<std::path::PathBuf>) -> Result<Self, AppError> {
+        let db = open(path)?;
+        create_library_schema(&db)?;
 
-        Self { db }
+        Ok(Self { db })
     }
 
     pub fn insert(&self, chapter: Chapter) -> Result<usize, rusqlite::Error> {
-        let Chapter {
-            id,
-            manga_id,
-            title,
-            chapter,
-            volume,
-            last_read,
-            date_uploaded,
-            last_updated,
-            time_spent_reading,
-            pages,
-            total,
-            lang,
-            scanlators,
-            source,
-        } = chapter;
+        let transaction = self.db.unchecked_transaction()?;
+        let changed = upsert_chapter(&transaction, chapter)?;
+        transaction.commit()?;
 
-        self.db.execute(
-            "REPLACE INTO Chapters
-                (id, manga_id, title, chapter, volume, last_read, date_uploaded, last_updated, time_spent_reading, pages, total, scanlators, lang, source)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
-             (
-                 id,
-                 manga_id,
-                 title,
-                 chapter,
-                 volume,
-                 last_read,
-                 date_uploaded,
-                 last_updated,
-                 time_spent_reading,
-                 pages,
-                 total,
-                 serde_json::to_string(&scanlators).unwrap(),
-                 lang,
-                 source,
-            )
-        )
+        Ok(changed)
+    }
+
+    /// Scanlators with the number of chapters they're credited on, optionally
+    /// only counting one manga's chapters.
+    pub fn get_scanlators(
+        &self,
+        source: Option<String>,
+        manga_id: Option<String>,
+    ) -> Result<Vec<NameCount>, rusqlite::Error> {
+        let mut prepared_rows = self.db.prepare(
+            "SELECT Scanlator.name AS name, COUNT(*) AS count FROM Scanlator
+                JOIN ChapterScanlator ON ChapterScanlator.scanlator_id = Scanlator.id
+            WHERE (?1 IS NULL OR ChapterScanlator.source = ?1)
+            AND (?2 IS NULL OR ChapterScanlator.manga_id = ?2)
+            GROUP BY Scanlator.id
+            ORDER BY count DESC, Scanlator.name",
+        )?;
+        let iter = prepared_rows.query_map([source, manga_id], |row| {
+            Ok(NameCount {
+                name: row.get("name")?,
+                count: row.get("count")?,
+            })
+        })?;
+
+        iter.collect()
     }
 
     pub fn get(
@@ -465,20 +1507,24 @@ impl ChapterDB {
         source: String,
         manga_id: String,
         ids: Vec<String>,
-    ) -> Result<std::vec::Vec<Chapter>, rusqlite::Error> {
-        load_module(&self.db).unwrap();
-        let mut prepared_rows = self.db.prepare(
-            "SELECT * FROM Chapters where manga_id = ?1 and source = ?2 AND id IN rarray(?2)",
-        )?;
+        mode: DecodeMode,
+    ) -> Result<Decoded<Chapter>, AppError> {
+        load_module(&self.db)?;
         let values_iter: Vec<rusqlite::types::Value> =
             ids.into_iter().map(rusqlite::types::Value::from).collect();
-        let iter = prepared_rows.query_map((manga_id, source, Rc::new(values_iter)), |row| {
-            generate_chapter_from_row(row)
-        })?;
 
-        Ok(iter
-            .map(std::result::Result::unwrap)
-            .collect::<Vec<Chapter>>())
+        decode_rows(
+            &self.db,
+            "Chapters",
+            &format!(
+                "SELECT rowid AS row_id, * FROM Chapters
+                WHERE manga_id = ?1 AND source = ?2 AND id IN rarray(?3)
+                {CHAPTER_ORDER}"
+            ),
+            (manga_id, source, Rc::new(values_iter)),
+            generate_chapter_from_row,
+            mode,
+        )
     }
 
     pub fn get_all(
@@ -486,44 +1532,58 @@ impl ChapterDB {
         source: Option<String>,
         id: Option<String>,
         manga_id: Option<String>,
-    ) -> Result<Vec<Chapter>, rusqlite::Error> {
-        let mut id_value = String::new();
-        let mut source_value = String::new();
-        let mut manga_id_value = String::new();
-
-        if let Some(id) = id {
-            id_value = format!("AND id={id}");
-        };
-
-        if let Some(id) = manga_id {
-            manga_id_value = format!("AND manga_id={id}");
-        };
+        mode: DecodeMode,
+    ) -> Result<Decoded<Chapter>, AppError> {
+        decode_rows(
+            &self.db,
+            "Chapters",
+            &format!(
+                "SELECT rowid AS row_id, * FROM Chapters
+                WHERE (?1 IS NULL OR id = ?1)
+                AND (?2 IS NULL OR manga_id = ?2)
+                AND (?3 IS NULL OR source = ?3)
+                {CHAPTER_ORDER}"
+            ),
+            [id, manga_id, source],
+            generate_chapter_from_row,
+            mode,
+        )
+    }
 
-        if let Some(source) = source {
-            source_value = format!("AND source={source}");
-        };
+    pub fn get_for_manga(
+        &self,
+        source: String,
+        manga_id: String,
+    ) -> Result<Vec<Chapter>, AppError> {
+        Ok(decode_rows(
+            &self.db,
+            "Chapters",
+            &format!(
+                "SELECT rowid AS row_id, * FROM Chapters WHERE source = ?1 AND manga_id = ?2
+                {CHAPTER_ORDER}"
+            ),
+            [source, manga_id],
+            generate_chapter_from_row,
+            DecodeMode::Strict,
+        )?
+        .rows)
+    }
 
-        match self
-            .db
-            .prepare(
-                format!(
-                    "SELECT * FROM Chapters WHERE 1=1 {id_value} {manga_id_value} {source_value}"
-                )
-                .as_str(),
-            )?
-            .query_map([], generate_chapter_from_row)
-        {
-            Ok(iter) => Ok(iter
-                .map(std::result::Result::unwrap)
-                .collect::<Vec<Chapter>>()),
-            Err(why) => Err(why),
-        }
+    pub fn delete_for_manga(
+        &self,
+        source: String,
+        manga_id: String,
+    ) -> Result<usize, rusqlite::Error> {
+        self.db.execute(
+            "DELETE FROM Chapters WHERE source = ?1 AND manga_id = ?2",
+            [source, manga_id],
+        )
     }
 
     pub fn delete(&self, manga_id: String, id: String) -> Result<Option<usize>, rusqlite::Error> {
         self.db
             .execute(
-                "DELETE * FROM Chapters WHERE manga_id = ?1 AND id = ?2",
+                "DELETE FROM Chapters WHERE manga_id = ?1 AND id = ?2",
                 [manga_id, id],
             )
             .optional()
@@ -537,16 +1597,48 @@ impl ChapterDB {
     }
 }
 
-pub fn init(mut _path: &std::path::PathBuf) -> Result<DBHandler, crate::errors::InternalError> {
+pub fn init(mut _path: &std::path::PathBuf) -> Result<DBHandler, AppError> {
     // Create database files in the app folder
     // For now, open the database in memory for testing purposes.
     // path.push("sw.db");
 
-    let manga_handler = MangaDB::new(&None);
-    let chapter_handler = ChapterDB::new(&None);
+    let manga_handler = MangaDB::new(&None)?;
+    let chapter_handler = ChapterDB::new(&None)?;
 
     Ok(DBHandler {
         manga_db: manga_handler,
         chapter_db: chapter_handler,
     })
 }
+
+/// A database file of a test's own, removed again when dropped.
+#[cfg(test)]
+pub struct TestDb {
+    pub path: Option<std::path::PathBuf>,
+}
+
+#[cfg(test)]
+impl Default for TestDb {
+    fn default() -> Self {
+        static NEXT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "swrs-test-{}-{}.db3",
+            std::process::id(),
+            NEXT.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        Self { path: Some(path) }
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestDb {
+    fn drop(&mut self) {
+        if let Some(path) = &self.path {
+            for suffix in ["", "-journal", "-wal", "-shm"] {
+                let _ = std::fs::remove_file(format!("{}{suffix}", path.display()));
+            }
+        }
+    }
+}
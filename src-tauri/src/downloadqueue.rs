@@ -0,0 +1,118 @@
+use rusqlite::{Connection, Row};
+use serde::Serialize;
+
+use crate::{
+    db::{open, ChapterDB},
+    errors::{AppError, Context},
+};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QueuedDownload {
+    pub source: String,
+    pub manga_id: String,
+    pub chapter_id: String,
+    pub queued_at: i64,
+}
+
+pub struct DownloadQueueDB {
+    db: Connection,
+}
+
+fn generate_queued_download_from_row(row: &Row) -> Result<QueuedDownload, rusqlite::Error> {
+    Ok(QueuedDownload {
+        source: row.get("source")?,
+        manga_id: row.get("manga_id")?,
+        chapter_id: row.get("chapter_id")?,
+        queued_at: row.get("queued_at")?,
+    })
+}
+
+impl DownloadQueueDB {
+    pub fn new(path: &Option<std::path::PathBuf>) -> Result<Self, AppError> {
+        // Queued chapters reference `Chapters`, so make sure it exists first.
+        ChapterDB::new(path)?;
+        let db = open(path)?;
+
+        db.execute_batch(
+            "CREATE TABLE IF NOT EXISTS DownloadQueue
+            (
+               source TEXT NOT NULL,
+               manga_id TEXT NOT NULL,
+               chapter_id TEXT NOT NULL,
+               queued_at INT NOT NULL,
+
+               PRIMARY KEY (source, manga_id, chapter_id),
+               FOREIGN KEY (source, manga_id, chapter_id)
+                   REFERENCES Chapters (source, manga_id, id) ON DELETE CASCADE
+            );
+
+            CREATE TABLE IF NOT EXISTS DownloadedChapters
+            (
+               source TEXT NOT NULL,
+               manga_id TEXT NOT NULL,
+               chapter_id TEXT NOT NULL,
+               downloaded_at INT NOT NULL,
+
+               PRIMARY KEY (source, manga_id, chapter_id),
+               FOREIGN KEY (source, manga_id, chapter_id)
+                   REFERENCES Chapters (source, manga_id, id) ON DELETE CASCADE
+            );
+            ",
+        )
+        .context("unable to create download tables")?;
+
+        Ok(Self { db })
+    }
+
+    /// Every queued chapter, oldest first.
+    pub fn get_all(&self) -> Result<Vec<QueuedDownload>, rusqlite::Error> {
+        let mut prepared_rows = self
+            .db
+            .prepare("SELECT * FROM DownloadQueue ORDER BY queued_at, rowid")?;
+        let iter = prepared_rows.query_map([], generate_queued_download_from_row)?;
+
+        iter.collect()
+    }
+
+    pub fn remove(
+        &self,
+        source: &str,
+        manga_id: &str,
+        chapter_id: &str,
+    ) -> Result<usize, rusqlite::Error> {
+        self.db.execute(
+            "DELETE FROM DownloadQueue WHERE source = ?1 AND manga_id = ?2 AND chapter_id = ?3",
+            [source, manga_id, chapter_id],
+        )
+    }
+
+    /// Records that a chapter finished downloading and takes it off the queue.
+    pub fn complete(
+        &self,
+        source: &str,
+        manga_id: &str,
+        chapter_id: &str,
+    ) -> Result<(), rusqlite::Error> {
+        let transaction = self.db.unchecked_transaction()?;
+        transaction.execute(
+            "DELETE FROM DownloadQueue WHERE source = ?1 AND manga_id = ?2 AND chapter_id = ?3",
+            [source, manga_id, chapter_id],
+        )?;
+        transaction.execute(
+            "REPLACE INTO DownloadedChapters (source, manga_id, chapter_id, downloaded_at)
+            VALUES (?1, ?2, ?3, ?4)",
+            (
+                source,
+                manga_id,
+                chapter_id,
+                chrono::Utc::now().timestamp_millis(),
+            ),
+        )?;
+
+        transaction.commit()
+    }
+
+    pub fn clear(&self) -> Result<usize, rusqlite::Error> {
+        self.db.execute("DELETE FROM DownloadQueue", [])
+    }
+}
@@ -0,0 +1,370 @@
+use std::collections::HashSet;
+
+use rusqlite::{Connection, Row};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::{
+        get_source_manga, open, upsert_manga, Chapter, ChapterDB, DecodeMode, Decoded, Manga,
+        MangaDB, MangaStatus,
+    },
+    errors::{AppError, Context},
+    get_db_path,
+    library::{get_library, LibraryQuery},
+    matching::{normalize_title, title_similarity},
+};
+
+pub const DEFAULT_DUPLICATE_THRESHOLD: f64 = 0.75;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MangaRef {
+    pub source: String,
+    pub id: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DuplicateCandidate {
+    pub first: MangaRef,
+    pub second: MangaRef,
+    pub name: String,
+    pub score: f64,
+}
+
+/// A source the canonical library entry `(source, id)` also reads from.
+/// Lower priorities win when picking which source a chapter comes from.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LinkedSource {
+    pub source: String,
+    pub id: String,
+    pub linked_source: String,
+    pub linked_id: String,
+    pub priority: i64,
+}
+
+pub struct LinkedSourcesDB {
+    db: Connection,
+}
+
+fn generate_linked_source_from_row(row: &Row) -> Result<LinkedSource, rusqlite::Error> {
+    Ok(LinkedSource {
+        source: row.get("source")?,
+        id: row.get("id")?,
+        linked_source: row.get("linked_source")?,
+        linked_id: row.get("linked_id")?,
+        priority: row.get("priority")?,
+    })
+}
+
+fn link(
+    db: &Connection,
+    canonical: &MangaRef,
+    linked: &MangaRef,
+    priority: i64,
+) -> Result<usize, rusqlite::Error> {
+    db.execute(
+        "REPLACE INTO LinkedSources
+            (source, id, linked_source, linked_id, priority) VALUES (?1, ?2, ?3, ?4, ?5)",
+        (
+            &canonical.source,
+            &canonical.id,
+            &linked.source,
+            &linked.id,
+            priority,
+        ),
+    )
+}
+
+fn get_links(db: &Connection, canonical: &MangaRef) -> Result<Vec<LinkedSource>, rusqlite::Error> {
+    let mut prepared_rows =
+        db.prepare("SELECT * FROM LinkedSources WHERE source = ?1 AND id = ?2 ORDER BY priority")?;
+    let iter = prepared_rows.query_map(
+        [&canonical.source, &canonical.id],
+        generate_linked_source_from_row,
+    )?;
+
+    iter.collect()
+}
+
+fn relink(db: &Connection, from: &MangaRef, to: &MangaRef) -> Result<usize, rusqlite::Error> {
+    db.execute(
+        "UPDATE LinkedSources SET source = ?3, id = ?4 WHERE source = ?1 AND id = ?2",
+        (&from.source, &from.id, &to.source, &to.id),
+    )
+}
+
+impl LinkedSourcesDB {
+    pub fn new(path: &Option<std::path::PathBuf>) -> Result<Self, AppError> {
+        let db = open(path)?;
+
+        db.execute(
+            "CREATE TABLE IF NOT EXISTS LinkedSources
+            (
+               source TEXT NOT NULL,
+               id TEXT NOT NULL,
+               linked_source TEXT NOT NULL,
+               linked_id TEXT NOT NULL,
+               priority INT NOT NULL,
+               PRIMARY KEY (linked_source, linked_id)
+            )
+            ",
+            (),
+        )
+        .context("unable to create LinkedSources table")?;
+
+        Ok(Self { db })
+    }
+
+    pub fn link(
+        &self,
+        canonical: &MangaRef,
+        linked: &MangaRef,
+        priority: i64,
+    ) -> Result<usize, rusqlite::Error> {
+        link(&self.db, canonical, linked, priority)
+    }
+
+    pub fn get_links(&self, canonical: &MangaRef) -> Result<Vec<LinkedSource>, rusqlite::Error> {
+        get_links(&self.db, canonical)
+    }
+
+    pub fn get_all(&self) -> Result<Vec<LinkedSource>, rusqlite::Error> {
+        let mut prepared_rows = self.db.prepare("SELECT * FROM LinkedSources")?;
+        let iter = prepared_rows.query_map([], generate_linked_source_from_row)?;
+
+        iter.collect()
+    }
+
+    /// Moves every link pointing at `from` so it points at `to` instead.
+    pub fn relink(&self, from: &MangaRef, to: &MangaRef) -> Result<usize, rusqlite::Error> {
+        relink(&self.db, from, to)
+    }
+
+    pub fn set_priority(&self, linked: &MangaRef, priority: i64) -> Result<usize, rusqlite::Error> {
+        self.db.execute(
+            "UPDATE LinkedSources SET priority = ?3 WHERE linked_source = ?1 AND linked_id = ?2",
+            (&linked.source, &linked.id, priority),
+        )
+    }
+
+    pub fn unlink(&self, linked: &MangaRef) -> Result<usize, rusqlite::Error> {
+        self.db.execute(
+            "DELETE FROM LinkedSources WHERE linked_source = ?1 AND linked_id = ?2",
+            [&linked.source, &linked.id],
+        )
+    }
+}
+
+fn author_similarity(a: &Manga, b: &Manga) -> f64 {
+    let a_authors: HashSet<String> = a.authors.iter().map(|v| normalize_title(v)).collect();
+    let b_authors: HashSet<String> = b.authors.iter().map(|v| normalize_title(v)).collect();
+    if a_authors.is_empty() || b_authors.is_empty() {
+        return 0.0;
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    let score = a_authors.intersection(&b_authors).count() as f64
+        / a_authors.union(&b_authors).count() as f64;
+    score
+}
+
+/// Scores every pair of library entries from different sources and returns
+/// the pairs scoring at least `threshold`, highest first. Entries that don't
+/// decode are left out and reported alongside the pairs.
+pub fn find_duplicates(threshold: f64) -> Result<Decoded<DuplicateCandidate>, AppError> {
+    // Only what's in the library, not every manga that was ever opened.
+    let (entries, _) = get_library(LibraryQuery::default(), DecodeMode::SkipCorrupt)?;
+    let library: Vec<Manga> = entries.rows.into_iter().map(|entry| entry.manga).collect();
+    let linked: HashSet<(String, String)> = LinkedSourcesDB::new(&get_db_path())?
+        .get_all()?
+        .into_iter()
+        .map(|link| (link.linked_source, link.linked_id))
+        .collect();
+
+    let mut candidates = vec![];
+    for (index, first) in library.iter().enumerate() {
+        for second in &library[index + 1..] {
+            if first.source == second.source
+                || linked.contains(&(first.source.clone(), first.id.clone()))
+                || linked.contains(&(second.source.clone(), second.id.clone()))
+            {
+                continue;
+            }
+
+            let title_score = title_similarity(&first.name, &second.name);
+            let score = if first.authors.is_empty() || second.authors.is_empty() {
+                title_score
+            } else {
+                title_score.mul_add(0.8, author_similarity(first, second) * 0.2)
+            };
+
+            if score >= threshold {
+                candidates.push(DuplicateCandidate {
+                    first: MangaRef {
+                        source: first.source.clone(),
+                        id: first.id.clone(),
+                    },
+                    second: MangaRef {
+                        source: second.source.clone(),
+                        id: second.id.clone(),
+                    },
+                    name: first.name.clone(),
+                    score,
+                });
+            }
+        }
+    }
+
+    candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+    Ok(Decoded {
+        rows: candidates,
+        corrupt: entries.corrupt,
+    })
+}
+
+fn merge_unique<T: PartialEq>(into: &mut Vec<T>, from: Vec<T>) {
+    for value in from {
+        if !into.contains(&value) {
+            into.push(value);
+        }
+    }
+}
+
+/// When the first of two entries was added to the library. Manga that were
+/// only opened are stored with `added = -1`, which only sticks when neither
+/// entry is in the library.
+fn earliest_added(first: i64, second: i64) -> i64 {
+    match (first >= 0, second >= 0) {
+        (true, true) => first.min(second),
+        (true, false) => first,
+        (false, true) => second,
+        (false, false) => -1,
+    }
+}
+
+/// Folds `duplicates` into the canonical entry. They stay in `Library` as the
+/// parents of their chapters but are hidden from listings, linked to the
+/// canonical entry as alternate sources. The whole merge is saved at once.
+pub fn merge(canonical: &MangaRef, duplicates: Vec<MangaRef>) -> Result<Manga, AppError> {
+    // `MangaDB::new` makes the links table as well.
+    MangaDB::new(&get_db_path())?;
+    let mut db = open(&get_db_path())?;
+    let transaction = db.transaction()?;
+
+    // Merged from the metadata stored from each source, so overrides stay
+    // overrides.
+    let mut merged = get_source_manga(&transaction, &canonical.source, &canonical.id)?
+        .ok_or_else(|| AppError::not_found("canonical manga is not in the library"))?;
+
+    let mut priority = get_links(&transaction, canonical)?
+        .iter()
+        .map(|link| link.priority)
+        .max()
+        .unwrap_or(0);
+
+    for duplicate in duplicates.into_iter().filter(|d| d != canonical) {
+        let manga = match get_source_manga(&transaction, &duplicate.source, &duplicate.id)? {
+            Some(manga) => manga,
+            None => continue,
+        };
+
+        priority += 1;
+        link(&transaction, canonical, &duplicate, priority)?;
+        relink(&transaction, &duplicate, canonical)?;
+
+        merge_unique(&mut merged.authors, manga.authors);
+        merge_unique(&mut merged.tags, manga.tags);
+        merge_unique(&mut merged.covers, manga.covers);
+        merge_unique(&mut merged.artists, manga.artists);
+        merge_unique(&mut merged.links, manga.links);
+        if manga.name != merged.name {
+            merge_unique(&mut merged.alt_titles, vec![manga.name]);
+        }
+        merge_unique(&mut merged.alt_titles, manga.alt_titles);
+        merged.added = earliest_added(merged.added, manga.added);
+
+        if merged.status == MangaStatus::Unknown {
+            merged.status = manga.status;
+        }
+        merged.demographic = merged.demographic.or(manga.demographic);
+        merged.content_rating = merged.content_rating.or(manga.content_rating);
+        merged.original_language = merged.original_language.or(manga.original_language);
+    }
+
+    upsert_manga(&transaction, merged)?;
+    transaction.commit()?;
+
+    MangaDB::new(&get_db_path())?
+        .get(canonical.id.clone(), canonical.source.clone())?
+        .ok_or_else(|| AppError::internal("merged manga was not saved"))
+}
+
+/// Makes `linked` the first source chapters are taken from.
+pub fn set_preferred(canonical: &MangaRef, linked: &MangaRef) -> Result<(), AppError> {
+    let linked_db = LinkedSourcesDB::new(&get_db_path())?;
+    let lowest = linked_db
+        .get_links(canonical)?
+        .iter()
+        .map(|link| link.priority)
+        .min()
+        .unwrap_or(0)
+        .min(0);
+
+    linked_db.set_priority(linked, lowest - 1)?;
+    Ok(())
+}
+
+/// How far into a chapter its reading progress is, as a fraction of its
+/// pages. Nothing is known until the page count is.
+fn completion(chapter: &Chapter) -> f64 {
+    if chapter.total > 0 {
+        f64::from(chapter.pages) / f64::from(chapter.total)
+    } else {
+        0.0
+    }
+}
+
+/// Lists one chapter per chapter number across the canonical entry and all of
+/// its linked sources, taken from the highest-priority source that has it.
+/// Reading progress is the furthest recorded in any source.
+pub fn unified_chapters(canonical: &MangaRef) -> Result<Vec<Chapter>, AppError> {
+    let chapter_db = ChapterDB::new(&get_db_path())?;
+
+    let mut sources = vec![(0, canonical.source.clone(), canonical.id.clone())];
+    sources.extend(
+        LinkedSourcesDB::new(&get_db_path())?
+            .get_links(canonical)?
+            .into_iter()
+            .map(|link| (link.priority, link.linked_source, link.linked_id)),
+    );
+    sources.sort_by_key(|(priority, ..)| *priority);
+
+    let mut unified: Vec<Chapter> = vec![];
+    for (_, source, id) in sources {
+        for chapter in chapter_db.get_for_manga(source, id)? {
+            match unified
+                .iter_mut()
+                .find(|preferred| preferred.same_number(&chapter))
+            {
+                Some(preferred) => {
+                    preferred.last_read = preferred.last_read.max(chapter.last_read);
+                    preferred.time_spent_reading =
+                        preferred.time_spent_reading.max(chapter.time_spent_reading);
+                    // Pages and total go together, or a chapter read to the
+                    // end elsewhere could end up past its own last page.
+                    if completion(&chapter)
+                        .total_cmp(&completion(preferred))
+                        .then(chapter.pages.cmp(&preferred.pages))
+                        .is_gt()
+                    {
+                        preferred.pages = chapter.pages;
+                        preferred.total = chapter.total;
+                    }
+                }
+                None => unified.push(chapter),
+            }
+        }
+    }
+
+    unified.sort_by(Chapter::natural_cmp);
+    Ok(unified)
+}
@@ -1,57 +1,176 @@
-use std::string::ToString;
-#[derive(Debug, serde::Serialize)]
-pub struct InternalError {
-    message: std::string::String,
+use serde::Serialize;
+
+/// What went wrong, for the frontend to branch on. These names are part of
+/// the IPC contract, so only ever add to them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ErrorCode {
+    NotFound,
+    Database,
+    Io,
+    Network,
+    Parse,
+    Source,
+    Validation,
+    Internal,
+}
+
+/// The error every command returns. Serialises as
+/// `{ code, message, context }`, where `context` lists what was being done
+/// when the error happened, outermost first.
+#[derive(Debug, Serialize)]
+pub struct AppError {
+    pub code: ErrorCode,
+    pub message: String,
+    pub context: Vec<String>,
 }
 
-impl InternalError {
+impl AppError {
     #[must_use]
-    pub fn new<T>(message: T) -> Self
+    pub fn new<T>(code: ErrorCode, message: T) -> Self
     where
         T: ToString,
     {
         Self {
+            code,
             message: message.to_string(),
+            context: vec![],
         }
     }
+
+    #[must_use]
+    pub fn not_found<T: ToString>(message: T) -> Self {
+        Self::new(ErrorCode::NotFound, message)
+    }
+
+    #[must_use]
+    pub fn validation<T: ToString>(message: T) -> Self {
+        Self::new(ErrorCode::Validation, message)
+    }
+
+    #[must_use]
+    pub fn parse<T: ToString>(message: T) -> Self {
+        Self::new(ErrorCode::Parse, message)
+    }
+
+    #[must_use]
+    pub fn source<T: ToString>(message: T) -> Self {
+        Self::new(ErrorCode::Source, message)
+    }
+
+    #[must_use]
+    pub fn internal<T: ToString>(message: T) -> Self {
+        Self::new(ErrorCode::Internal, message)
+    }
+
+    /// Records what was being done when the error happened.
+    #[must_use]
+    pub fn context<T: ToString>(mut self, context: T) -> Self {
+        self.context.insert(0, context.to_string());
+        self
+    }
+}
+
+/// Adds context to any error that converts into an [`AppError`].
+pub trait Context<T> {
+    fn context<C: ToString>(self, context: C) -> Result<T, AppError>;
+}
+
+impl<T, E> Context<T> for Result<T, E>
+where
+    E: Into<AppError>,
+{
+    fn context<C: ToString>(self, context: C) -> Result<T, AppError> {
+        self.map_err(|why| why.into().context(context))
+    }
 }
 
-impl From<rusqlite::Error> for InternalError {
+impl From<rusqlite::Error> for AppError {
     fn from(err: rusqlite::Error) -> Self {
-        Self {
-            message: err.to_string(),
+        match err {
+            rusqlite::Error::QueryReturnedNoRows => Self::not_found("no matching rows"),
+            rusqlite::Error::FromSqlConversionFailure(..)
+            | rusqlite::Error::InvalidColumnType(..)
+            | rusqlite::Error::IntegralValueOutOfRange(..) => Self::parse(err),
+            err => Self::new(ErrorCode::Database, err),
         }
     }
 }
 
-impl From<serde_json::Error> for InternalError {
+impl From<serde_json::Error> for AppError {
     fn from(err: serde_json::Error) -> Self {
-        Self {
-            message: err.to_string(),
-        }
+        Self::parse(err)
     }
 }
 
-impl From<serde_rusqlite::Error> for InternalError {
+impl From<serde_rusqlite::Error> for AppError {
     fn from(err: serde_rusqlite::Error) -> Self {
-        Self {
-            message: err.to_string(),
+        Self::new(ErrorCode::Database, err)
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(err: std::io::Error) -> Self {
+        match err.kind() {
+            std::io::ErrorKind::NotFound => Self::not_found(err),
+            _ => Self::new(ErrorCode::Io, err),
         }
     }
 }
 
-impl std::fmt::Display for InternalError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "InternalError {{\n\tmessage:{}\n}}", &self.message)
+impl From<toml::de::Error> for AppError {
+    fn from(err: toml::de::Error) -> Self {
+        Self::parse(err)
     }
 }
 
-impl std::error::Error for InternalError {
-    fn description(&self) -> &str {
-        &self.message
+impl From<toml::ser::Error> for AppError {
+    fn from(err: toml::ser::Error) -> Self {
+        Self::parse(err)
     }
 }
 
+impl From<std::num::ParseIntError> for AppError {
+    fn from(err: std::num::ParseIntError) -> Self {
+        Self::parse(err)
+    }
+}
+
+impl From<tauri::Error> for AppError {
+    fn from(err: tauri::Error) -> Self {
+        Self::internal(err)
+    }
+}
+
+impl From<reqwest::Error> for AppError {
+    fn from(err: reqwest::Error) -> Self {
+        Self::new(ErrorCode::Network, err)
+    }
+}
+
+impl From<DownloadError> for AppError {
+    fn from(err: DownloadError) -> Self {
+        Self::new(ErrorCode::Network, err)
+    }
+}
+
+impl From<RequestError> for AppError {
+    fn from(err: RequestError) -> Self {
+        Self::new(ErrorCode::Network, err)
+    }
+}
+
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for context in &self.context {
+            write!(f, "{context}: ")?;
+        }
+
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for AppError {}
+
 #[derive(Debug)]
 pub struct DownloadError {
     message: String,
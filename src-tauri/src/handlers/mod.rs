@@ -1,61 +1,63 @@
 use crate::{
-    db::{Chapter, ChapterDB, Chapters, Manga, MangaDB, Mangas},
-    errors::{self, InternalError},
+    backup::{self, BackupFile, RestoreMode, RestoreReport},
+    bookmarks::{Bookmark, BookmarkDB},
+    bulk::{self, ChapterSelection},
+    categories::CategoryDB,
+    db::{
+        Chapter, ChapterDB, Chapters, DecodeMode, Decoded, Manga, MangaDB, MangaOverride, Mangas,
+        NameCount,
+    },
+    downloadqueue::{DownloadQueueDB, QueuedDownload},
+    duplicates::{self, DuplicateCandidate, LinkedSource, LinkedSourcesDB, MangaRef},
+    errors::{AppError, Context},
     get_db_path,
-    readerdb::ReaderDB,
-    settings::Settings,
+    legacy::{self, LegacyImportReport},
+    library::{self, LibraryEntry, LibraryPage, LibraryQuery},
+    maintenance::{self, MaintenanceDB, MaintenanceReport},
+    migrate::{self, MigrationCandidate, MigrationReport},
+    profiles::{self, ExportFormat, ProfileDB, SettingsBundle, SettingsProfile},
+    progress::{self, ChapterProgress, ProgressDB, ProgressUpdate},
+    readerdb::{self, EffectiveReaderSettings, ReaderDB, ReaderLayer},
+    repair::{self, QuarantineDB, QuarantinedRow, RepairReport},
+    savedsearch::{self, SavedSearch, SavedSearchDB},
+    search::{RecentSearch, SearchDB, SearchOrchestrator},
+    settings::{AppSettings, Settings, SettingsChanged},
+    sourcedb::SourcePreferencesDB,
+    sources::{self, SourceManifest},
+    tachiyomi::{self, SourceMapping, TachiyomiImportReport, TachiyomiSourceMapDB},
 };
 use std::{
-    error::Error,
     fs,
     path::{Path, PathBuf},
 };
 
-use tauri::{
-    api::path::{resolve_path, BaseDirectory},
-    Env, Manager,
-};
+use tauri::Manager;
 
-pub fn stringify_result<T, E>(r: Result<T, E>) -> Result<T, String>
-where
-    E: Error,
-{
-    match r {
-        Ok(y) => Ok(y),
-        Err(e) => Err(e.to_string()),
-    }
+pub fn get_manga_db() -> Result<MangaDB, AppError> {
+    MangaDB::new(&get_db_path())
 }
 
-pub fn stringify_result_none<T, E>(r: Result<T, E>) -> Result<(), String>
-where
-    E: Error,
-{
-    match r {
-        Ok(..) => Ok(()),
-        Err(e) => Err(e.to_string()),
-    }
+pub fn get_chapter_db() -> Result<ChapterDB, AppError> {
+    ChapterDB::new(&get_db_path())
 }
 
-#[must_use]
-pub fn get_manga_db() -> MangaDB {
-    MangaDB::new(&get_db_path())
-}
+/// Emits `corrupt-rows` for whatever a skip-and-report listing left out and
+/// hands back the rows that did decode.
+fn report_corrupt<T>(window: &tauri::Window, decoded: Decoded<T>) -> Vec<T> {
+    if !decoded.corrupt.is_empty() {
+        if let Err(why) = window.emit("corrupt-rows", &decoded.corrupt) {
+            log::warn!("unable to emit corrupt rows: {why}");
+        }
+    }
 
-#[must_use]
-pub fn get_chapter_db() -> ChapterDB {
-    ChapterDB::new(&get_db_path())
+    decoded.rows
 }
 
 #[tauri::command]
-pub fn splash_close(window: tauri::Window) -> Result<(), errors::InternalError> {
+pub fn splash_close(window: tauri::Window) -> Result<(), AppError> {
     window.get_window("splashscreen").map_or_else(
-        || Err(crate::errors::InternalError::new("No splash-screen found.")),
-        |splashscreen| match splashscreen.close() {
-            Ok(()) => Ok(()),
-            Err(why) => Err(crate::errors::InternalError::new(format!(
-                "Splashscreen failed to close: {why}"
-            ))),
-        },
+        || Err(AppError::not_found("No splash-screen found.")),
+        |splashscreen| splashscreen.close().context("Splashscreen failed to close"),
     )
 }
 
@@ -66,58 +68,247 @@ pub fn path_exists(path: &str) -> bool {
 }
 
 #[tauri::command]
-pub fn get_all_manga(source: Option<String>) -> Result<Mangas, String> {
-    let db = get_manga_db();
-    match db.get_all(source) {
-        Ok(mangas) => Ok(Mangas { mangas }),
-        Err(y) => Err(y.to_string()),
-    }
+pub fn get_all_manga(
+    window: tauri::Window,
+    source: Option<String>,
+    skip_corrupt: Option<bool>,
+) -> Result<Mangas, AppError> {
+    let db = get_manga_db()?;
+    Ok(Mangas {
+        mangas: report_corrupt(
+            &window,
+            db.get_all(source, DecodeMode::skip_corrupt(skip_corrupt))?,
+        ),
+    })
 }
 
 #[tauri::command]
-pub fn get_manga(id: String, source: String) -> Result<Option<Manga>, String> {
-    let db = get_manga_db();
-    stringify_result(db.get(id, source))
+pub fn get_library(
+    window: tauri::Window,
+    query: Option<LibraryQuery>,
+    skip_corrupt: Option<bool>,
+) -> Result<LibraryPage, AppError> {
+    let (entries, total) = library::get_library(
+        query.unwrap_or_default(),
+        DecodeMode::skip_corrupt(skip_corrupt),
+    )?;
+
+    Ok(LibraryPage {
+        entries: report_corrupt(&window, entries),
+        total,
+    })
+}
+
+#[tauri::command]
+pub fn get_manga_override(source: String, id: String) -> Result<Option<MangaOverride>, AppError> {
+    Ok(get_manga_db()?.get_override(&source, &id)?)
+}
+
+#[tauri::command]
+pub fn set_manga_override(
+    source: String,
+    id: String,
+    overrides: MangaOverride,
+) -> Result<(), AppError> {
+    get_manga_db()?.set_override(&source, &id, overrides)
+}
+
+#[tauri::command]
+pub fn reset_manga_override(source: String, id: String) -> Result<(), AppError> {
+    get_manga_db()?.reset_override(&source, &id)
+}
+
+#[tauri::command]
+pub fn get_update_targets(
+    window: tauri::Window,
+    skip_corrupt: Option<bool>,
+) -> Result<Vec<LibraryEntry>, AppError> {
+    Ok(report_corrupt(
+        &window,
+        library::update_targets(DecodeMode::skip_corrupt(skip_corrupt))?,
+    ))
+}
+
+#[tauri::command]
+pub fn set_manga_added(source: String, id: String, added: i64) -> Result<usize, AppError> {
+    Ok(get_manga_db()?.set_added(&source, &id, added)?)
+}
+
+#[tauri::command]
+pub fn mark_manga_checked(source: String, id: String) -> Result<usize, AppError> {
+    Ok(get_manga_db()?.set_checked(&source, &id)?)
+}
+
+#[tauri::command]
+pub fn get_manga(id: String, source: String) -> Result<Option<Manga>, AppError> {
+    let db = get_manga_db()?;
+    Ok(db.get(id, source)?)
 }
 
 #[tauri::command]
 pub fn get_mangas(
+    window: tauri::Window,
     source: String,
     ids: std::vec::Vec<String>,
-) -> Result<std::vec::Vec<Manga>, String> {
-    let db = get_manga_db();
-    stringify_result(db.get_multiple(source, ids))
+    skip_corrupt: Option<bool>,
+) -> Result<std::vec::Vec<Manga>, AppError> {
+    let db = get_manga_db()?;
+    Ok(report_corrupt(
+        &window,
+        db.get_multiple(source, ids, DecodeMode::skip_corrupt(skip_corrupt))?,
+    ))
+}
+
+#[tauri::command]
+pub fn get_tags() -> Result<Vec<NameCount>, AppError> {
+    Ok(get_manga_db()?.get_tags()?)
+}
+
+#[tauri::command]
+pub fn get_authors() -> Result<Vec<NameCount>, AppError> {
+    Ok(get_manga_db()?.get_authors()?)
 }
 
 #[tauri::command]
-pub fn insert_manga(manga: Manga) -> Result<usize, String> {
-    let db = get_manga_db();
-    stringify_result(db.insert(manga))
+pub fn get_manga_by_tag(
+    window: tauri::Window,
+    tag: String,
+    skip_corrupt: Option<bool>,
+) -> Result<Vec<Manga>, AppError> {
+    let db = get_manga_db()?;
+    Ok(report_corrupt(
+        &window,
+        db.get_by_tag(tag, DecodeMode::skip_corrupt(skip_corrupt))?,
+    ))
 }
 
 #[tauri::command]
-pub fn remove_manga(source: String, id: String) -> Result<(), String> {
-    let db = get_manga_db();
-    stringify_result_none(db.delete(id, source))
+pub fn get_manga_by_author(
+    window: tauri::Window,
+    author: String,
+    skip_corrupt: Option<bool>,
+) -> Result<Vec<Manga>, AppError> {
+    let db = get_manga_db()?;
+    Ok(report_corrupt(
+        &window,
+        db.get_by_author(author, DecodeMode::skip_corrupt(skip_corrupt))?,
+    ))
 }
 
 #[tauri::command]
-pub fn clear_manga() -> Result<(), String> {
-    let db = get_manga_db();
-    stringify_result_none(db.clear())
+pub fn insert_manga(manga: Manga) -> Result<usize, AppError> {
+    let db = get_manga_db()?;
+    Ok(db.insert(manga)?)
+}
+
+#[tauri::command]
+pub fn remove_manga(source: String, id: String) -> Result<(), AppError> {
+    let db = get_manga_db()?;
+    db.delete(id, source)?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn clear_manga() -> Result<(), AppError> {
+    let db = get_manga_db()?;
+    db.clear()?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn find_duplicate_manga(
+    window: tauri::Window,
+    threshold: Option<f64>,
+) -> Result<Vec<DuplicateCandidate>, AppError> {
+    Ok(report_corrupt(
+        &window,
+        duplicates::find_duplicates(threshold.unwrap_or(duplicates::DEFAULT_DUPLICATE_THRESHOLD))?,
+    ))
+}
+
+#[tauri::command]
+pub fn merge_manga(
+    source: String,
+    id: String,
+    duplicates: Vec<MangaRef>,
+) -> Result<Manga, AppError> {
+    duplicates::merge(&MangaRef { source, id }, duplicates)
+}
+
+#[tauri::command]
+pub fn get_linked_sources(source: String, id: String) -> Result<Vec<LinkedSource>, AppError> {
+    let db = LinkedSourcesDB::new(&get_db_path())?;
+    Ok(db.get_links(&MangaRef { source, id })?)
+}
+
+#[tauri::command]
+pub fn set_preferred_source(
+    source: String,
+    id: String,
+    linked_source: String,
+    linked_id: String,
+) -> Result<(), AppError> {
+    duplicates::set_preferred(
+        &MangaRef { source, id },
+        &MangaRef {
+            source: linked_source,
+            id: linked_id,
+        },
+    )
+}
+
+#[tauri::command]
+pub fn unlink_source(linked_source: String, linked_id: String) -> Result<(), AppError> {
+    let db = LinkedSourcesDB::new(&get_db_path())?;
+    db.unlink(&MangaRef {
+        source: linked_source,
+        id: linked_id,
+    })?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_unified_chapters(source: String, id: String) -> Result<Vec<Chapter>, AppError> {
+    duplicates::unified_chapters(&MangaRef { source, id })
+}
+
+#[tauri::command]
+pub async fn find_migration_candidates(
+    window: tauri::Window,
+    orchestrator: tauri::State<'_, SearchOrchestrator>,
+    source: String,
+    id: String,
+    targets: Vec<String>,
+) -> Result<Vec<MigrationCandidate>, AppError> {
+    migrate::find_candidates(&window, &orchestrator, source, id, targets).await
+}
+
+#[tauri::command]
+pub fn migrate_manga(
+    source: String,
+    id: String,
+    target: Manga,
+    target_chapters: Vec<Chapter>,
+    keep_old: bool,
+) -> Result<MigrationReport, AppError> {
+    migrate::migrate(source, id, target, target_chapters, keep_old)
 }
 
 #[tauri::command]
 pub fn get_all_chapters(
+    window: tauri::Window,
     source: Option<String>,
     id: Option<String>,
     manga_id: Option<String>,
-) -> Result<Chapters, String> {
-    let db = get_chapter_db();
-    match db.get_all(source, id, manga_id) {
-        Ok(chapters) => Ok(Chapters { chapters }),
-        Err(y) => Err(y.to_string()),
-    }
+    skip_corrupt: Option<bool>,
+) -> Result<Chapters, AppError> {
+    let db = get_chapter_db()?;
+    Ok(Chapters {
+        chapters: report_corrupt(
+            &window,
+            db.get_all(source, id, manga_id, DecodeMode::skip_corrupt(skip_corrupt))?,
+        ),
+    })
 }
 
 #[tauri::command]
@@ -125,63 +316,72 @@ pub fn get_chapter(
     manga_id: String,
     source: String,
     id: String,
-) -> Result<Option<Chapter>, String> {
-    let db = get_chapter_db();
-    stringify_result(db.get(source, id, manga_id))
+) -> Result<Option<Chapter>, AppError> {
+    let db = get_chapter_db()?;
+    Ok(db.get(source, id, manga_id)?)
 }
 
 #[tauri::command]
 pub fn get_chapters(
+    window: tauri::Window,
     source: String,
     manga_id: String,
     ids: std::vec::Vec<String>,
-) -> Result<std::vec::Vec<Chapter>, String> {
-    let db = get_chapter_db();
-    stringify_result(db.get_multiple(source, manga_id, ids))
+    skip_corrupt: Option<bool>,
+) -> Result<std::vec::Vec<Chapter>, AppError> {
+    let db = get_chapter_db()?;
+    Ok(report_corrupt(
+        &window,
+        db.get_multiple(
+            source,
+            manga_id,
+            ids,
+            DecodeMode::skip_corrupt(skip_corrupt),
+        )?,
+    ))
 }
 
 #[tauri::command]
-pub fn insert_chapter(chapter: Chapter) -> Result<(), String> {
-    let db = get_chapter_db();
-    stringify_result_none(db.insert(chapter))
+pub fn get_scanlators(
+    source: Option<String>,
+    manga_id: Option<String>,
+) -> Result<Vec<NameCount>, AppError> {
+    Ok(get_chapter_db()?.get_scanlators(source, manga_id)?)
 }
 
 #[tauri::command]
-pub fn remove_chapter(manga_id: String, id: String) -> Result<(), String> {
-    let db = get_chapter_db();
-    stringify_result_none(db.delete(manga_id, id))
+pub fn insert_chapter(chapter: Chapter) -> Result<(), AppError> {
+    let db = get_chapter_db()?;
+    db.insert(chapter)?;
+    Ok(())
 }
 
 #[tauri::command]
-pub fn clear_chapters() -> Result<(), String> {
-    let db = get_chapter_db();
-    stringify_result_none(db.clear())
+pub fn remove_chapter(manga_id: String, id: String) -> Result<(), AppError> {
+    let db = get_chapter_db()?;
+    db.delete(manga_id, id)?;
+    Ok(())
 }
 
 #[tauri::command]
-pub fn get_sources() -> Result<Vec<PathBuf>, InternalError> {
-    let app_context = tauri::generate_context!();
-    let sources_path = resolve_path(
-        app_context.config(),
-        app_context.package_info(),
-        &Env::default(),
-        Path::new("com.suwariyomirs.swrs\\sources")
-            .as_os_str()
-            .to_str()
-            .unwrap(),
-        Some(BaseDirectory::Config),
-    );
+pub fn clear_chapters() -> Result<(), AppError> {
+    let db = get_chapter_db()?;
+    db.clear()?;
+    Ok(())
+}
 
-    if let Ok(path) = sources_path {
+#[tauri::command]
+pub fn get_sources() -> Result<Vec<PathBuf>, AppError> {
+    if let Some(path) = sources::get_sources_path() {
         match fs::try_exists(&path) {
             Ok(exists) => {
                 if !exists {
-                    return Err(InternalError::new("sources directory does not exist"));
+                    return Err(AppError::not_found("sources directory does not exist"));
                 }
 
                 let mut all_sources: Vec<PathBuf> = vec![];
                 fs::read_dir(path).map_or_else(
-                    |_| Err(InternalError::new("unable to read sources directory")),
+                    |why| Err(AppError::from(why).context("unable to read sources directory")),
                     |itr| {
                         itr.for_each(|val| {
                             if let Ok(val) = val {
@@ -193,45 +393,600 @@ pub fn get_sources() -> Result<Vec<PathBuf>, InternalError> {
                     },
                 )
             }
-            Err(y) => Err(InternalError::new(y.to_string().as_str())),
+            Err(y) => Err(y.into()),
         }
     } else {
-        Err(InternalError::new("unable to get sources dir"))
+        Err(AppError::internal("unable to get sources dir"))
     }
 }
 
+fn get_source_manifest(source: &str) -> Result<SourceManifest, AppError> {
+    sources::find_source(source)?.map_or_else(
+        || {
+            Err(AppError::not_found(format!(
+                "source '{source}' is not installed"
+            )))
+        },
+        |(_, manifest)| Ok(manifest),
+    )
+}
+
 #[tauri::command]
-pub fn get_app_settings() -> Result<Option<serde_json::Value>, InternalError> {
+pub fn get_source_preference_schema(
+    source: String,
+) -> Result<Vec<sources::PreferenceSchema>, AppError> {
+    Ok(get_source_manifest(&source)?.preferences)
+}
+
+#[tauri::command]
+pub fn get_source_preferences(
+    source: String,
+) -> Result<serde_json::Map<String, serde_json::Value>, AppError> {
+    let manifest = get_source_manifest(&source)?;
+    SourcePreferencesDB::new(&get_db_path())?.get_all(&manifest)
+}
+
+#[tauri::command]
+pub fn get_source_preference(source: String, key: String) -> Result<serde_json::Value, AppError> {
+    let manifest = get_source_manifest(&source)?;
+    SourcePreferencesDB::new(&get_db_path())?.get(&manifest, &key)
+}
+
+#[tauri::command]
+pub fn set_source_preference(
+    source: String,
+    key: String,
+    value: serde_json::Value,
+) -> Result<(), AppError> {
+    let manifest = get_source_manifest(&source)?;
+    SourcePreferencesDB::new(&get_db_path())?.set(&manifest, &key, &value)
+}
+
+#[tauri::command]
+pub fn uninstall_source(source: String) -> Result<(), AppError> {
+    if let Some((path, _)) = sources::find_source(&source)? {
+        fs::remove_dir_all(&path).context("unable to remove source directory")?;
+    }
+
+    SourcePreferencesDB::new(&get_db_path())?.delete(&source)?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn start_global_search(
+    window: tauri::Window,
+    orchestrator: tauri::State<'_, SearchOrchestrator>,
+    query: String,
+    sources: Vec<String>,
+    filters: Option<serde_json::Value>,
+) -> u64 {
+    orchestrator.start(
+        window,
+        query,
+        sources,
+        filters.unwrap_or(serde_json::Value::Null),
+    )
+}
+
+#[tauri::command]
+pub fn submit_search_result(
+    orchestrator: tauri::State<'_, SearchOrchestrator>,
+    search_id: u64,
+    source: String,
+    results: Option<serde_json::Value>,
+    error: Option<String>,
+) -> bool {
+    let results = error.map_or_else(|| Ok(results.unwrap_or_default()), Err);
+    orchestrator.submit(search_id, source, results)
+}
+
+#[tauri::command]
+pub fn get_cached_search(
+    source: String,
+    query: String,
+    filters: Option<serde_json::Value>,
+) -> Result<Option<serde_json::Value>, AppError> {
+    let db = SearchDB::new(&get_db_path())?;
+    Ok(db.get_cached(&source, &query, &filters.unwrap_or(serde_json::Value::Null))?)
+}
+
+#[tauri::command]
+pub fn clear_search_cache() -> Result<(), AppError> {
+    let db = SearchDB::new(&get_db_path())?;
+    db.clear_cache()?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_recent_searches() -> Result<Vec<RecentSearch>, AppError> {
+    let db = SearchDB::new(&get_db_path())?;
+    Ok(db.get_recent()?)
+}
+
+#[tauri::command]
+pub fn clear_recent_searches() -> Result<(), AppError> {
+    let db = SearchDB::new(&get_db_path())?;
+    db.clear_recent()?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn create_saved_search(
+    name: String,
+    source: String,
+    query: String,
+    filters: Option<serde_json::Value>,
+    interval: Option<i64>,
+) -> Result<i64, AppError> {
+    let db = SavedSearchDB::new(&get_db_path())?;
+    Ok(db.insert(
+        name,
+        source,
+        query,
+        &filters.unwrap_or(serde_json::Value::Null),
+        interval.unwrap_or(0),
+    )?)
+}
+
+#[tauri::command]
+pub fn get_saved_searches() -> Result<Vec<SavedSearch>, AppError> {
+    let db = SavedSearchDB::new(&get_db_path())?;
+    Ok(db.get_all()?)
+}
+
+#[tauri::command]
+pub fn set_saved_search_interval(id: i64, interval: i64) -> Result<(), AppError> {
+    let db = SavedSearchDB::new(&get_db_path())?;
+    db.set_interval(id, interval)?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn remove_saved_search(id: i64) -> Result<(), AppError> {
+    let db = SavedSearchDB::new(&get_db_path())?;
+    db.delete(id)?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn run_saved_search(
+    window: tauri::Window,
+    orchestrator: tauri::State<'_, SearchOrchestrator>,
+    id: i64,
+) -> Result<Vec<serde_json::Value>, AppError> {
+    let saved = SavedSearchDB::new(&get_db_path())?
+        .get(id)?
+        .ok_or_else(|| AppError::not_found(format!("no saved search with id {id}")))?;
+
+    savedsearch::run(&window, &orchestrator, &saved).await
+}
+
+#[tauri::command]
+pub fn get_app_settings() -> Result<AppSettings, AppError> {
     Settings {}.get()
 }
 
+fn broadcast_settings(
+    app: &tauri::AppHandle,
+    change: SettingsChanged,
+) -> Result<AppSettings, AppError> {
+    if !change.changed.is_empty() {
+        app.emit_all("settings-changed", change.clone())
+            .context("unable to broadcast settings")?;
+    }
+
+    Ok(change.settings)
+}
+
+#[tauri::command]
+pub fn set_app_settings(
+    app: tauri::AppHandle,
+    new_settings: serde_json::Value,
+) -> Result<AppSettings, AppError> {
+    broadcast_settings(&app, Settings {}.set(new_settings)?)
+}
+
+#[tauri::command]
+pub fn patch_app_settings(
+    app: tauri::AppHandle,
+    patch: serde_json::Value,
+) -> Result<AppSettings, AppError> {
+    broadcast_settings(&app, Settings {}.patch(&patch)?)
+}
+
+#[tauri::command]
+pub fn set_app_setting(
+    app: tauri::AppHandle,
+    key: String,
+    value: serde_json::Value,
+) -> Result<AppSettings, AppError> {
+    broadcast_settings(&app, Settings {}.set_key(&key, value)?)
+}
+
+#[tauri::command]
+pub fn save_settings_profile(name: String) -> Result<(), AppError> {
+    ProfileDB::new(&get_db_path())?.save(name, &SettingsBundle::current()?)?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_settings_profiles() -> Result<Vec<SettingsProfile>, AppError> {
+    let db = ProfileDB::new(&get_db_path())?;
+    Ok(db.get_all()?)
+}
+
+#[tauri::command]
+pub fn switch_settings_profile(
+    app: tauri::AppHandle,
+    name: String,
+) -> Result<AppSettings, AppError> {
+    broadcast_settings(&app, profiles::switch(name)?)
+}
+
+#[tauri::command]
+pub fn delete_settings_profile(name: String) -> Result<(), AppError> {
+    let db = ProfileDB::new(&get_db_path())?;
+    db.delete(name)?;
+    Ok(())
+}
+
 #[tauri::command]
-pub fn set_app_settings(new_settings: std::string::String) -> bool {
-    Settings {}.set(Some(new_settings))
+pub fn export_settings(path: PathBuf, format: Option<ExportFormat>) -> Result<(), AppError> {
+    profiles::export(&path, format)
+}
+
+#[tauri::command]
+pub fn import_settings(
+    app: tauri::AppHandle,
+    path: PathBuf,
+    format: Option<ExportFormat>,
+) -> Result<AppSettings, AppError> {
+    broadcast_settings(&app, profiles::import(&path, format)?)
+}
+
+#[tauri::command]
+pub fn create_backup(path: Option<PathBuf>) -> Result<PathBuf, AppError> {
+    backup::create_file(path)
+}
+
+#[tauri::command]
+pub fn get_backups() -> Result<Vec<BackupFile>, AppError> {
+    backup::list()
+}
+
+#[tauri::command]
+pub fn restore_backup(
+    app: tauri::AppHandle,
+    path: PathBuf,
+    mode: RestoreMode,
+    dry_run: bool,
+) -> Result<RestoreReport, AppError> {
+    let (report, change) = backup::restore(backup::read_file(&path)?, mode, dry_run)?;
+    if let Some(change) = change {
+        broadcast_settings(&app, change)?;
+    }
+
+    Ok(report)
+}
+
+#[tauri::command]
+pub fn import_tachiyomi_backup(
+    path: PathBuf,
+    dry_run: bool,
+) -> Result<TachiyomiImportReport, AppError> {
+    tachiyomi::import(&path, dry_run)
+}
+
+#[tauri::command]
+pub fn import_legacy_library(
+    app: tauri::AppHandle,
+    path: Option<PathBuf>,
+    dry_run: bool,
+) -> Result<LegacyImportReport, AppError> {
+    let (report, change) = legacy::import(path, dry_run)?;
+    if let Some(change) = change {
+        broadcast_settings(&app, change)?;
+    }
+
+    Ok(report)
+}
+
+#[tauri::command]
+pub fn get_tachiyomi_source_map() -> Result<Vec<SourceMapping>, AppError> {
+    Ok(TachiyomiSourceMapDB::new(&get_db_path())?.get_all()?)
+}
+
+#[tauri::command]
+pub fn set_tachiyomi_source_map(tachiyomi_id: String, source: String) -> Result<(), AppError> {
+    let tachiyomi_id: i64 = tachiyomi_id.parse()?;
+    TachiyomiSourceMapDB::new(&get_db_path())?.set(tachiyomi_id, source)?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn remove_tachiyomi_source_map(tachiyomi_id: String) -> Result<(), AppError> {
+    let tachiyomi_id: i64 = tachiyomi_id.parse()?;
+    TachiyomiSourceMapDB::new(&get_db_path())?.delete(tachiyomi_id)?;
+    Ok(())
 }
 
 #[tauri::command]
 pub fn get_reader_settings(
     source: String,
     id: String,
-) -> Result<Option<serde_json::Value>, InternalError> {
-    let db_return = ReaderDB::new(&get_db_path()).get(source, id);
-    db_return.map_or_else(|why| Err(InternalError::new(why)), Ok)
+) -> Result<Option<serde_json::Value>, AppError> {
+    Ok(ReaderDB::new(&get_db_path())?.get(source, id)?)
 }
 
 #[tauri::command]
-pub fn set_reader_settings(source: String, id: String, data: String) -> Result<(), InternalError> {
-    let reader_db = ReaderDB::new(&get_db_path());
-    reader_db.insert(source, id, data);
-
+pub fn set_reader_settings(source: String, id: String, data: String) -> Result<(), AppError> {
+    ReaderDB::new(&get_db_path())?.insert(source, id, data)?;
     Ok(())
 }
 
 #[tauri::command]
-pub fn return_to_tray(window: tauri::Window) -> Result<(), errors::InternalError> {
-    if window.hide().is_err() {
-        Err(errors::InternalError::new("failed to hide window"))
-    } else {
-        Ok(())
+pub fn get_effective_reader_settings(
+    source: String,
+    id: String,
+) -> Result<EffectiveReaderSettings, AppError> {
+    readerdb::resolve(source, id)
+}
+
+#[tauri::command]
+pub fn set_reader_override(
+    layer: ReaderLayer,
+    scope: String,
+    data: serde_json::Value,
+) -> Result<(), AppError> {
+    match layer {
+        ReaderLayer::Source | ReaderLayer::Category => {
+            ReaderDB::new(&get_db_path())?.set_override(layer, scope, &data)?;
+            Ok(())
+        }
+        _ => Err(AppError::validation(
+            "only source and category overrides are set here",
+        )),
+    }
+}
+
+/// Clears one layer. `scope` is the source id or category name; for the manga
+/// layer it is the source and `id` is the manga id.
+#[tauri::command]
+pub fn reset_reader_layer(
+    app: tauri::AppHandle,
+    layer: ReaderLayer,
+    scope: Option<String>,
+    id: Option<String>,
+) -> Result<(), AppError> {
+    let reader_db = ReaderDB::new(&get_db_path())?;
+    let scope = scope.ok_or_else(|| AppError::validation("a scope is required for this layer"));
+
+    match layer {
+        ReaderLayer::Global => {
+            let defaults = serde_json::to_value(crate::settings::ReaderSettings::default())?;
+            broadcast_settings(&app, Settings {}.set_key("Reader", defaults)?)?;
+        }
+        ReaderLayer::Source | ReaderLayer::Category => {
+            reader_db.delete_override(layer, scope?)?;
+        }
+        ReaderLayer::Manga => {
+            let id = id.ok_or_else(|| AppError::validation("a manga id is required"))?;
+            reader_db.delete(scope?, id)?;
+        }
     }
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_categories() -> Result<Vec<String>, AppError> {
+    let db = CategoryDB::new(&get_db_path())?;
+    Ok(db.get_all()?)
+}
+
+#[tauri::command]
+pub fn get_manga_categories(source: String, id: String) -> Result<Vec<String>, AppError> {
+    let db = CategoryDB::new(&get_db_path())?;
+    Ok(db.get_for_manga(source, id)?)
+}
+
+#[tauri::command]
+pub fn add_manga_to_category(source: String, id: String, category: String) -> Result<(), AppError> {
+    let db = CategoryDB::new(&get_db_path())?;
+    db.add(source, id, category)?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn remove_manga_from_category(
+    source: String,
+    id: String,
+    category: String,
+) -> Result<(), AppError> {
+    let db = CategoryDB::new(&get_db_path())?;
+    db.remove(source, id, category)?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn return_to_tray(window: tauri::Window) -> Result<(), AppError> {
+    window.hide().context("failed to hide window")
+}
+
+#[tauri::command]
+pub fn repair_database(dry_run: bool) -> Result<RepairReport, AppError> {
+    repair::repair(dry_run)
+}
+
+#[tauri::command]
+pub fn get_quarantined_rows() -> Result<Vec<QuarantinedRow>, AppError> {
+    Ok(QuarantineDB::new(&get_db_path())?.get_all()?)
+}
+
+#[tauri::command]
+pub fn remove_quarantined_row(id: i64) -> Result<(), AppError> {
+    QuarantineDB::new(&get_db_path())?.delete(id)?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn run_maintenance(dry_run: bool) -> Result<MaintenanceReport, AppError> {
+    maintenance::run(dry_run)
+}
+
+#[tauri::command]
+pub fn get_maintenance_history() -> Result<Vec<MaintenanceReport>, AppError> {
+    Ok(MaintenanceDB::new(&get_db_path())?.get_history()?)
+}
+
+#[tauri::command]
+pub fn set_reading_progress(
+    source: String,
+    manga_id: String,
+    chapter_id: String,
+    page: i32,
+    total: Option<i32>,
+    scroll_offset: Option<f64>,
+) -> Result<ProgressUpdate, AppError> {
+    progress::record(source, manga_id, chapter_id, page, total, scroll_offset)
+}
+
+#[tauri::command]
+pub fn get_chapter_progress(
+    source: String,
+    manga_id: String,
+    chapter_id: String,
+) -> Result<Option<ChapterProgress>, AppError> {
+    Ok(ProgressDB::new(&get_db_path())?.get(&source, &manga_id, &chapter_id)?)
+}
+
+#[tauri::command]
+pub fn get_resume_position(
+    source: String,
+    manga_id: String,
+) -> Result<Option<ChapterProgress>, AppError> {
+    progress::resume_position(source, manga_id)
+}
+
+#[tauri::command]
+pub fn add_bookmark(
+    source: String,
+    manga_id: String,
+    chapter_id: String,
+    page: i32,
+    note: Option<String>,
+    tag: Option<String>,
+) -> Result<Bookmark, AppError> {
+    BookmarkDB::new(&get_db_path())?.add(source, manga_id, chapter_id, page, note, tag)
+}
+
+#[tauri::command]
+pub fn update_bookmark(
+    id: i64,
+    note: Option<String>,
+    tag: Option<String>,
+) -> Result<usize, AppError> {
+    Ok(BookmarkDB::new(&get_db_path())?.update(id, note, tag)?)
+}
+
+#[tauri::command]
+pub fn remove_bookmark(id: i64) -> Result<usize, AppError> {
+    Ok(BookmarkDB::new(&get_db_path())?.remove(id)?)
+}
+
+#[tauri::command]
+pub fn get_manga_bookmarks(source: String, manga_id: String) -> Result<Vec<Bookmark>, AppError> {
+    Ok(BookmarkDB::new(&get_db_path())?.get_for_manga(&source, &manga_id)?)
+}
+
+#[tauri::command]
+pub fn get_all_bookmarks(tag: Option<String>) -> Result<Vec<Bookmark>, AppError> {
+    Ok(BookmarkDB::new(&get_db_path())?.get_all(tag)?)
+}
+
+#[tauri::command]
+pub fn get_bookmark_tags() -> Result<Vec<NameCount>, AppError> {
+    Ok(BookmarkDB::new(&get_db_path())?.get_tags()?)
+}
+
+#[tauri::command]
+pub fn jump_to_bookmark(id: i64) -> Result<ChapterProgress, AppError> {
+    Ok(BookmarkDB::new(&get_db_path())?
+        .get(id)?
+        .ok_or_else(|| AppError::not_found("bookmark does not exist"))?
+        .position())
+}
+
+#[tauri::command]
+pub fn set_chapters_read(
+    source: String,
+    manga_id: String,
+    selection: ChapterSelection,
+    read: bool,
+) -> Result<Vec<String>, AppError> {
+    bulk::set_read(source, manga_id, selection, read)
+}
+
+#[tauri::command]
+pub fn mark_previous_chapters_read(
+    source: String,
+    manga_id: String,
+    chapter_id: String,
+) -> Result<Vec<String>, AppError> {
+    bulk::mark_previous_read(source, manga_id, chapter_id)
+}
+
+#[tauri::command]
+pub fn reset_chapter_progress(
+    source: String,
+    manga_id: String,
+    selection: ChapterSelection,
+) -> Result<Vec<String>, AppError> {
+    bulk::reset_progress(source, manga_id, selection)
+}
+
+#[tauri::command]
+pub fn queue_chapter_downloads(
+    source: String,
+    manga_id: String,
+    selection: ChapterSelection,
+) -> Result<Vec<String>, AppError> {
+    bulk::queue_downloads(source, manga_id, selection)
+}
+
+#[tauri::command]
+pub fn delete_chapter_downloads(
+    source: String,
+    manga_id: String,
+    selection: ChapterSelection,
+) -> Result<Vec<PathBuf>, AppError> {
+    bulk::delete_downloads(source, manga_id, selection)
+}
+
+#[tauri::command]
+pub fn get_download_queue() -> Result<Vec<QueuedDownload>, AppError> {
+    Ok(DownloadQueueDB::new(&get_db_path())?.get_all()?)
+}
+
+#[tauri::command]
+pub fn remove_from_download_queue(
+    source: String,
+    manga_id: String,
+    chapter_id: String,
+) -> Result<usize, AppError> {
+    Ok(DownloadQueueDB::new(&get_db_path())?.remove(&source, &manga_id, &chapter_id)?)
+}
+
+#[tauri::command]
+pub fn mark_chapter_downloaded(
+    source: String,
+    manga_id: String,
+    chapter_id: String,
+) -> Result<(), AppError> {
+    Ok(DownloadQueueDB::new(&get_db_path())?.complete(&source, &manga_id, &chapter_id)?)
+}
+
+#[tauri::command]
+pub fn clear_download_queue() -> Result<usize, AppError> {
+    Ok(DownloadQueueDB::new(&get_db_path())?.clear()?)
 }
@@ -0,0 +1,612 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+use tauri::api::path::BaseDirectory;
+
+use crate::{
+    db::{open, upsert_chapter, upsert_manga, Chapter, ChapterDB, Manga, MangaDB, MangaStatus},
+    errors::{AppError, Context},
+    get_db_path,
+    settings::{Settings, SettingsChanged},
+    sources,
+};
+
+/// Where Electron kept the original Suwariyomi's `userData`.
+#[must_use]
+pub fn get_legacy_path() -> Option<PathBuf> {
+    let app_context = tauri::generate_context!();
+    tauri::api::path::resolve_path(
+        app_context.config(),
+        app_context.package_info(),
+        &tauri::Env::default(),
+        "suwariyomi",
+        Some(BaseDirectory::Config),
+    )
+    .ok()
+}
+
+#[derive(Debug, Serialize)]
+pub struct SkippedEntry {
+    pub source: String,
+    pub id: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UnmappedLegacySource {
+    pub name: String,
+    pub manga: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct LegacyImportReport {
+    pub dry_run: bool,
+    pub imported_manga: usize,
+    pub imported_chapters: usize,
+    /// Dotted paths of the settings carried over, e.g. `Reader.readingMode`.
+    pub imported_settings: Vec<String>,
+    pub unsupported_settings: Vec<String>,
+    pub settings_error: Option<String>,
+    pub unmapped_sources: Vec<UnmappedLegacySource>,
+    pub skipped: Vec<SkippedEntry>,
+}
+
+/// The old stores were written by hand over several releases, so keys show up
+/// in both PascalCase and camelCase. Returns the first of `names` present.
+fn field<'a>(object: &'a Map<String, Value>, names: &[&str]) -> Option<&'a Value> {
+    names.iter().find_map(|name| object.get(*name))
+}
+
+fn string(object: &Map<String, Value>, names: &[&str]) -> Option<String> {
+    field(object, names).and_then(|value| match value {
+        Value::String(s) if !s.is_empty() => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    })
+}
+
+fn number(object: &Map<String, Value>, names: &[&str]) -> Option<f64> {
+    field(object, names).and_then(|value| match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    })
+}
+
+fn strings(object: &Map<String, Value>, names: &[&str]) -> Vec<String> {
+    match field(object, names) {
+        Some(Value::Array(values)) => values
+            .iter()
+            .filter_map(|value| match value {
+                Value::String(s) => Some(s.clone()),
+                Value::Object(o) => string(o, &["Name", "name"]),
+                _ => None,
+            })
+            .collect(),
+        Some(Value::String(s)) if !s.is_empty() => vec![s.clone()],
+        _ => vec![],
+    }
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn int(object: &Map<String, Value>, names: &[&str]) -> Option<i64> {
+    number(object, names).map(|n| n as i64)
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn int32(object: &Map<String, Value>, names: &[&str]) -> Option<i32> {
+    number(object, names).map(|n| n as i32)
+}
+
+/// Reads `name` from the data directory, returning the object under one of
+/// `roots` if the store nests everything under a single key.
+fn read_store(dir: &Path, name: &str, roots: &[&str]) -> Result<Option<Value>, AppError> {
+    let path = dir.join(name);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let contents = fs::read(&path).context(format!("unable to read {name}"))?;
+    let mut store: Value = serde_json::from_slice(&contents)?;
+
+    if let Some(object) = store.as_object_mut() {
+        if let Some(root) = roots.iter().find_map(|root| object.remove(*root)) {
+            return Ok(Some(root));
+        }
+    }
+
+    Ok(Some(store))
+}
+
+/// Iterates `source -> id -> entry` maps, the layout of both the library and
+/// the read store.
+fn entries(store: &Value) -> Vec<(&String, &String, &Map<String, Value>)> {
+    store
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(source, manga)| manga.as_object().map(|manga| (source, manga)))
+        .flat_map(|(source, manga)| {
+            manga
+                .iter()
+                .filter_map(move |(id, entry)| entry.as_object().map(|entry| (source, id, entry)))
+        })
+        .collect()
+}
+
+fn convert_manga(
+    source: &str,
+    key: &str,
+    entry: &Map<String, Value>,
+) -> Result<Manga, &'static str> {
+    let name = string(entry, &["Name", "name", "Title", "title"]).ok_or("missing title")?;
+
+    Ok(Manga {
+        id: string(entry, &["MangaID", "mangaId", "ID", "id"]).unwrap_or_else(|| key.to_string()),
+        name,
+        description: string(
+            entry,
+            &["Synopsis", "synopsis", "Description", "description"],
+        )
+        .unwrap_or_default(),
+        source: source.to_string(),
+        covers: strings(entry, &["CoverURL", "coverUrl", "Cover", "cover", "Covers"]),
+        authors: strings(entry, &["Authors", "authors", "Author", "author"]),
+        chapters: vec![],
+        tags: strings(entry, &["Tags", "tags", "Genres", "genres"]),
+        uploaded: 0,
+        added: int(entry, &["Added", "added", "DateAdded"])
+            .unwrap_or_else(|| chrono::Utc::now().timestamp_millis()),
+        status: string(entry, &["Status", "status"])
+            .map_or(MangaStatus::Unknown, |status| MangaStatus::parse(&status)),
+        alt_titles: strings(entry, &["AltTitles", "altTitles", "AlternativeTitles"]),
+        artists: strings(entry, &["Artists", "artists", "Artist", "artist"]),
+        demographic: None,
+        content_rating: None,
+        original_language: None,
+        links: vec![],
+    })
+}
+
+fn convert_chapters(source: &str, manga_id: &str, entry: &Map<String, Value>) -> Vec<Chapter> {
+    let chapters: Vec<(String, &Map<String, Value>)> = match field(entry, &["Chapters", "chapters"])
+    {
+        Some(Value::Object(chapters)) => chapters
+            .iter()
+            .filter_map(|(id, chapter)| chapter.as_object().map(|c| (id.clone(), c)))
+            .collect(),
+        Some(Value::Array(chapters)) => chapters
+            .iter()
+            .filter_map(Value::as_object)
+            .map(|c| (String::new(), c))
+            .collect(),
+        _ => vec![],
+    };
+
+    chapters
+        .into_iter()
+        .filter_map(|(key, chapter)| {
+            let id = string(chapter, &["ChapterID", "chapterId", "ID", "id"])
+                .or_else(|| Some(key).filter(|key| !key.is_empty()))?;
+
+            let (number, label) = Chapter::parse_number(
+                &string(chapter, &["Chapter", "chapter", "Number"]).unwrap_or_default(),
+            );
+
+            Some(Chapter {
+                id,
+                manga_id: manga_id.to_string(),
+                source: source.to_string(),
+                chapter: number,
+                label,
+                volume: int32(chapter, &["Volume", "volume"]).filter(|volume| *volume > 0),
+                title: string(chapter, &["Title", "title", "Name", "name"]).unwrap_or_default(),
+                last_read: int(chapter, &["LastRead", "lastRead"]).unwrap_or(0),
+                last_updated: int(chapter, &["LastUpdated", "lastUpdated"]).unwrap_or(0),
+                date_uploaded: int(chapter, &["PublishedAt", "publishedAt", "Uploaded"])
+                    .unwrap_or(0),
+                time_spent_reading: int(chapter, &["TimeSpentReading", "timeSpentReading"])
+                    .unwrap_or(0),
+                pages: int32(chapter, &["CurrentPage", "currentPage", "Page", "page"]).unwrap_or(0),
+                total: int32(chapter, &["PageCount", "pageCount", "TotalPages", "total"])
+                    .unwrap_or(0),
+                lang: string(chapter, &["Language", "language", "lang"]).unwrap_or_default(),
+                scanlators: strings(chapter, &["Scanlators", "scanlators", "Groups", "groups"]),
+            })
+        })
+        .collect()
+}
+
+/// Splits the old settings into a patch of everything the current settings
+/// also have and the dotted paths of everything they don't.
+fn convert_settings(
+    old: &Map<String, Value>,
+    current: &Map<String, Value>,
+    prefix: &str,
+    patch: &mut Map<String, Value>,
+    unsupported: &mut Vec<String>,
+) {
+    for (key, value) in old {
+        // The old store's own version means nothing to the new settings.
+        if prefix.is_empty() && key == "version" {
+            continue;
+        }
+
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{prefix}.{key}")
+        };
+
+        match (value, current.get(key)) {
+            (Value::Object(old), Some(Value::Object(current))) => {
+                let mut nested = Map::new();
+                convert_settings(old, current, &path, &mut nested, unsupported);
+                if !nested.is_empty() {
+                    patch.insert(key.clone(), Value::Object(nested));
+                }
+            }
+            (Value::Object(_), _) | (_, Some(Value::Object(_)) | None) => unsupported.push(path),
+            (value, Some(current))
+                if std::mem::discriminant(value) == std::mem::discriminant(current) =>
+            {
+                patch.insert(key.clone(), value.clone());
+            }
+            _ => unsupported.push(path),
+        }
+    }
+}
+
+fn leaf_paths(value: &Value, prefix: &str, paths: &mut Vec<String>) {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object {
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{prefix}.{key}")
+                };
+                leaf_paths(value, &path, paths);
+            }
+        }
+        _ => paths.push(prefix.to_string()),
+    }
+}
+
+/// Imports the library and read progress in one transaction, so a failed
+/// import leaves nothing half-written. `find_source` matches the name of an
+/// old source to an installed one.
+fn import_library(
+    path: &Option<PathBuf>,
+    library: Option<&Value>,
+    read: Option<&Value>,
+    find_source: impl Fn(&str) -> Option<String>,
+    report: &mut LegacyImportReport,
+) -> Result<(), AppError> {
+    MangaDB::new(path)?;
+    ChapterDB::new(path)?;
+
+    let mut mapping: HashMap<String, Option<String>> = HashMap::new();
+    let mut unmapped: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut imported: HashMap<(String, String), Manga> = HashMap::new();
+    let mut read_chapters = vec![];
+
+    for (source, key, entry) in library.map(entries).unwrap_or_default() {
+        let mapped = mapping
+            .entry(source.clone())
+            .or_insert_with(|| find_source(source))
+            .clone();
+
+        let manga = match convert_manga(mapped.as_deref().unwrap_or(source), key, entry) {
+            Ok(manga) => manga,
+            Err(reason) => {
+                report.skipped.push(SkippedEntry {
+                    source: source.clone(),
+                    id: key.clone(),
+                    reason: reason.to_string(),
+                });
+                continue;
+            }
+        };
+
+        if mapped.is_none() {
+            unmapped.entry(source.clone()).or_default().push(manga.name);
+            continue;
+        }
+
+        imported.insert((source.clone(), key.clone()), manga);
+    }
+
+    for (source, key, entry) in read.map(entries).unwrap_or_default() {
+        let manga = match imported.get_mut(&(source.clone(), key.clone())) {
+            Some(manga) => manga,
+            None => {
+                if mapping.get(source).is_some_and(Option::is_some) {
+                    report.skipped.push(SkippedEntry {
+                        source: source.clone(),
+                        id: key.clone(),
+                        reason: "read progress for a manga that isn't in the library".to_string(),
+                    });
+                }
+                continue;
+            }
+        };
+
+        let chapters = convert_chapters(&manga.source, &manga.id, entry);
+        manga.chapters = chapters.iter().map(|chapter| chapter.id.clone()).collect();
+        report.imported_chapters += chapters.len();
+
+        read_chapters.extend(chapters);
+    }
+
+    report.imported_manga = imported.len();
+
+    let mut db = open(path)?;
+    let transaction = db.transaction()?;
+    // Chapters reference their manga, so the library goes in first.
+    for manga in imported.into_values() {
+        upsert_manga(&transaction, manga)?;
+    }
+
+    for chapter in read_chapters {
+        upsert_chapter(&transaction, chapter)?;
+    }
+
+    if report.dry_run {
+        transaction.rollback()?;
+    } else {
+        transaction.commit()?;
+    }
+
+    report.unmapped_sources = unmapped
+        .into_iter()
+        .map(|(name, manga)| UnmappedLegacySource { name, manga })
+        .collect();
+
+    Ok(())
+}
+
+/// Imports the library, read progress and settings of the original Suwariyomi
+/// from its data directory, or the default Electron location if none is
+/// given. Sources are matched to installed sources by name. With `dry_run`
+/// nothing is written.
+pub fn import(
+    dir: Option<PathBuf>,
+    dry_run: bool,
+) -> Result<(LegacyImportReport, Option<SettingsChanged>), AppError> {
+    let dir = match dir {
+        Some(dir) => dir,
+        None => get_legacy_path()
+            .ok_or_else(|| AppError::not_found("unable to find the Suwariyomi data directory"))?,
+    };
+
+    let library = read_store(&dir, "library.json", &["Library", "library"])?;
+    let read = read_store(&dir, "read.json", &["Read", "read", "ReadManga"])?;
+    let old_settings = read_store(&dir, "settings.json", &["Settings", "settings"])?;
+    if library.is_none() && read.is_none() && old_settings.is_none() {
+        return Err(AppError::not_found(format!(
+            "no Suwariyomi data found in {}",
+            dir.display()
+        )));
+    }
+
+    let mut report = LegacyImportReport {
+        dry_run,
+        ..LegacyImportReport::default()
+    };
+    import_library(
+        &get_db_path(),
+        library.as_ref(),
+        read.as_ref(),
+        sources::find_source_by_name,
+        &mut report,
+    )?;
+
+    let mut patch = Map::new();
+    if let Some(Value::Object(old_settings)) = old_settings {
+        let current = serde_json::to_value(Settings {}.get()?)?;
+        convert_settings(
+            &old_settings,
+            current.as_object().unwrap(),
+            "",
+            &mut patch,
+            &mut report.unsupported_settings,
+        );
+    }
+
+    let patch = Value::Object(patch);
+    leaf_paths(&patch, "", &mut report.imported_settings);
+    report.imported_settings.retain(|path| !path.is_empty());
+
+    if dry_run || report.imported_settings.is_empty() {
+        return Ok((report, None));
+    }
+
+    match (Settings {}).patch(&patch) {
+        Ok(changed) => Ok((report, Some(changed))),
+        Err(why) => {
+            report.settings_error = Some(why.to_string());
+            report
+                .unsupported_settings
+                .append(&mut report.imported_settings);
+            Ok((report, None))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::TestDb;
+    use crate::settings::AppSettings;
+
+    /// `userData` of the original app, laid out the way its electron-store
+    /// files were: entries from older releases use camelCase keys, one has no
+    /// title, one comes from a source that isn't installed and one has read
+    /// progress but isn't in the library.
+    fn fixture() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/suwariyomi")
+    }
+
+    fn find_source(name: &str) -> Option<String> {
+        (name == "MangaDex").then(|| "mangadex".to_string())
+    }
+
+    fn import_fixture(path: &Option<PathBuf>, dry_run: bool) -> LegacyImportReport {
+        let library = read_store(&fixture(), "library.json", &["Library", "library"]).unwrap();
+        let read = read_store(&fixture(), "read.json", &["Read", "read", "ReadManga"]).unwrap();
+        let mut report = LegacyImportReport {
+            dry_run,
+            ..LegacyImportReport::default()
+        };
+        import_library(
+            path,
+            library.as_ref(),
+            read.as_ref(),
+            find_source,
+            &mut report,
+        )
+        .unwrap();
+
+        report
+    }
+
+    #[test]
+    fn imports_the_library_and_read_progress() {
+        let test_db = TestDb::default();
+        let path = test_db.path.clone();
+        let report = import_fixture(&path, false);
+
+        assert_eq!(report.imported_manga, 2);
+        assert_eq!(report.imported_chapters, 2);
+        assert_eq!(report.unmapped_sources.len(), 1);
+        assert_eq!(report.unmapped_sources[0].name, "MangaSee");
+        assert_eq!(
+            report.unmapped_sources[0].manga,
+            ["Kaguya-sama: Love is War"]
+        );
+        let mut skipped: Vec<&str> = report.skipped.iter().map(|s| s.reason.as_str()).collect();
+        skipped.sort_unstable();
+        assert_eq!(
+            skipped,
+            [
+                "missing title",
+                "read progress for a manga that isn't in the library"
+            ]
+        );
+
+        let manga_db = MangaDB::new(&path).unwrap();
+        let komi = manga_db
+            .get(
+                "a96676e5-8ae2-425e-b549-7f15dd34a6d8".to_string(),
+                "mangadex".to_string(),
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(komi.name, "Komi Can't Communicate");
+        assert_eq!(komi.authors, ["Oda Tomohito"]);
+        assert_eq!(komi.status, MangaStatus::Ongoing);
+        assert_eq!(komi.added, 1_644_172_800_000);
+        assert_eq!(komi.chapters.len(), 2);
+
+        let solo = manga_db
+            .get(
+                "32d76d19-8a05-4db0-9fc2-e0b0648fe9d0".to_string(),
+                "mangadex".to_string(),
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(solo.name, "Solo Leveling");
+        assert_eq!(solo.authors, ["Chugong"]);
+        assert!(solo.covers[0].ends_with("/cover.jpg"));
+
+        let db = open(&path).unwrap();
+        let first: (Option<f64>, Option<i32>, i32, i32, i64, i64) = db
+            .query_row(
+                "SELECT chapter, volume, pages, total, last_read, time_spent_reading
+                FROM Chapters WHERE id = '1f7fc9b8-5b8c-4d71-b8d8-87c3a6e9a2c1'",
+                [],
+                |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                        row.get(5)?,
+                    ))
+                },
+            )
+            .unwrap();
+        assert_eq!(first, (Some(1.0), Some(1), 19, 19, 1_644_259_200_000, 420));
+
+        let extra: (Option<f64>, Option<i32>, i32, i32) = db
+            .query_row(
+                "SELECT chapter, volume, pages, total
+                FROM Chapters WHERE id = '8d8a1f9e-3f5a-4d08-9c2b-2e5b0b0e6f33'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .unwrap();
+        assert_eq!(extra, (Some(10.5), Some(1), 4, 12));
+    }
+
+    #[test]
+    fn dry_runs_write_nothing() {
+        let test_db = TestDb::default();
+        let path = test_db.path.clone();
+        let report = import_fixture(&path, true);
+        assert_eq!(report.imported_manga, 2);
+
+        let manga: i64 = open(&path)
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM Library", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(manga, 0);
+    }
+
+    #[test]
+    fn carries_over_matching_settings() {
+        let Some(Value::Object(old)) =
+            read_store(&fixture(), "settings.json", &["Settings", "settings"]).unwrap()
+        else {
+            panic!("settings.json should hold an object");
+        };
+        let current = serde_json::to_value(AppSettings::default()).unwrap();
+
+        let (mut patch, mut unsupported) = (Map::new(), vec![]);
+        convert_settings(
+            &old,
+            current.as_object().unwrap(),
+            "",
+            &mut patch,
+            &mut unsupported,
+        );
+
+        let mut imported = vec![];
+        leaf_paths(&Value::Object(patch), "", &mut imported);
+        imported.sort_unstable();
+        unsupported.sort_unstable();
+        assert_eq!(
+            imported,
+            [
+                "General.locale",
+                "General.minimizeToTray",
+                "Reader.readingMode"
+            ]
+        );
+        assert_eq!(
+            unsupported,
+            [
+                "General.enableDiscordRPC",
+                "Reader.markReadThreshold",
+                "Reader.showPageNumber"
+            ]
+        );
+    }
+}
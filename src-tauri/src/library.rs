@@ -0,0 +1,285 @@
+use rusqlite::{named_params, Row};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    categories::CategoryDB,
+    db::{
+        decode_rows, generate_manga_from_row, open, DecodeMode, Decoded, Manga, MangaDB,
+        MangaStatus, VISIBLE_MANGA,
+    },
+    downloadqueue::DownloadQueueDB,
+    errors::AppError,
+    get_db_path,
+    settings::Settings,
+};
+
+/// A library entry with the chapter counts shown on its badges.
+#[derive(Clone, Serialize)]
+pub struct LibraryEntry {
+    #[serde(flatten)]
+    pub manga: Manga,
+
+    pub total_chapters: i64,
+    pub unread: i64,
+    pub downloaded: i64,
+    /// Chapters stored since the manga was last checked.
+    pub new_chapters: i64,
+    pub last_read: i64,
+    pub last_updated: i64,
+}
+
+fn generate_library_entry_from_row(row: &Row) -> Result<LibraryEntry, rusqlite::Error> {
+    Ok(LibraryEntry {
+        manga: generate_manga_from_row(row)?,
+        total_chapters: row.get("total_chapters")?,
+        unread: row.get("unread")?,
+        downloaded: row.get("downloaded")?,
+        new_chapters: row.get("new_chapters")?,
+        last_read: row.get("last_read")?,
+        last_updated: row.get("last_updated")?,
+    })
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LibrarySort {
+    #[default]
+    Alphabetical,
+    DateAdded,
+    LastRead,
+    LastUpdated,
+    UnreadCount,
+    TotalChapters,
+}
+
+impl LibrarySort {
+    const fn column(self) -> &'static str {
+        match self {
+            Self::Alphabetical => "Library.name COLLATE NOCASE",
+            Self::DateAdded => "Library.added",
+            Self::LastRead => "last_read",
+            Self::LastUpdated => "last_updated",
+            Self::UnreadCount => "unread",
+            Self::TotalChapters => "total_chapters",
+        }
+    }
+}
+
+/// How to sort, filter and page the library. Every filter that's set has to
+/// match.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct LibraryQuery {
+    pub sort: LibrarySort,
+    pub descending: bool,
+
+    pub downloaded_only: bool,
+    pub unread_only: bool,
+    /// Only manga with every chapter read.
+    pub completed: bool,
+    pub status: Option<MangaStatus>,
+    /// Leaves out series that have finished publishing.
+    pub skip_completed: bool,
+    pub source: Option<String>,
+    pub tag: Option<String>,
+    pub category: Option<String>,
+
+    pub offset: i64,
+    /// Every matching entry when not set.
+    pub limit: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct LibraryPage {
+    pub entries: Vec<LibraryEntry>,
+    /// How many entries match the filters, across all pages.
+    pub total: i64,
+}
+
+/// Lists the library like `MangaDB::get_all`, counting the chapters of every
+/// entry in the same query, then filters, sorts and pages it by `query`.
+/// Returns the page along with how many entries match across all pages.
+pub fn get_library(
+    query: LibraryQuery,
+    mode: DecodeMode,
+) -> Result<(Decoded<LibraryEntry>, i64), AppError> {
+    get_library_at(&get_db_path(), query, mode)
+}
+
+fn get_library_at(
+    path: &Option<std::path::PathBuf>,
+    query: LibraryQuery,
+    mode: DecodeMode,
+) -> Result<(Decoded<LibraryEntry>, i64), AppError> {
+    if query.offset < 0 || query.limit.is_some_and(|limit| limit < 0) {
+        return Err(AppError::validation(
+            "library pages can't have a negative offset or limit",
+        ));
+    }
+
+    // Counting joins `Library`, `Chapters` and `DownloadedChapters`, and
+    // filtering reads `MangaCategories`, so make sure they all exist first.
+    MangaDB::new(path)?;
+    DownloadQueueDB::new(path)?;
+    CategoryDB::new(path)?;
+    let db = open(path)?;
+
+    let mut having = vec!["1"];
+    if query.downloaded_only {
+        having.push("downloaded > 0");
+    }
+    if query.unread_only {
+        having.push("unread > 0");
+    }
+    if query.completed {
+        having.push("total_chapters > 0 AND unread = 0");
+    }
+
+    // Manga that were only opened are cached in `Library` with `added = -1`;
+    // they aren't part of the library.
+    let filtered = format!(
+        "SELECT Library.*,
+            COUNT(Chapters.id) AS total_chapters,
+            COUNT(CASE WHEN NOT (Chapters.total > 0 AND Chapters.pages >= Chapters.total)
+                THEN 1 END) AS unread,
+            COUNT(DownloadedChapters.chapter_id) AS downloaded,
+            COUNT(CASE WHEN Library.last_checked > 0 AND Chapters.added > Library.last_checked
+                THEN 1 END) AS new_chapters,
+            COALESCE(MAX(Chapters.last_read), 0) AS last_read,
+            COALESCE(MAX(Chapters.last_updated), 0) AS last_updated
+        FROM EffectiveLibrary AS Library
+        LEFT JOIN Chapters
+            ON Chapters.source = Library.source AND Chapters.manga_id = Library.id
+        LEFT JOIN DownloadedChapters
+            ON DownloadedChapters.source = Chapters.source
+            AND DownloadedChapters.manga_id = Chapters.manga_id
+            AND DownloadedChapters.chapter_id = Chapters.id
+        WHERE {VISIBLE_MANGA}
+            AND Library.added != -1
+            AND (:status IS NULL OR Library.status = :status)
+            AND (NOT :skip_completed OR Library.status != 'completed')
+            AND (:source IS NULL OR Library.source = :source)
+            AND (:tag IS NULL OR EXISTS (SELECT 1 FROM MangaTag
+                JOIN Tag ON Tag.id = MangaTag.tag_id
+                WHERE MangaTag.source = Library.source AND MangaTag.manga_id = Library.id
+                AND Tag.name = :tag))
+            AND (:category IS NULL OR EXISTS (SELECT 1 FROM MangaCategories
+                WHERE MangaCategories.source = Library.source AND MangaCategories.id = Library.id
+                AND MangaCategories.category = :category))
+        GROUP BY Library.source, Library.id
+        HAVING {}",
+        having.join(" AND ")
+    );
+
+    let total = db.query_row(
+        &format!("SELECT COUNT(*) FROM ({filtered})"),
+        named_params! {
+            ":status": query.status,
+            ":skip_completed": query.skip_completed,
+            ":source": query.source,
+            ":tag": query.tag,
+            ":category": query.category,
+        },
+        |row| row.get(0),
+    )?;
+
+    let direction = if query.descending { "DESC" } else { "ASC" };
+    let entries = decode_rows(
+        &db,
+        "Library",
+        &format!(
+            "{filtered}
+            ORDER BY {} {direction}, Library.name COLLATE NOCASE, Library.source, Library.id
+            LIMIT :limit OFFSET :offset",
+            query.sort.column()
+        ),
+        named_params! {
+            ":status": query.status,
+            ":skip_completed": query.skip_completed,
+            ":source": query.source,
+            ":tag": query.tag,
+            ":category": query.category,
+            // SQLite reads a negative limit as no limit.
+            ":limit": query.limit.unwrap_or(-1),
+            ":offset": query.offset,
+        },
+        generate_library_entry_from_row,
+        mode,
+    )?;
+
+    Ok((entries, total))
+}
+
+/// The library entries a library update should check for new chapters,
+/// following `Library.updateOngoingMangaOnly`.
+pub fn update_targets(mode: DecodeMode) -> Result<Decoded<LibraryEntry>, AppError> {
+    let ongoing_only = Settings {}.get()?.library.update_ongoing_manga_only;
+    Ok(get_library(update_query(ongoing_only), mode)?.0)
+}
+
+/// Goes through `get_library`, so manga that were only opened are never
+/// polled.
+fn update_query(ongoing_only: bool) -> LibraryQuery {
+    LibraryQuery {
+        skip_completed: ongoing_only,
+        ..LibraryQuery::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{ChapterDB, TestDb};
+
+    #[test]
+    fn cached_manga_are_not_listed() {
+        let test_db = TestDb::default();
+        let path = test_db.path.clone();
+        MangaDB::new(&path).unwrap();
+        open(&path)
+            .unwrap()
+            .execute_batch(
+                "INSERT INTO Library (source, id, name, description, covers, chapters, authors,
+                    tags, uploaded, added)
+                VALUES ('source', 'library', 'In Library', '', '[]', '[]', '[]', '[]', 0, 1000),
+                    ('source', 'cached', 'Only Opened', '', '[]', '[]', '[]', '[]', 0, -1);",
+            )
+            .unwrap();
+
+        let (entries, total) =
+            get_library_at(&path, LibraryQuery::default(), DecodeMode::Strict).unwrap();
+        assert_eq!(total, 1);
+        assert_eq!(entries.rows.len(), 1);
+        assert_eq!(entries.rows[0].manga.id, "library");
+    }
+
+    #[test]
+    fn cached_manga_are_not_updated() {
+        let test_db = TestDb::default();
+        let path = test_db.path.clone();
+        ChapterDB::new(&path).unwrap();
+        open(&path)
+            .unwrap()
+            .execute_batch(
+                "INSERT INTO Library (source, id, name, description, covers, chapters, authors,
+                    tags, uploaded, added, last_checked)
+                VALUES ('source', 'library', 'In Library', '', '[]', '[]', '[]', '[]', 0, 1000, 1),
+                    ('source', 'cached', 'Only Opened', '', '[]', '[]', '[]', '[]', 0, -1, 1);
+                INSERT INTO Chapters (source, manga_id, id, title, chapter, pages, total,
+                    last_read, last_updated, time_spent_reading, date_uploaded, scanlators, lang,
+                    added)
+                VALUES ('source', 'library', '1', '', 1, 0, 0, 0, 0, 0, 0, '[]', 'en', 2),
+                    ('source', 'cached', '1', '', 1, 0, 0, 0, 0, 0, 0, '[]', 'en', 2);",
+            )
+            .unwrap();
+
+        for ongoing_only in [false, true] {
+            let (targets, _) =
+                get_library_at(&path, update_query(ongoing_only), DecodeMode::Strict).unwrap();
+            assert_eq!(targets.rows.len(), 1);
+            assert_eq!(targets.rows[0].manga.id, "library");
+            assert_eq!(targets.rows[0].unread, 1);
+            assert_eq!(targets.rows[0].new_chapters, 1);
+        }
+    }
+}
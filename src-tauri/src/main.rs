@@ -15,12 +15,31 @@ use tauri::{
     SystemTray, SystemTrayEvent, SystemTrayMenu, SystemTrayMenuItem,
 };
 
+pub mod backup;
+pub mod bookmarks;
+pub mod bulk;
+pub mod categories;
 pub mod db;
 pub mod download;
+pub mod downloadqueue;
+pub mod duplicates;
 pub mod errors;
 pub mod handlers;
+pub mod legacy;
+pub mod library;
+pub mod maintenance;
+pub mod matching;
+pub mod migrate;
+pub mod profiles;
+pub mod progress;
 pub mod readerdb;
+pub mod repair;
+pub mod savedsearch;
+pub mod search;
 pub mod settings;
+pub mod sourcedb;
+pub mod sources;
+pub mod tachiyomi;
 
 #[must_use]
 pub fn get_db_path() -> Option<PathBuf> {
@@ -58,18 +77,36 @@ async fn main() {
                         window_main.open_devtools();
 
                         if window_main.set_focus().is_err() {
-                            Err(errors::InternalError::new("unable to focus main window"))
+                            Err(errors::AppError::internal("unable to focus main window"))
                         } else {
                             Ok(())
                         }
                     } else {
-                        Err(errors::InternalError::new("unable to show main window"))
+                        Err(errors::AppError::internal("unable to show main window"))
                     }
                 } else {
                     panic!("splash screen failed to close.")
                 }
             });
 
+            // Re-run saved searches and take automatic backups in the background.
+            let scheduler_window = app.get_window("main").unwrap();
+            let orchestrator = app.state::<search::SearchOrchestrator>().inner().clone();
+            tauri::async_runtime::spawn(async move {
+                loop {
+                    tokio::time::sleep(savedsearch::SCHEDULER_INTERVAL).await;
+                    savedsearch::run_due(&scheduler_window, &orchestrator).await;
+
+                    if let Err(why) = backup::run_scheduled() {
+                        log::error!("automatic backup failed: {why}");
+                    }
+
+                    if let Err(why) = maintenance::run_scheduled() {
+                        log::error!("automatic maintenance failed: {why}");
+                    }
+                }
+            });
+
             // Setup files in filesystem
             let app_config = app.config();
             let app_data = tauri::api::path::app_config_dir(&app_config);
@@ -92,6 +129,7 @@ async fn main() {
             }
             Ok(())
         })
+        .manage(search::SearchOrchestrator::default())
         .system_tray(
             SystemTray::new().with_menu(
                 SystemTrayMenu::new()
@@ -110,21 +148,104 @@ async fn main() {
             handlers::splash_close,
             handlers::path_exists,
             handlers::get_all_manga,
+            handlers::get_library,
+            handlers::get_update_targets,
+            handlers::set_manga_added,
+            handlers::mark_manga_checked,
+            handlers::get_manga_override,
+            handlers::set_manga_override,
+            handlers::reset_manga_override,
             handlers::get_manga,
             handlers::get_mangas,
+            handlers::get_tags,
+            handlers::get_authors,
+            handlers::get_manga_by_tag,
+            handlers::get_manga_by_author,
             handlers::insert_manga,
             handlers::remove_manga,
             handlers::clear_manga,
+            handlers::find_duplicate_manga,
+            handlers::merge_manga,
+            handlers::get_linked_sources,
+            handlers::set_preferred_source,
+            handlers::unlink_source,
+            handlers::get_unified_chapters,
+            handlers::find_migration_candidates,
+            handlers::migrate_manga,
             handlers::get_all_chapters,
             handlers::get_chapter,
             handlers::get_chapters,
+            handlers::get_scanlators,
+            handlers::set_reading_progress,
+            handlers::get_chapter_progress,
+            handlers::get_resume_position,
+            handlers::add_bookmark,
+            handlers::update_bookmark,
+            handlers::remove_bookmark,
+            handlers::get_manga_bookmarks,
+            handlers::get_all_bookmarks,
+            handlers::get_bookmark_tags,
+            handlers::jump_to_bookmark,
+            handlers::set_chapters_read,
+            handlers::mark_previous_chapters_read,
+            handlers::reset_chapter_progress,
+            handlers::queue_chapter_downloads,
+            handlers::delete_chapter_downloads,
+            handlers::get_download_queue,
+            handlers::remove_from_download_queue,
+            handlers::mark_chapter_downloaded,
+            handlers::clear_download_queue,
             handlers::insert_chapter,
             handlers::remove_chapter,
             handlers::clear_chapters,
             handlers::get_sources,
+            handlers::get_source_preference_schema,
+            handlers::get_source_preferences,
+            handlers::get_source_preference,
+            handlers::set_source_preference,
+            handlers::uninstall_source,
+            handlers::start_global_search,
+            handlers::submit_search_result,
+            handlers::get_cached_search,
+            handlers::clear_search_cache,
+            handlers::get_recent_searches,
+            handlers::clear_recent_searches,
+            handlers::create_saved_search,
+            handlers::get_saved_searches,
+            handlers::set_saved_search_interval,
+            handlers::remove_saved_search,
+            handlers::run_saved_search,
+            handlers::save_settings_profile,
+            handlers::get_settings_profiles,
+            handlers::switch_settings_profile,
+            handlers::delete_settings_profile,
+            handlers::export_settings,
+            handlers::import_settings,
+            handlers::create_backup,
+            handlers::get_backups,
+            handlers::restore_backup,
+            handlers::import_tachiyomi_backup,
+            handlers::import_legacy_library,
+            handlers::repair_database,
+            handlers::get_quarantined_rows,
+            handlers::remove_quarantined_row,
+            handlers::run_maintenance,
+            handlers::get_maintenance_history,
+            handlers::get_tachiyomi_source_map,
+            handlers::set_tachiyomi_source_map,
+            handlers::remove_tachiyomi_source_map,
             handlers::get_reader_settings,
             handlers::set_reader_settings,
+            handlers::get_effective_reader_settings,
+            handlers::set_reader_override,
+            handlers::reset_reader_layer,
+            handlers::get_categories,
+            handlers::get_manga_categories,
+            handlers::add_manga_to_category,
+            handlers::remove_manga_from_category,
             handlers::set_app_settings,
+            handlers::patch_app_settings,
+            handlers::set_app_setting,
             handlers::get_app_settings,
             handlers::return_to_tray,
         ])
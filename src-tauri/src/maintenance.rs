@@ -0,0 +1,383 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::{json_column, open, ChapterDB, MangaDB},
+    downloadqueue::DownloadQueueDB,
+    errors::{AppError, Context},
+    get_db_path,
+    readerdb::ReaderDB,
+    repair::QuarantineDB,
+    settings::Settings,
+};
+
+/// Downloads live in `<Downloads.location>/Suwariyomi/<source>/<manga id>`.
+pub const DOWNLOADS_DIR: &str = "Suwariyomi";
+
+/// How many maintenance reports are kept around.
+pub const MAINTENANCE_HISTORY: usize = 20;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ForeignKeyViolation {
+    pub table: String,
+    pub rowid: Option<i64>,
+    pub parent: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MaintenanceReport {
+    pub dry_run: bool,
+    pub ran_at: i64,
+
+    /// Whatever `PRAGMA integrity_check` reported. Empty when the database is
+    /// sound; otherwise nothing else is touched.
+    pub integrity_errors: Vec<String>,
+    pub foreign_key_violations: Vec<ForeignKeyViolation>,
+
+    pub orphaned_chapters: usize,
+    pub orphaned_reader_settings: usize,
+    /// Authors, tags and scanlators no manga or chapter refers to anymore.
+    pub unused_names: usize,
+    /// Files of downloaded chapters whose chapter or manga is gone.
+    pub stale_downloads: Vec<PathBuf>,
+
+    pub vacuumed: bool,
+    pub freed_bytes: i64,
+}
+
+pub struct MaintenanceDB {
+    db: Connection,
+}
+
+impl MaintenanceDB {
+    pub fn new(path: &Option<std::path::PathBuf>) -> Result<Self, AppError> {
+        let db = open(path)?;
+
+        db.execute(
+            "CREATE TABLE IF NOT EXISTS MaintenanceRuns
+            (
+               ran_at INT NOT NULL,
+               report TEXT NOT NULL
+            )
+            ",
+            (),
+        )
+        .context("unable to create MaintenanceRuns table")?;
+
+        Ok(Self { db })
+    }
+
+    pub fn record(&self, report: &MaintenanceReport) -> Result<(), AppError> {
+        self.db.execute(
+            "INSERT INTO MaintenanceRuns (ran_at, report) VALUES (?1, ?2)",
+            (report.ran_at, serde_json::to_string(report)?),
+        )?;
+
+        self.db.execute(
+            "DELETE FROM MaintenanceRuns WHERE rowid NOT IN
+                (SELECT rowid FROM MaintenanceRuns ORDER BY ran_at DESC LIMIT ?1)",
+            [MAINTENANCE_HISTORY],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn last_run(&self) -> Result<Option<i64>, rusqlite::Error> {
+        self.db
+            .query_row("SELECT MAX(ran_at) FROM MaintenanceRuns", [], |row| {
+                row.get(0)
+            })
+            .optional()
+            .map(Option::flatten)
+    }
+
+    pub fn get_history(&self) -> Result<Vec<MaintenanceReport>, rusqlite::Error> {
+        let mut prepared_rows = self
+            .db
+            .prepare("SELECT * FROM MaintenanceRuns ORDER BY ran_at DESC")?;
+        let iter = prepared_rows.query_map([], |row| json_column(row, "report"))?;
+
+        iter.collect()
+    }
+}
+
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Where the chapters of a manga are downloaded to.
+#[must_use]
+pub fn manga_download_dir(location: &Path, source: &str, id: &str) -> PathBuf {
+    location
+        .join(DOWNLOADS_DIR)
+        .join(sanitize(source))
+        .join(sanitize(id))
+}
+
+/// Where a chapter may have been downloaded to: a directory of pages, or a
+/// CBZ archive when `Downloads.saveChaptersAsCBZ` was on.
+#[must_use]
+pub fn chapter_download_paths(
+    location: &Path,
+    source: &str,
+    manga_id: &str,
+    chapter_id: &str,
+) -> [PathBuf; 2] {
+    let path = manga_download_dir(location, source, manga_id).join(sanitize(chapter_id));
+    [path.clone(), path.with_extension("cbz")]
+}
+
+/// Whether the manga a row of `table` belongs to was quarantined by
+/// `repair`. Its chapters are kept until the quarantined row is dealt with.
+fn quarantined_manga(table: &str) -> String {
+    format!(
+        "EXISTS (SELECT 1 FROM QuarantinedRows WHERE source_table = 'Library'
+            AND json_extract(data, '$.source') = {table}.source
+            AND json_extract(data, '$.id') = {table}.manga_id)"
+    )
+}
+
+/// Forgets the downloads recorded in `DownloadedChapters` whose chapter or
+/// manga is no longer in the database, and returns the files they were
+/// downloaded to, to be removed once the transaction commits. Nothing else in
+/// the downloads directory is ever touched.
+fn stale_downloads(db: &Connection, location: &str) -> Result<Vec<PathBuf>, AppError> {
+    let mut prepared_rows = db.prepare(&format!(
+        "DELETE FROM DownloadedChapters
+        WHERE (NOT EXISTS (SELECT 1 FROM Chapters
+                WHERE Chapters.source = DownloadedChapters.source
+                AND Chapters.manga_id = DownloadedChapters.manga_id
+                AND Chapters.id = DownloadedChapters.chapter_id)
+            OR NOT EXISTS (SELECT 1 FROM Library
+                WHERE Library.source = DownloadedChapters.source
+                AND Library.id = DownloadedChapters.manga_id))
+        AND NOT {}
+        RETURNING source, manga_id, chapter_id",
+        quarantined_manga("DownloadedChapters")
+    ))?;
+    let stale = prepared_rows
+        .query_map([], |row| {
+            Ok((
+                row.get::<&str, String>("source")?,
+                row.get::<&str, String>("manga_id")?,
+                row.get::<&str, String>("chapter_id")?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if location.is_empty() {
+        return Ok(vec![]);
+    }
+
+    Ok(stale
+        .iter()
+        .flat_map(|(source, manga_id, chapter_id)| {
+            chapter_download_paths(Path::new(location), source, manga_id, chapter_id)
+        })
+        .filter(|path| path.exists())
+        .collect())
+}
+
+fn database_size(db: &Connection) -> Result<i64, rusqlite::Error> {
+    let page_count: i64 = db.query_row("PRAGMA page_count", [], |row| row.get(0))?;
+    let page_size: i64 = db.query_row("PRAGMA page_size", [], |row| row.get(0))?;
+
+    Ok(page_count * page_size)
+}
+
+/// Checks the database, removes chapters, reader settings and downloads that
+/// no longer belong to a manga in the library, then vacuums. With `dry_run`
+/// only the report of what would be removed is returned.
+pub fn run(dry_run: bool) -> Result<MaintenanceReport, AppError> {
+    MangaDB::new(&get_db_path())?;
+    ChapterDB::new(&get_db_path())?;
+    ReaderDB::new(&get_db_path())?;
+    DownloadQueueDB::new(&get_db_path())?;
+    QuarantineDB::new(&get_db_path())?;
+    let maintenance_db = MaintenanceDB::new(&get_db_path())?;
+
+    let mut db = open(&get_db_path())?;
+    let mut report = MaintenanceReport {
+        dry_run,
+        ran_at: chrono::Utc::now().timestamp(),
+        ..MaintenanceReport::default()
+    };
+
+    let mut prepared_rows = db.prepare("PRAGMA integrity_check")?;
+    report.integrity_errors = prepared_rows
+        .query_map([], |row| row.get::<usize, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .filter(|line| line != "ok")
+        .collect();
+    drop(prepared_rows);
+
+    let mut prepared_rows = db.prepare("PRAGMA foreign_key_check")?;
+    report.foreign_key_violations = prepared_rows
+        .query_map([], |row| {
+            Ok(ForeignKeyViolation {
+                table: row.get(0)?,
+                rowid: row.get(1)?,
+                parent: row.get(2)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    drop(prepared_rows);
+
+    if !report.integrity_errors.is_empty() {
+        log::error!(
+            "database failed its integrity check, skipping cleanup: {}",
+            report.integrity_errors.join("; ")
+        );
+
+        if !dry_run {
+            maintenance_db.record(&report)?;
+        }
+
+        return Ok(report);
+    }
+
+    let transaction = db.transaction()?;
+    // Before orphaned chapters are removed, which would forget their
+    // downloads along with them.
+    report.stale_downloads = stale_downloads(&transaction, &Settings {}.get()?.downloads.location)?;
+    report.orphaned_chapters = transaction.execute(
+        &format!(
+            "DELETE FROM Chapters WHERE NOT EXISTS
+                (SELECT 1 FROM Library WHERE Library.source = Chapters.source AND Library.id = Chapters.manga_id)
+            AND NOT {}",
+            quarantined_manga("Chapters")
+        ),
+        [],
+    )?;
+    report.orphaned_reader_settings = transaction.execute(
+        "DELETE FROM ReaderSettings WHERE NOT EXISTS
+            (SELECT 1 FROM Library WHERE Library.source = ReaderSettings.source AND Library.id = ReaderSettings.id)",
+        [],
+    )?;
+
+    for (table, link, key) in [
+        ("Author", "MangaAuthor", "author_id"),
+        ("Tag", "MangaTag", "tag_id"),
+        ("Scanlator", "ChapterScanlator", "scanlator_id"),
+    ] {
+        report.unused_names += transaction.execute(
+            &format!(
+                "DELETE FROM {table} WHERE NOT EXISTS
+                    (SELECT 1 FROM {link} WHERE {link}.{key} = {table}.id)"
+            ),
+            [],
+        )?;
+    }
+
+    if dry_run {
+        transaction.rollback()?;
+        return Ok(report);
+    }
+
+    transaction.commit()?;
+
+    for stale in &report.stale_downloads {
+        if stale.is_dir() {
+            fs::remove_dir_all(stale)
+        } else {
+            fs::remove_file(stale)
+        }
+        .context(format!("unable to remove {}", stale.display()))?;
+    }
+
+    let size_before = database_size(&db)?;
+    db.execute("VACUUM", [])?;
+    report.vacuumed = true;
+    report.freed_bytes = size_before - database_size(&db)?;
+
+    maintenance_db.record(&report)?;
+
+    Ok(report)
+}
+
+/// Runs maintenance if it's enabled and the interval has passed since the
+/// last run.
+pub fn run_scheduled() -> Result<Option<MaintenanceReport>, AppError> {
+    let settings = Settings {}.get()?.advanced;
+    if !settings.automatic_maintenance {
+        return Ok(None);
+    }
+
+    let last_run = MaintenanceDB::new(&get_db_path())?
+        .last_run()?
+        .unwrap_or_default();
+    if chrono::Utc::now().timestamp() - last_run
+        < settings.maintenance_interval.saturating_mul(60 * 60)
+    {
+        return Ok(None);
+    }
+
+    Ok(Some(run(false)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::TestDb;
+
+    #[test]
+    fn quarantined_downloads_are_kept() {
+        let test_db = TestDb::default();
+        let path = test_db.path.clone();
+        ChapterDB::new(&path).unwrap();
+        DownloadQueueDB::new(&path).unwrap();
+        QuarantineDB::new(&path).unwrap();
+        let db = open(&path).unwrap();
+        // The quarantined manga's row is gone, as after `repair`, and so is
+        // one of its chapters.
+        db.execute_batch(
+            "PRAGMA foreign_keys = OFF;
+            INSERT INTO QuarantinedRows (source_table, source_rowid, data, reason, quarantined_at)
+            VALUES ('Library', 1, '{\"source\":\"source\",\"id\":\"quarantined\"}', '', 0);
+            INSERT INTO Chapters (source, manga_id, id, title, chapter, pages, total,
+                last_read, last_updated, time_spent_reading, date_uploaded, scanlators, lang)
+            VALUES ('source', 'quarantined', '1', '', 1, 0, 0, 0, 0, 0, 0, '[]', 'en');
+            INSERT INTO DownloadedChapters (source, manga_id, chapter_id, downloaded_at)
+            VALUES ('source', 'quarantined', '1', 0),
+                ('source', 'quarantined', '2', 0),
+                ('source', 'orphaned', '1', 0);
+            PRAGMA foreign_keys = ON;",
+        )
+        .unwrap();
+
+        stale_downloads(&db, "").unwrap();
+
+        let mut prepared_rows = db
+            .prepare("SELECT manga_id, chapter_id FROM DownloadedChapters ORDER BY chapter_id")
+            .unwrap();
+        let kept = prepared_rows
+            .query_map([], |row| {
+                Ok((row.get::<usize, String>(0)?, row.get::<usize, String>(1)?))
+            })
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            kept,
+            vec![
+                ("quarantined".to_string(), "1".to_string()),
+                ("quarantined".to_string(), "2".to_string()),
+            ]
+        );
+    }
+}
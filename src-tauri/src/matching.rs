@@ -0,0 +1,53 @@
+use std::collections::HashSet;
+
+use crate::db::Chapter;
+
+#[must_use]
+pub fn normalize_title(title: &str) -> String {
+    title
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// Scores how alike two titles are, from `0.0` to `1.0`, by comparing the
+/// words they share once case and punctuation are ignored.
+#[must_use]
+pub fn title_similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (normalize_title(a), normalize_title(b));
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+
+    if a == b {
+        return 1.0;
+    }
+
+    let a_words: HashSet<&str> = a.split(' ').collect();
+    let b_words: HashSet<&str> = b.split(' ').collect();
+    let shared = a_words.intersection(&b_words).count();
+    let total = a_words.union(&b_words).count();
+
+    #[allow(clippy::cast_precision_loss)]
+    let score = shared as f64 / total as f64;
+    score
+}
+
+/// Picks the chapter in `candidates` with the same chapter number as
+/// `chapter`, preferring one from the same volume.
+#[must_use]
+pub fn find_counterpart<'a>(chapter: &Chapter, candidates: &'a [Chapter]) -> Option<&'a Chapter> {
+    let mut same_number = candidates.iter().filter(|c| c.same_number(chapter));
+    let first = same_number.next()?;
+
+    Some(
+        std::iter::once(first)
+            .chain(same_number)
+            .find(|c| c.volume == chapter.volume)
+            .unwrap_or(first),
+    )
+}
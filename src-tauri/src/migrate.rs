@@ -0,0 +1,267 @@
+use futures_util::future::join_all;
+use rusqlite::{OptionalExtension, Transaction};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    bookmarks::BookmarkDB,
+    categories::CategoryDB,
+    db::{
+        decode_rows, generate_chapter_from_row, generate_manga_from_row, index_effective_manga,
+        open, upsert_chapter, upsert_manga, Chapter, DecodeMode, Manga, MangaDB,
+    },
+    errors::AppError,
+    get_db_path,
+    matching::{find_counterpart, title_similarity},
+    progress::ProgressDB,
+    readerdb::ReaderDB,
+    search::{SearchOrchestrator, SourceSearchStatus},
+};
+
+#[derive(Debug, Serialize)]
+pub struct MigrationCandidate {
+    pub source: String,
+    pub manga: Value,
+    pub score: f64,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct MigrationReport {
+    pub migrated_chapters: usize,
+    /// Ids of old chapters with reading progress that had no counterpart in the target.
+    pub unmatched_chapters: Vec<String>,
+    pub reader_settings: bool,
+    pub categories: usize,
+    pub bookmarks: usize,
+    pub overrides: bool,
+    /// Entries merged into the old one that now point at the new one.
+    pub linked_sources: usize,
+    pub removed_old: bool,
+}
+
+/// Searches each of `targets` for the title of the library entry and returns
+/// every result, best title match first.
+pub async fn find_candidates(
+    window: &tauri::Window,
+    orchestrator: &SearchOrchestrator,
+    source: String,
+    id: String,
+    targets: Vec<String>,
+) -> Result<Vec<MigrationCandidate>, AppError> {
+    let manga = MangaDB::new(&get_db_path())?
+        .get(id, source.clone())?
+        .ok_or_else(|| AppError::not_found("manga is not in the library"))?;
+
+    let searches = targets
+        .into_iter()
+        .filter(|target| *target != source)
+        .map(|target| {
+            let name = manga.name.clone();
+            async move {
+                let status = orchestrator
+                    .request_source(
+                        window,
+                        orchestrator.next_search_id(),
+                        &target,
+                        &name,
+                        &Value::Null,
+                    )
+                    .await;
+
+                (target, status)
+            }
+        });
+
+    let mut candidates: Vec<MigrationCandidate> = join_all(searches)
+        .await
+        .into_iter()
+        .filter_map(|(target, status)| match status {
+            SourceSearchStatus::Done { results, .. } => Some((target, results)),
+            _ => None,
+        })
+        .flat_map(|(target, results)| {
+            let title = &manga.name;
+            results
+                .get("data")
+                .and_then(Value::as_array)
+                .cloned()
+                .unwrap_or_default()
+                .into_iter()
+                .map(move |found| MigrationCandidate {
+                    source: target.clone(),
+                    score: found
+                        .get("name")
+                        .and_then(Value::as_str)
+                        .map_or(0.0, |name| title_similarity(title, name)),
+                    manga: found,
+                })
+        })
+        .collect();
+
+    candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+    Ok(candidates)
+}
+
+/// Copies the rows of `table` belonging to the manga `from` over to `to`.
+/// `key` names the table's manga id column, and `columns` the rest to copy.
+fn copy_manga_rows(
+    transaction: &Transaction,
+    table: &str,
+    key: &str,
+    columns: &str,
+    from: (&str, &str),
+    to: (&str, &str),
+) -> Result<usize, rusqlite::Error> {
+    transaction.execute(
+        &format!(
+            "INSERT OR IGNORE INTO {table} (source, {key}, {columns})
+            SELECT ?3, ?4, {columns} FROM {table} WHERE source = ?1 AND {key} = ?2"
+        ),
+        (from.0, from.1, to.0, to.1),
+    )
+}
+
+/// Moves the library entry `(source, id)` onto `target`, carrying reading
+/// progress over to `target_chapters` by chapter number, along with the
+/// entry's bookmarks, categories, reader settings, metadata overrides and
+/// source links. Either all of it is moved or none of it.
+pub fn migrate(
+    source: String,
+    id: String,
+    mut target: Manga,
+    target_chapters: Vec<Chapter>,
+    keep_old: bool,
+) -> Result<MigrationReport, AppError> {
+    if target.source == source && target.id == id {
+        return Err(AppError::validation("cannot migrate a manga onto itself"));
+    }
+
+    // Everything hanging off a library entry moves with it, so make sure
+    // those tables all exist first.
+    MangaDB::new(&get_db_path())?;
+    ReaderDB::new(&get_db_path())?;
+    CategoryDB::new(&get_db_path())?;
+    ProgressDB::new(&get_db_path())?;
+    BookmarkDB::new(&get_db_path())?;
+
+    let mut db = open(&get_db_path())?;
+    let transaction = db.transaction()?;
+
+    // The source's own metadata; overrides are carried over separately.
+    let old = transaction
+        .query_row(
+            "SELECT * FROM Library WHERE source = ?1 AND id = ?2",
+            [&source, &id],
+            generate_manga_from_row,
+        )
+        .optional()?
+        .ok_or_else(|| AppError::not_found("manga is not in the library"))?;
+    let old_chapters = decode_rows(
+        &transaction,
+        "Chapters",
+        "SELECT rowid AS row_id, * FROM Chapters WHERE source = ?1 AND manga_id = ?2",
+        [&source, &id],
+        generate_chapter_from_row,
+        DecodeMode::Strict,
+    )?
+    .rows;
+
+    let mut report = MigrationReport::default();
+    let from = (source.as_str(), id.as_str());
+
+    target.added = old.added;
+    upsert_manga(&transaction, target.clone())?;
+    let to = (target.source.as_str(), target.id.as_str());
+
+    let mut new_chapters = vec![];
+    for mut chapter in target_chapters {
+        chapter.source = target.source.clone();
+        chapter.manga_id = target.id.clone();
+
+        let previous = find_counterpart(&chapter, &old_chapters);
+        if let Some(previous) = previous {
+            // A chapter counts as read once `pages` reaches `total`, so
+            // they're carried over together.
+            chapter.last_read = previous.last_read;
+            chapter.time_spent_reading = previous.time_spent_reading;
+            chapter.pages = previous.pages;
+            chapter.total = previous.total;
+        }
+
+        upsert_chapter(&transaction, chapter.clone())?;
+        report.migrated_chapters += 1;
+
+        if let Some(previous) = previous {
+            transaction.execute(
+                "REPLACE INTO ChapterProgress
+                    (source, manga_id, chapter_id, page, scroll_offset, updated_at)
+                SELECT ?4, ?5, ?6, page, scroll_offset, updated_at FROM ChapterProgress
+                WHERE source = ?1 AND manga_id = ?2 AND chapter_id = ?3",
+                (&source, &id, &previous.id, to.0, to.1, &chapter.id),
+            )?;
+
+            report.bookmarks += transaction.execute(
+                "INSERT OR IGNORE INTO Bookmark
+                    (source, manga_id, chapter_id, page, note, tag, created_at)
+                SELECT ?4, ?5, ?6, page, note, tag, created_at FROM Bookmark
+                WHERE source = ?1 AND manga_id = ?2 AND chapter_id = ?3",
+                (&source, &id, &previous.id, to.0, to.1, &chapter.id),
+            )?;
+        }
+
+        new_chapters.push(chapter);
+    }
+
+    report.unmatched_chapters = old_chapters
+        .iter()
+        .filter(|old| old.last_read > 0 || old.time_spent_reading > 0)
+        .filter(|old| find_counterpart(old, &new_chapters).is_none())
+        .map(|old| old.id.clone())
+        .collect();
+
+    report.categories =
+        copy_manga_rows(&transaction, "MangaCategories", "id", "category", from, to)?;
+
+    transaction.execute(
+        "DELETE FROM ReaderSettings WHERE source = ?1 AND id = ?2",
+        to,
+    )?;
+    report.reader_settings =
+        copy_manga_rows(&transaction, "ReaderSettings", "id", "data", from, to)? > 0;
+
+    report.overrides = copy_manga_rows(
+        &transaction,
+        "MangaOverrides",
+        "id",
+        "name, description, authors, tags, cover, status",
+        from,
+        to,
+    )? > 0;
+    if report.overrides {
+        index_effective_manga(&transaction, to.0, to.1)?;
+    }
+
+    if !keep_old {
+        // Entries merged into the old one follow it. Its chapters, and
+        // everything hanging off them, go with the row.
+        report.linked_sources = transaction.execute(
+            "UPDATE LinkedSources SET source = ?3, id = ?4 WHERE source = ?1 AND id = ?2",
+            (from.0, from.1, to.0, to.1),
+        )?;
+        transaction.execute(
+            "DELETE FROM LinkedSources WHERE linked_source = ?1 AND linked_id = ?2",
+            from,
+        )?;
+        for table in ["MangaCategories", "ReaderSettings"] {
+            transaction.execute(
+                &format!("DELETE FROM {table} WHERE source = ?1 AND id = ?2"),
+                from,
+            )?;
+        }
+        transaction.execute("DELETE FROM Library WHERE source = ?1 AND id = ?2", from)?;
+        report.removed_old = true;
+    }
+
+    transaction.commit()?;
+    Ok(report)
+}
@@ -0,0 +1,183 @@
+use std::{fs, path::Path};
+
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    db::open,
+    errors::{AppError, Context},
+    get_db_path,
+    readerdb::{ReaderDB, ReaderOverride},
+    settings::{AppSettings, Settings, SettingsChanged},
+};
+
+/// Everything a profile or an export carries: the app settings, which include
+/// the global reader defaults, plus every source and category reader override.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingsBundle {
+    pub app: Value,
+    #[serde(default)]
+    pub reader_overrides: Vec<ReaderOverride>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Json,
+    Toml,
+}
+
+impl ExportFormat {
+    /// Guesses the format from a file extension, defaulting to JSON.
+    #[must_use]
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(std::ffi::OsStr::to_str) {
+            Some("toml") => Self::Toml,
+            _ => Self::Json,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SettingsProfile {
+    pub name: String,
+    pub active: bool,
+    pub updated_at: i64,
+}
+
+impl SettingsBundle {
+    pub fn current() -> Result<Self, AppError> {
+        Ok(Self {
+            app: serde_json::to_value(Settings {}.get()?)?,
+            reader_overrides: ReaderDB::new(&get_db_path())?.get_overrides()?,
+        })
+    }
+
+    /// Replaces the current settings and reader overrides with this bundle.
+    /// Nothing is touched if the app settings don't validate, and the settings
+    /// file is only written once the overrides are.
+    pub fn apply(self) -> Result<SettingsChanged, AppError> {
+        AppSettings::from_value(self.app.clone())?;
+
+        ReaderDB::new(&get_db_path())?.replace_overrides(self.reader_overrides)?;
+
+        Settings {}.set(self.app)
+    }
+
+    pub fn encode(&self, format: ExportFormat) -> Result<String, AppError> {
+        match format {
+            ExportFormat::Json => Ok(serde_json::to_string_pretty(self)?),
+            ExportFormat::Toml => toml::Value::try_from(self)
+                .and_then(|value| toml::to_string_pretty(&value))
+                .map_err(AppError::from),
+        }
+    }
+
+    pub fn decode(contents: &str, format: ExportFormat) -> Result<Self, AppError> {
+        match format {
+            ExportFormat::Json => Ok(serde_json::from_str(contents)?),
+            ExportFormat::Toml => toml::from_str(contents).map_err(AppError::from),
+        }
+    }
+}
+
+pub struct ProfileDB {
+    db: Connection,
+}
+
+impl ProfileDB {
+    pub fn new(path: &Option<std::path::PathBuf>) -> Result<Self, AppError> {
+        let db = open(path)?;
+
+        db.execute(
+            "CREATE TABLE IF NOT EXISTS SettingsProfiles
+            (
+               name TEXT NOT NULL PRIMARY KEY,
+               data TEXT NOT NULL,
+               active INT NOT NULL,
+               updated_at INT NOT NULL
+            )
+            ",
+            (),
+        )
+        .context("unable to create SettingsProfiles table")?;
+
+        Ok(Self { db })
+    }
+
+    pub fn save(&self, name: String, bundle: &SettingsBundle) -> Result<usize, AppError> {
+        Ok(self.db.execute(
+            "INSERT INTO SettingsProfiles (name, data, active, updated_at) VALUES (?1, ?2, 0, ?3)
+                ON CONFLICT(name) DO UPDATE SET data = ?2, updated_at = ?3",
+            (
+                name,
+                serde_json::to_string(bundle)?,
+                chrono::Utc::now().timestamp(),
+            ),
+        )?)
+    }
+
+    pub fn get(&self, name: String) -> Result<Option<SettingsBundle>, AppError> {
+        let data: Option<String> = self
+            .db
+            .query_row(
+                "SELECT data FROM SettingsProfiles WHERE name = ?1",
+                [name],
+                |row| row.get("data"),
+            )
+            .optional()?;
+
+        data.map_or(Ok(None), |data| Ok(Some(serde_json::from_str(&data)?)))
+    }
+
+    pub fn get_all(&self) -> Result<Vec<SettingsProfile>, rusqlite::Error> {
+        let mut prepared_rows = self
+            .db
+            .prepare("SELECT name, active, updated_at FROM SettingsProfiles ORDER BY name")?;
+        let iter = prepared_rows.query_map([], |row| {
+            Ok(SettingsProfile {
+                name: row.get("name")?,
+                active: row.get("active")?,
+                updated_at: row.get("updated_at")?,
+            })
+        })?;
+
+        iter.collect()
+    }
+
+    pub fn set_active(&self, name: String) -> Result<usize, rusqlite::Error> {
+        self.db
+            .execute("UPDATE SettingsProfiles SET active = (name = ?1)", [name])
+    }
+
+    pub fn delete(&self, name: String) -> Result<usize, rusqlite::Error> {
+        self.db
+            .execute("DELETE FROM SettingsProfiles WHERE name = ?1", [name])
+    }
+}
+
+/// Applies the saved profile `name` and marks it as the active one.
+pub fn switch(name: String) -> Result<SettingsChanged, AppError> {
+    let profile_db = ProfileDB::new(&get_db_path())?;
+    let bundle = profile_db
+        .get(name.clone())?
+        .ok_or_else(|| AppError::not_found(format!("no settings profile named '{name}'")))?;
+
+    let changed = bundle.apply()?;
+    profile_db.set_active(name)?;
+
+    Ok(changed)
+}
+
+pub fn export(path: &Path, format: Option<ExportFormat>) -> Result<(), AppError> {
+    let format = format.unwrap_or_else(|| ExportFormat::from_path(path));
+    fs::write(path, SettingsBundle::current()?.encode(format)?).context("unable to write export")
+}
+
+pub fn import(path: &Path, format: Option<ExportFormat>) -> Result<SettingsChanged, AppError> {
+    let format = format.unwrap_or_else(|| ExportFormat::from_path(path));
+    let contents = fs::read_to_string(path).context("unable to read import")?;
+
+    SettingsBundle::decode(&contents, format)?.apply()
+}
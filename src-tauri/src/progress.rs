@@ -0,0 +1,235 @@
+use rusqlite::{Connection, OptionalExtension, Row};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    db::{open, ChapterDB},
+    errors::{AppError, Context},
+    get_db_path, readerdb,
+};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChapterProgress {
+    pub source: String,
+    pub manga_id: String,
+    pub chapter_id: String,
+
+    /// The page being shown, starting at 1.
+    pub page: i32,
+    /// How far down the page long-strip mode was scrolled, from 0 to 1.
+    pub scroll_offset: f64,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProgressUpdate {
+    #[serde(flatten)]
+    pub progress: ChapterProgress,
+    /// Whether this update took the chapter past `Reader.markReadThreshold`.
+    pub marked_read: bool,
+}
+
+pub struct ProgressDB {
+    db: Connection,
+}
+
+fn generate_progress_from_row(row: &Row) -> Result<ChapterProgress, rusqlite::Error> {
+    Ok(ChapterProgress {
+        source: row.get("source")?,
+        manga_id: row.get("manga_id")?,
+        chapter_id: row.get("chapter_id")?,
+        page: row.get("page")?,
+        scroll_offset: row.get("scroll_offset")?,
+        updated_at: row.get("updated_at")?,
+    })
+}
+
+impl ProgressDB {
+    pub fn new(path: &Option<std::path::PathBuf>) -> Result<Self, AppError> {
+        // Progress references `Chapters`, so make sure it exists first.
+        ChapterDB::new(path)?;
+        let db = open(path)?;
+
+        db.execute_batch(
+            "CREATE TABLE IF NOT EXISTS ChapterProgress
+            (
+               source TEXT NOT NULL,
+               manga_id TEXT NOT NULL,
+               chapter_id TEXT NOT NULL,
+               page INT NOT NULL,
+               scroll_offset REAL NOT NULL,
+               updated_at INT NOT NULL,
+
+               PRIMARY KEY (source, manga_id, chapter_id),
+               FOREIGN KEY (source, manga_id, chapter_id)
+                   REFERENCES Chapters (source, manga_id, id) ON DELETE CASCADE
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_chapter_progress_updated
+                ON ChapterProgress (source, manga_id, updated_at);
+            ",
+        )
+        .context("unable to create ChapterProgress table")?;
+
+        Ok(Self { db })
+    }
+
+    pub fn get(
+        &self,
+        source: &str,
+        manga_id: &str,
+        chapter_id: &str,
+    ) -> Result<Option<ChapterProgress>, rusqlite::Error> {
+        self.db
+            .query_row(
+                "SELECT * FROM ChapterProgress
+                WHERE source = ?1 AND manga_id = ?2 AND chapter_id = ?3",
+                [source, manga_id, chapter_id],
+                generate_progress_from_row,
+            )
+            .optional()
+    }
+
+    /// The chapter of a manga that was read most recently.
+    pub fn get_latest(
+        &self,
+        source: &str,
+        manga_id: &str,
+    ) -> Result<Option<ChapterProgress>, rusqlite::Error> {
+        self.db
+            .query_row(
+                "SELECT * FROM ChapterProgress WHERE source = ?1 AND manga_id = ?2
+                ORDER BY updated_at DESC LIMIT 1",
+                [source, manga_id],
+                generate_progress_from_row,
+            )
+            .optional()
+    }
+
+    fn set(
+        &self,
+        progress: &ChapterProgress,
+        pages: i32,
+        total: i32,
+    ) -> Result<(), rusqlite::Error> {
+        let transaction = self.db.unchecked_transaction()?;
+        transaction.execute(
+            "REPLACE INTO ChapterProgress
+                (source, manga_id, chapter_id, page, scroll_offset, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            (
+                &progress.source,
+                &progress.manga_id,
+                &progress.chapter_id,
+                progress.page,
+                progress.scroll_offset,
+                progress.updated_at,
+            ),
+        )?;
+        transaction.execute(
+            "UPDATE Chapters SET pages = ?4, total = ?5, last_read = ?6
+            WHERE source = ?1 AND manga_id = ?2 AND id = ?3",
+            (
+                &progress.source,
+                &progress.manga_id,
+                &progress.chapter_id,
+                pages,
+                total,
+                progress.updated_at,
+            ),
+        )?;
+
+        transaction.commit()
+    }
+}
+
+/// Records that `page` of a chapter is being shown. `total` updates the
+/// chapter's page count when the reader knows it. Reaching the manga's
+/// effective `Reader.markReadThreshold` marks the chapter read.
+pub fn record(
+    source: String,
+    manga_id: String,
+    chapter_id: String,
+    page: i32,
+    total: Option<i32>,
+    scroll_offset: Option<f64>,
+) -> Result<ProgressUpdate, AppError> {
+    if page < 1 {
+        return Err(AppError::validation("page numbers start at 1"));
+    }
+
+    let scroll_offset = scroll_offset.unwrap_or(0.0);
+    if !(0.0..=1.0).contains(&scroll_offset) {
+        return Err(AppError::validation(
+            "scroll offset must be between 0 and 1",
+        ));
+    }
+
+    let chapter = ChapterDB::new(&get_db_path())?
+        .get(source.clone(), chapter_id.clone(), manga_id.clone())?
+        .ok_or_else(|| AppError::not_found("chapter is not in the library"))?;
+
+    let threshold = readerdb::resolve(source.clone(), manga_id.clone())?
+        .settings
+        .get("markReadThreshold")
+        .and_then(Value::as_f64)
+        .unwrap_or(1.0);
+
+    let total = total.filter(|total| *total > 0).unwrap_or(chapter.total);
+    let was_read = chapter.total > 0 && chapter.pages >= chapter.total;
+    let reached = total > 0 && f64::from(page) / f64::from(total) >= threshold;
+    let pages = if reached {
+        total
+    } else {
+        chapter.pages.max(page)
+    };
+
+    let progress = ChapterProgress {
+        source,
+        manga_id,
+        chapter_id,
+        page,
+        scroll_offset,
+        updated_at: chrono::Utc::now().timestamp_millis(),
+    };
+    ProgressDB::new(&get_db_path())?.set(&progress, pages, total)?;
+
+    Ok(ProgressUpdate {
+        progress,
+        marked_read: reached && !was_read,
+    })
+}
+
+/// Where to pick a manga back up: the page last shown in the chapter read
+/// most recently, or the start of the chapter after it if that one is read.
+pub fn resume_position(
+    source: String,
+    manga_id: String,
+) -> Result<Option<ChapterProgress>, AppError> {
+    let latest = match ProgressDB::new(&get_db_path())?.get_latest(&source, &manga_id)? {
+        Some(latest) => latest,
+        None => return Ok(None),
+    };
+
+    let chapters = ChapterDB::new(&get_db_path())?.get_for_manga(source, manga_id)?;
+    let position = chapters
+        .iter()
+        .position(|chapter| chapter.id == latest.chapter_id);
+    let finished = position
+        .map(|position| &chapters[position])
+        .is_some_and(|chapter| chapter.total > 0 && chapter.pages >= chapter.total);
+
+    let next = position
+        .filter(|_| finished)
+        .and_then(|position| chapters.get(position + 1));
+
+    Ok(Some(match next {
+        Some(next) => ChapterProgress {
+            chapter_id: next.id.clone(),
+            page: 1,
+            scroll_offset: 0.0,
+            ..latest
+        },
+        None => latest,
+    }))
+}
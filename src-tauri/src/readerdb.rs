@@ -1,56 +1,150 @@
 use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    categories::CategoryDB,
+    db::open,
+    errors::{AppError, Context},
+    get_db_path,
+    settings::Settings,
+};
+
+/// A level reader settings can be set at. Later layers win over earlier ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReaderLayer {
+    Global,
+    Source,
+    Category,
+    Manga,
+}
+
+impl ReaderLayer {
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Global => "global",
+            Self::Source => "source",
+            Self::Category => "category",
+            Self::Manga => "manga",
+        }
+    }
+
+    fn parse(layer: &str) -> Option<Self> {
+        match layer {
+            "global" => Some(Self::Global),
+            "source" => Some(Self::Source),
+            "category" => Some(Self::Category),
+            "manga" => Some(Self::Manga),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReaderOverride {
+    pub layer: ReaderLayer,
+    pub scope: String,
+    pub data: Value,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ValueOrigin {
+    pub layer: ReaderLayer,
+    /// The source id or category name for those layers.
+    pub scope: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EffectiveReaderSettings {
+    pub settings: Map<String, Value>,
+    pub origins: std::collections::BTreeMap<String, ValueOrigin>,
+}
 
 pub struct ReaderDB {
     db: Connection,
 }
 
-impl ReaderDB {
-    #[must_use]
-    pub fn new(path: &Option<std::path::PathBuf>) -> Self {
-        let db = path.as_ref().map_or_else(
-            || Connection::open_in_memory().expect("unable to open in-memory database"),
-            |p| {
-                let p2 = &p.clone();
-                Connection::open(p2).unwrap_or_else(|_| {
-                    panic!(
-                        "unable to open database from path {}",
-                        &p2.to_str().unwrap()
-                    )
-                })
-            },
+/// Tables made before `ReaderSettings` had a primary key gained a new row on
+/// every save, so only the most recently written row for each manga is kept.
+fn migrate_settings_key(db: &mut Connection) -> Result<(), rusqlite::Error> {
+    let unkeyed: bool = db.query_row(
+        "SELECT COUNT(*) > 0 AND SUM(pk) = 0 FROM pragma_table_info('ReaderSettings')",
+        [],
+        |row| row.get(0),
+    )?;
+    if !unkeyed {
+        return Ok(());
+    }
+
+    let transaction = db.transaction()?;
+    transaction.execute_batch(
+        "ALTER TABLE ReaderSettings RENAME TO ReaderSettingsUnkeyed;
+        CREATE TABLE ReaderSettings
+        (
+           source TEXT NOT NULL,
+           id TEXT NOT NULL,
+           data TEXT NOT NULL,
+           PRIMARY KEY (source, id)
         );
+        INSERT INTO ReaderSettings (source, id, data)
+        SELECT source, id, data FROM ReaderSettingsUnkeyed
+        WHERE rowid IN (SELECT MAX(rowid) FROM ReaderSettingsUnkeyed GROUP BY source, id);
+        DROP TABLE ReaderSettingsUnkeyed;",
+    )?;
+    transaction.commit()
+}
+
+impl ReaderDB {
+    pub fn new(path: &Option<std::path::PathBuf>) -> Result<Self, AppError> {
+        let mut db = open(path)?;
 
-        if let Err(why) = db.execute(
+        migrate_settings_key(&mut db).context("unable to key ReaderSettings table")?;
+
+        db.execute(
             "CREATE TABLE IF NOT EXISTS ReaderSettings
             (
                source TEXT NOT NULL,
                id TEXT NOT NULL,
-               data TEXT NOT NULL
+               data TEXT NOT NULL,
+               PRIMARY KEY (source, id)
             )
             ",
             (),
-        ) {
-            panic!(
-                "unable to create ReaderSettings table: {}",
-                why.to_string().as_str(),
-            );
-        };
+        )
+        .context("unable to create ReaderSettings table")?;
 
-        Self { db }
+        db.execute(
+            "CREATE TABLE IF NOT EXISTS ReaderOverrides
+            (
+               layer TEXT NOT NULL,
+               scope TEXT NOT NULL,
+               data TEXT NOT NULL,
+               PRIMARY KEY (layer, scope)
+            )
+            ",
+            (),
+        )
+        .context("unable to create ReaderOverrides table")?;
+
+        Ok(Self { db })
     }
 
-    pub fn insert<T>(&self, source: String, id: String, json_data: T)
+    pub fn insert<T>(
+        &self,
+        source: String,
+        id: String,
+        json_data: T,
+    ) -> Result<usize, rusqlite::Error>
     where
         T: serde::Serialize + rusqlite::ToSql,
     {
-        self.db
-            .execute(
-                "REPLACE INTO ReaderSettings
+        self.db.execute(
+            "REPLACE INTO ReaderSettings
                 (source, id, data) VALUES (?1, ?2, ?3)
             ",
-                (source, id, json_data),
-            )
-            .map_or_else(|_| (), |_| ())
+            (source, id, json_data),
+        )
     }
 
     pub fn get(
@@ -66,4 +160,210 @@ impl ReaderDB {
             )
             .optional()
     }
+
+    pub fn delete(&self, source: String, id: String) -> Result<usize, rusqlite::Error> {
+        self.db.execute(
+            "DELETE FROM ReaderSettings WHERE id = ?1 AND source = ?2",
+            (id, source),
+        )
+    }
+
+    /// Stores the overrides for a source or category layer.
+    pub fn set_override(
+        &self,
+        layer: ReaderLayer,
+        scope: String,
+        data: &Value,
+    ) -> Result<usize, rusqlite::Error> {
+        self.db.execute(
+            "REPLACE INTO ReaderOverrides (layer, scope, data) VALUES (?1, ?2, ?3)",
+            (layer.as_str(), scope, data.to_string()),
+        )
+    }
+
+    pub fn get_override(
+        &self,
+        layer: ReaderLayer,
+        scope: String,
+    ) -> Result<Option<Value>, rusqlite::Error> {
+        self.db
+            .query_row(
+                "SELECT data FROM ReaderOverrides WHERE layer = ?1 AND scope = ?2",
+                (layer.as_str(), scope),
+                |row| row.get("data"),
+            )
+            .optional()
+    }
+
+    pub fn get_overrides(&self) -> Result<Vec<ReaderOverride>, rusqlite::Error> {
+        let mut prepared_rows = self.db.prepare("SELECT * FROM ReaderOverrides")?;
+        let iter = prepared_rows.query_map([], |row| {
+            Ok(ReaderOverride {
+                layer: ReaderLayer::parse(row.get::<&str, String>("layer")?.as_str())
+                    .unwrap_or(ReaderLayer::Source),
+                scope: row.get("scope")?,
+                data: row.get("data")?,
+            })
+        })?;
+
+        iter.collect()
+    }
+
+    /// Swaps every source and category override for `overrides`, all at once.
+    pub fn replace_overrides(
+        &mut self,
+        overrides: Vec<ReaderOverride>,
+    ) -> Result<(), rusqlite::Error> {
+        let transaction = self.db.transaction()?;
+        transaction.execute("DELETE FROM ReaderOverrides", [])?;
+        for ReaderOverride { layer, scope, data } in overrides {
+            transaction.execute(
+                "INSERT INTO ReaderOverrides (layer, scope, data) VALUES (?1, ?2, ?3)",
+                (layer.as_str(), scope, data.to_string()),
+            )?;
+        }
+
+        transaction.commit()
+    }
+
+    pub fn delete_override(
+        &self,
+        layer: ReaderLayer,
+        scope: String,
+    ) -> Result<usize, rusqlite::Error> {
+        self.db.execute(
+            "DELETE FROM ReaderOverrides WHERE layer = ?1 AND scope = ?2",
+            (layer.as_str(), scope),
+        )
+    }
+}
+
+fn apply_layer(
+    effective: &mut EffectiveReaderSettings,
+    layer: ReaderLayer,
+    scope: Option<&str>,
+    data: Option<Value>,
+) {
+    if let Some(Value::Object(data)) = data {
+        for (key, value) in data {
+            effective.origins.insert(
+                key.clone(),
+                ValueOrigin {
+                    layer,
+                    scope: scope.map(ToString::to_string),
+                },
+            );
+            effective.settings.insert(key, value);
+        }
+    }
+}
+
+/// Merges the global reader defaults with the overrides for the manga's
+/// source, each of its categories (in name order) and the manga itself.
+pub fn resolve(source: String, id: String) -> Result<EffectiveReaderSettings, AppError> {
+    let reader_db = ReaderDB::new(&get_db_path())?;
+    let mut effective = EffectiveReaderSettings {
+        settings: Map::new(),
+        origins: std::collections::BTreeMap::new(),
+    };
+
+    let global = serde_json::to_value(Settings {}.get()?.reader)?;
+    apply_layer(&mut effective, ReaderLayer::Global, None, Some(global));
+
+    let source_layer = reader_db.get_override(ReaderLayer::Source, source.clone())?;
+    apply_layer(
+        &mut effective,
+        ReaderLayer::Source,
+        Some(&source),
+        source_layer,
+    );
+
+    for category in CategoryDB::new(&get_db_path())?.get_for_manga(source.clone(), id.clone())? {
+        let category_layer = reader_db.get_override(ReaderLayer::Category, category.clone())?;
+        apply_layer(
+            &mut effective,
+            ReaderLayer::Category,
+            Some(&category),
+            category_layer,
+        );
+    }
+
+    let manga_layer = reader_db.get(source, id)?;
+    apply_layer(&mut effective, ReaderLayer::Manga, None, manga_layer);
+
+    Ok(effective)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::TestDb;
+
+    #[test]
+    fn saving_again_replaces_the_settings() {
+        let test_db = TestDb::default();
+        let reader_db = ReaderDB::new(&test_db.path).unwrap();
+
+        let first = serde_json::json!({ "zoom": 1 });
+        let second = serde_json::json!({ "zoom": 2 });
+        reader_db.insert("src".into(), "1".into(), &first).unwrap();
+        reader_db.insert("src".into(), "1".into(), &second).unwrap();
+
+        assert_eq!(
+            reader_db.get("src".into(), "1".into()).unwrap(),
+            Some(second)
+        );
+        let rows: i64 = reader_db
+            .db
+            .query_row("SELECT COUNT(*) FROM ReaderSettings", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(rows, 1);
+    }
+
+    #[test]
+    fn replacing_overrides_drops_the_old_ones() {
+        let test_db = TestDb::default();
+        let mut reader_db = ReaderDB::new(&test_db.path).unwrap();
+        reader_db
+            .set_override(ReaderLayer::Source, "old".into(), &serde_json::json!({}))
+            .unwrap();
+
+        reader_db
+            .replace_overrides(vec![ReaderOverride {
+                layer: ReaderLayer::Category,
+                scope: "new".into(),
+                data: serde_json::json!({ "zoom": 2 }),
+            }])
+            .unwrap();
+
+        let overrides = reader_db.get_overrides().unwrap();
+        assert_eq!(overrides.len(), 1);
+        assert_eq!(overrides[0].layer, ReaderLayer::Category);
+        assert_eq!(overrides[0].scope, "new");
+    }
+
+    #[test]
+    fn unkeyed_settings_keep_the_latest_save() {
+        let test_db = TestDb::default();
+        open(&test_db.path)
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE ReaderSettings (source TEXT NOT NULL, id TEXT NOT NULL, data TEXT NOT NULL);
+                INSERT INTO ReaderSettings VALUES ('src', '1', '{\"zoom\":1}');
+                INSERT INTO ReaderSettings VALUES ('src', '1', '{\"zoom\":2}');
+                INSERT INTO ReaderSettings VALUES ('src', '2', '{\"zoom\":3}');",
+            )
+            .unwrap();
+
+        let reader_db = ReaderDB::new(&test_db.path).unwrap();
+
+        assert_eq!(
+            reader_db.get("src".into(), "1".into()).unwrap(),
+            Some(serde_json::json!({ "zoom": 2 }))
+        );
+        assert_eq!(
+            reader_db.get("src".into(), "2".into()).unwrap(),
+            Some(serde_json::json!({ "zoom": 3 }))
+        );
+    }
 }
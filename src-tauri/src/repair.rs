@@ -0,0 +1,360 @@
+use rusqlite::{types::ValueRef, Connection, Row, Transaction};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::{
+    backup::sql_to_json,
+    db::{
+        decode_rows, generate_chapter_from_row, generate_manga_from_row, open,
+        rebuild_metadata_index, ChapterDB, CorruptRow, DecodeMode, MangaDB, MangaLink,
+    },
+    errors::{AppError, Context},
+    get_db_path,
+};
+
+type RowCheck = fn(&Row) -> Result<(), rusqlite::Error>;
+
+/// Rewrites a column into something that decodes, or returns `None` if it
+/// already does or can't be salvaged.
+type Salvage = fn(ValueRef) -> Option<String>;
+
+type JsonColumns = &'static [(&'static str, Salvage)];
+
+/// The tables `repair` scans, the JSON columns in each and how they're
+/// salvaged, and how a row of each is decoded.
+const REPAIRABLE_TABLES: [(&str, JsonColumns, RowCheck); 2] = [
+    (
+        "Library",
+        &[
+            ("covers", repair_json_list),
+            ("authors", repair_json_list),
+            ("chapters", repair_json_list),
+            ("tags", repair_json_list),
+            ("alt_titles", repair_json_list),
+            ("artists", repair_json_list),
+            ("links", repair_links),
+        ],
+        |row| generate_manga_from_row(row).map(|_| ()),
+    ),
+    ("Chapters", &[("scanlators", repair_json_list)], |row| {
+        generate_chapter_from_row(row).map(|_| ())
+    }),
+];
+
+#[derive(Debug, Serialize)]
+pub struct QuarantinedRow {
+    pub id: i64,
+    pub table: String,
+    pub rowid: i64,
+    pub data: Value,
+    pub reason: String,
+    pub quarantined_at: i64,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct RepairReport {
+    pub dry_run: bool,
+    pub scanned: usize,
+    pub repaired: Vec<CorruptRow>,
+    pub quarantined: Vec<CorruptRow>,
+}
+
+pub struct QuarantineDB {
+    db: Connection,
+}
+
+impl QuarantineDB {
+    pub fn new(path: &Option<std::path::PathBuf>) -> Result<Self, AppError> {
+        let db = open(path)?;
+
+        db.execute(
+            "CREATE TABLE IF NOT EXISTS QuarantinedRows
+            (
+               id INTEGER PRIMARY KEY AUTOINCREMENT,
+               source_table TEXT NOT NULL,
+               source_rowid INT NOT NULL,
+               data TEXT NOT NULL,
+               reason TEXT NOT NULL,
+               quarantined_at INT NOT NULL
+            )
+            ",
+            (),
+        )
+        .context("unable to create QuarantinedRows table")?;
+
+        Ok(Self { db })
+    }
+
+    pub fn get_all(&self) -> Result<Vec<QuarantinedRow>, rusqlite::Error> {
+        let mut prepared_rows = self
+            .db
+            .prepare("SELECT * FROM QuarantinedRows ORDER BY quarantined_at DESC")?;
+        let iter = prepared_rows.query_map([], |row| {
+            Ok(QuarantinedRow {
+                id: row.get("id")?,
+                table: row.get("source_table")?,
+                rowid: row.get("source_rowid")?,
+                data: serde_json::from_str(row.get::<&str, String>("data")?.as_str())
+                    .unwrap_or_default(),
+                reason: row.get("reason")?,
+                quarantined_at: row.get("quarantined_at")?,
+            })
+        })?;
+
+        iter.collect()
+    }
+
+    pub fn delete(&self, id: i64) -> Result<usize, rusqlite::Error> {
+        self.db
+            .execute("DELETE FROM QuarantinedRows WHERE id = ?1", [id])
+    }
+}
+
+/// Rewrites a column that should hold a JSON list of strings into one, or
+/// returns `None` if it already is one or can't be salvaged.
+fn repair_json_list(value: ValueRef) -> Option<String> {
+    let text = match value {
+        ValueRef::Null => return Some("[]".to_string()),
+        ValueRef::Integer(i) => return serde_json::to_string(&[i.to_string()]).ok(),
+        ValueRef::Real(f) => return serde_json::to_string(&[f.to_string()]).ok(),
+        ValueRef::Text(t) | ValueRef::Blob(t) => std::str::from_utf8(t).ok()?,
+    };
+
+    if serde_json::from_str::<Vec<String>>(text).is_ok() {
+        return None;
+    }
+
+    let list: Vec<String> = match serde_json::from_str::<Value>(text) {
+        Ok(Value::Null) => vec![],
+        Ok(Value::String(s)) => vec![s],
+        Ok(Value::Array(values)) => values
+            .into_iter()
+            .filter(|value| !value.is_null())
+            .map(|value| match value {
+                Value::String(s) => s,
+                other => other.to_string(),
+            })
+            .collect(),
+        Ok(other) => vec![other.to_string()],
+        Err(_) if text.trim().is_empty() => vec![],
+        Err(_) => vec![text.trim().to_string()],
+    };
+
+    serde_json::to_string(&list).ok()
+}
+
+/// Keeps whichever entries of a `links` column still decode, or resets it to
+/// no links.
+fn repair_links(value: ValueRef) -> Option<String> {
+    let text = match value {
+        ValueRef::Text(t) | ValueRef::Blob(t) => std::str::from_utf8(t).ok(),
+        _ => None,
+    };
+
+    if text.is_some_and(|text| serde_json::from_str::<Vec<MangaLink>>(text).is_ok()) {
+        return None;
+    }
+
+    let links: Vec<MangaLink> = text
+        .and_then(|text| serde_json::from_str::<Vec<Value>>(text).ok())
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|value| serde_json::from_value(value).ok())
+        .collect();
+
+    serde_json::to_string(&links).ok()
+}
+
+fn row_object(row: &Row) -> Result<Map<String, Value>, rusqlite::Error> {
+    let mut object = Map::new();
+    for (index, column) in row.as_ref().column_names().into_iter().enumerate() {
+        if column != "row_id" {
+            object.insert(column.to_string(), sql_to_json(row.get_ref(index)?));
+        }
+    }
+
+    Ok(object)
+}
+
+fn quarantine(
+    transaction: &Transaction,
+    corrupt: &CorruptRow,
+    data: &Map<String, Value>,
+) -> Result<(), rusqlite::Error> {
+    transaction.execute(
+        "INSERT INTO QuarantinedRows
+            (source_table, source_rowid, data, reason, quarantined_at)
+        VALUES (?1, ?2, ?3, ?4, ?5)",
+        (
+            &corrupt.table,
+            corrupt.rowid,
+            Value::from(data.clone()).to_string(),
+            corrupt.to_string(),
+            chrono::Utc::now().timestamp(),
+        ),
+    )?;
+
+    transaction.execute(
+        &format!("DELETE FROM {} WHERE rowid = ?1", corrupt.table),
+        [corrupt.rowid],
+    )?;
+
+    Ok(())
+}
+
+fn repair_table(
+    transaction: &Transaction,
+    table: &str,
+    json_columns: JsonColumns,
+    check: RowCheck,
+    report: &mut RepairReport,
+) -> Result<(), AppError> {
+    let select = format!("SELECT rowid AS row_id, * FROM {table} WHERE rowid = ?1");
+    let decoded = decode_rows(
+        transaction,
+        table,
+        &format!("SELECT rowid AS row_id, * FROM {table}"),
+        [],
+        check,
+        DecodeMode::SkipCorrupt,
+    )?;
+    report.scanned += decoded.rows.len() + decoded.corrupt.len();
+
+    for corrupt in decoded.corrupt {
+        let original = transaction.query_row(&select, [corrupt.rowid], row_object)?;
+
+        let mut rewritten = false;
+        for (column, salvage) in json_columns {
+            let repaired = transaction.query_row(&select, [corrupt.rowid], |row| {
+                Ok(salvage(row.get_ref(*column)?))
+            })?;
+
+            if let Some(repaired) = repaired {
+                transaction.execute(
+                    &format!("UPDATE {table} SET {column} = ?1 WHERE rowid = ?2"),
+                    (repaired, corrupt.rowid),
+                )?;
+                rewritten = true;
+            }
+        }
+
+        let fixed = rewritten
+            && transaction
+                .query_row(&select, [corrupt.rowid], check)
+                .is_ok();
+
+        if fixed {
+            report.repaired.push(corrupt);
+        } else {
+            quarantine(transaction, &corrupt, &original)?;
+            report.quarantined.push(corrupt);
+        }
+    }
+
+    Ok(())
+}
+
+/// Finds every library and chapter row that no longer decodes. Malformed list
+/// columns are rewritten in place; rows that still don't decode afterwards
+/// are moved to `QuarantinedRows`. With `dry_run` nothing is kept.
+pub fn repair(dry_run: bool) -> Result<RepairReport, AppError> {
+    repair_at(&get_db_path(), dry_run)
+}
+
+fn repair_at(path: &Option<std::path::PathBuf>, dry_run: bool) -> Result<RepairReport, AppError> {
+    MangaDB::new(path)?;
+    ChapterDB::new(path)?;
+    QuarantineDB::new(path)?;
+
+    let mut db = open(path)?;
+    // Quarantining a row mustn't cascade to the chapters, progress and
+    // bookmarks hanging off it; they're kept for when it's repaired by hand.
+    // This can't change inside a transaction.
+    db.execute_batch("PRAGMA foreign_keys = OFF")?;
+    let transaction = db.transaction()?;
+    let mut report = RepairReport {
+        dry_run,
+        ..RepairReport::default()
+    };
+
+    for (table, json_columns, check) in REPAIRABLE_TABLES {
+        repair_table(&transaction, table, json_columns, check, &mut report)
+            .context(format!("unable to repair {table}"))?;
+    }
+
+    if dry_run {
+        transaction.rollback()?;
+        return Ok(report);
+    }
+
+    transaction.commit()?;
+    if !report.repaired.is_empty() || !report.quarantined.is_empty() {
+        rebuild_metadata_index(&db)?;
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::TestDb;
+
+    #[test]
+    fn quarantining_a_manga_keeps_its_chapters() {
+        let test_db = TestDb::default();
+        let path = test_db.path.clone();
+        ChapterDB::new(&path).unwrap();
+        let db = open(&path).unwrap();
+        db.execute_batch(
+            "INSERT INTO Library (source, id, name, description, covers, chapters, authors, tags,
+                uploaded, added)
+            VALUES ('source', 'manga', 'Manga', '', '[]', '[]', '[]', '[]', 'never', 0);
+            INSERT INTO Chapters (source, manga_id, id, title, chapter, pages, total, last_read,
+                last_updated, time_spent_reading, date_uploaded, scanlators, lang)
+            VALUES ('source', 'manga', 'chapter', 'Chapter 1', 1, 0, 0, 0, 0, 0, 0, '[]', 'en');",
+        )
+        .unwrap();
+
+        let report = repair_at(&path, false).unwrap();
+        assert_eq!(report.quarantined.len(), 1);
+        assert_eq!(report.quarantined[0].table, "Library");
+
+        let manga: i64 = db
+            .query_row("SELECT COUNT(*) FROM Library", [], |row| row.get(0))
+            .unwrap();
+        let chapters: i64 = db
+            .query_row("SELECT COUNT(*) FROM Chapters", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(manga, 0);
+        assert_eq!(chapters, 1);
+        assert_eq!(
+            QuarantineDB::new(&path).unwrap().get_all().unwrap().len(),
+            1
+        );
+    }
+
+    #[test]
+    fn malformed_links_are_salvaged() {
+        let test_db = TestDb::default();
+        let path = test_db.path.clone();
+        ChapterDB::new(&path).unwrap();
+        let db = open(&path).unwrap();
+        db.execute_batch(
+            r#"INSERT INTO Library (source, id, name, description, covers, chapters, authors, tags,
+                uploaded, added, links)
+            VALUES ('source', 'manga', 'Manga', '', '[]', '[]', '[]', '[]', 0, 0,
+                '[{"name": "Site", "url": "https://example.com"}, "https://example.org"]');"#,
+        )
+        .unwrap();
+
+        let report = repair_at(&path, false).unwrap();
+        assert_eq!(report.repaired.len(), 1);
+        assert!(report.quarantined.is_empty());
+
+        let links: String = db
+            .query_row("SELECT links FROM Library", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(links, r#"[{"name":"Site","url":"https://example.com"}]"#);
+    }
+}
@@ -0,0 +1,326 @@
+use std::{collections::HashSet, time::Duration};
+
+use rusqlite::{Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    db::open,
+    errors::{AppError, Context, ErrorCode},
+    get_db_path,
+    search::{SearchOrchestrator, SourceSearchStatus},
+};
+
+/// How often the scheduler looks for saved searches that are due.
+pub const SCHEDULER_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Seconds to wait before retrying a failed scheduled run. Doubles with every
+/// failure in a row, up to the search's own interval.
+pub const RETRY_DELAY: i64 = 5 * 60;
+
+/// How many manga ids a saved search remembers. The oldest are forgotten
+/// first, but whatever the latest run found is always kept.
+pub const MAX_KNOWN_IDS: usize = 500;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedSearch {
+    pub id: i64,
+    pub name: String,
+    pub source: String,
+    pub query: String,
+    pub filters: Value,
+
+    /// Seconds between scheduled runs. `0` disables scheduling.
+    pub interval: i64,
+    pub last_run: i64,
+    pub known_ids: Vec<String>,
+
+    /// Failed runs since the last one that succeeded.
+    pub failures: i64,
+    /// When a failed search is tried again. `0` unless `failures` is.
+    pub retry_at: i64,
+}
+
+/// Emitted as `saved-search-alert` when a run finds manga that weren't in the
+/// previous results.
+#[derive(Debug, Clone, Serialize)]
+pub struct SavedSearchAlert {
+    pub saved_search_id: i64,
+    pub name: String,
+    pub source: String,
+    pub new_manga: Vec<Value>,
+}
+
+pub struct SavedSearchDB {
+    db: Connection,
+}
+
+fn generate_saved_search_from_row(row: &Row) -> Result<SavedSearch, rusqlite::Error> {
+    Ok(SavedSearch {
+        id: row.get("id")?,
+        name: row.get("name")?,
+        source: row.get("source")?,
+        query: row.get("query")?,
+        filters: serde_json::from_str(row.get::<&str, String>("filters")?.as_str())
+            .unwrap_or_default(),
+
+        interval: row.get("interval")?,
+        last_run: row.get("last_run")?,
+        known_ids: serde_json::from_str(row.get::<&str, String>("known_ids")?.as_str())
+            .unwrap_or_default(),
+
+        failures: row.get("failures")?,
+        retry_at: row.get("retry_at")?,
+    })
+}
+
+/// How long to wait after the `failures`th failed run in a row.
+fn retry_delay(failures: i64, interval: i64) -> i64 {
+    let delay = RETRY_DELAY.saturating_mul(1 << (failures - 1).clamp(0, 32));
+    delay.min(interval.max(RETRY_DELAY))
+}
+
+impl SavedSearchDB {
+    pub fn new(path: &Option<std::path::PathBuf>) -> Result<Self, AppError> {
+        let db = open(path)?;
+
+        db.execute(
+            "CREATE TABLE IF NOT EXISTS SavedSearches
+            (
+               id INTEGER PRIMARY KEY AUTOINCREMENT,
+               name TEXT NOT NULL,
+               source TEXT NOT NULL,
+               query TEXT NOT NULL,
+               filters TEXT NOT NULL,
+               interval INT NOT NULL,
+               last_run INT NOT NULL,
+               known_ids TEXT NOT NULL,
+               failures INT NOT NULL DEFAULT 0,
+               retry_at INT NOT NULL DEFAULT 0
+            )
+            ",
+            (),
+        )
+        .context("unable to create SavedSearches table")?;
+
+        // Tables made before failed runs were tracked lack their columns.
+        let has_failures: bool = db.query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('SavedSearches') WHERE name = 'failures'",
+            [],
+            |row| row.get(0),
+        )?;
+        if !has_failures {
+            db.execute_batch(
+                "ALTER TABLE SavedSearches ADD COLUMN failures INT NOT NULL DEFAULT 0;
+                ALTER TABLE SavedSearches ADD COLUMN retry_at INT NOT NULL DEFAULT 0;",
+            )
+            .context("unable to add retry columns to SavedSearches")?;
+        }
+
+        Ok(Self { db })
+    }
+
+    pub fn insert(
+        &self,
+        name: String,
+        source: String,
+        query: String,
+        filters: &Value,
+        interval: i64,
+    ) -> Result<i64, rusqlite::Error> {
+        self.db.execute(
+            "INSERT INTO SavedSearches
+                (name, source, query, filters, interval, last_run, known_ids)
+            VALUES (?1, ?2, ?3, ?4, ?5, 0, '[]')",
+            (name, source, query, filters.to_string(), interval),
+        )?;
+
+        Ok(self.db.last_insert_rowid())
+    }
+
+    pub fn get(&self, id: i64) -> Result<Option<SavedSearch>, rusqlite::Error> {
+        self.db
+            .query_row(
+                "SELECT * FROM SavedSearches WHERE id = ?1",
+                [id],
+                generate_saved_search_from_row,
+            )
+            .optional()
+    }
+
+    pub fn get_all(&self) -> Result<Vec<SavedSearch>, rusqlite::Error> {
+        let mut prepared_rows = self.db.prepare("SELECT * FROM SavedSearches")?;
+        let iter = prepared_rows.query_map([], generate_saved_search_from_row)?;
+
+        iter.collect()
+    }
+
+    pub fn get_due(&self, now: i64) -> Result<Vec<SavedSearch>, rusqlite::Error> {
+        let mut prepared_rows = self.db.prepare(
+            "SELECT * FROM SavedSearches
+            WHERE interval > 0 AND last_run + interval <= ?1 AND retry_at <= ?1",
+        )?;
+        let iter = prepared_rows.query_map([now], generate_saved_search_from_row)?;
+
+        iter.collect()
+    }
+
+    pub fn set_interval(&self, id: i64, interval: i64) -> Result<usize, rusqlite::Error> {
+        self.db.execute(
+            "UPDATE SavedSearches SET interval = ?2 WHERE id = ?1",
+            (id, interval),
+        )
+    }
+
+    pub fn record_run(
+        &self,
+        id: i64,
+        ran_at: i64,
+        known_ids: &[String],
+    ) -> Result<usize, rusqlite::Error> {
+        self.db.execute(
+            "UPDATE SavedSearches SET last_run = ?2, known_ids = ?3, failures = 0, retry_at = 0
+            WHERE id = ?1",
+            (id, ran_at, serde_json::to_string(known_ids).unwrap()),
+        )
+    }
+
+    /// Puts off the next run of a search that just failed, backing off further
+    /// with every failure in a row.
+    pub fn record_failure(
+        &self,
+        saved: &SavedSearch,
+        failed_at: i64,
+    ) -> Result<usize, rusqlite::Error> {
+        let failures = saved.failures + 1;
+        self.db.execute(
+            "UPDATE SavedSearches SET failures = ?2, retry_at = ?3 WHERE id = ?1",
+            (
+                saved.id,
+                failures,
+                failed_at + retry_delay(failures, saved.interval),
+            ),
+        )
+    }
+
+    pub fn delete(&self, id: i64) -> Result<usize, rusqlite::Error> {
+        self.db
+            .execute("DELETE FROM SavedSearches WHERE id = ?1", [id])
+    }
+}
+
+/// Runs `saved` through the orchestrator and returns the manga that weren't
+/// part of its previous results. The first run only records a baseline.
+pub async fn run(
+    window: &tauri::Window,
+    orchestrator: &SearchOrchestrator,
+    saved: &SavedSearch,
+) -> Result<Vec<Value>, AppError> {
+    let status = orchestrator
+        .request_source(
+            window,
+            orchestrator.next_search_id(),
+            &saved.source,
+            &saved.query,
+            &saved.filters,
+        )
+        .await;
+
+    let results = match status {
+        SourceSearchStatus::Done { results, .. } => results,
+        SourceSearchStatus::Failed { message } => {
+            SavedSearchDB::new(&get_db_path())?
+                .record_failure(saved, chrono::Utc::now().timestamp())?;
+            return Err(AppError::source(message));
+        }
+        SourceSearchStatus::TimedOut => {
+            SavedSearchDB::new(&get_db_path())?
+                .record_failure(saved, chrono::Utc::now().timestamp())?;
+            return Err(AppError::new(ErrorCode::Network, "search timed out"));
+        }
+    };
+
+    let found: Vec<Value> = results
+        .get("data")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+    let mut found_ids: Vec<String> = vec![];
+    for id in found
+        .iter()
+        .filter_map(|manga| manga.get("id").and_then(Value::as_str))
+    {
+        if !found_ids.iter().any(|found_id| found_id == id) {
+            found_ids.push(id.to_string());
+        }
+    }
+
+    let known: HashSet<&String> = saved.known_ids.iter().collect();
+    let new_manga = if saved.last_run == 0 {
+        vec![]
+    } else {
+        found
+            .into_iter()
+            .filter(|manga| {
+                manga
+                    .get("id")
+                    .and_then(Value::as_str)
+                    .is_some_and(|id| !known.contains(&id.to_string()))
+            })
+            .collect()
+    };
+
+    // Oldest first, with this run's results moved to the end so they're the
+    // last to be forgotten.
+    let mut known_ids: Vec<String> = saved
+        .known_ids
+        .iter()
+        .filter(|id| !found_ids.contains(id))
+        .cloned()
+        .collect();
+    let excess = (known_ids.len() + found_ids.len()).saturating_sub(MAX_KNOWN_IDS);
+    known_ids.drain(..excess.min(known_ids.len()));
+    known_ids.extend(found_ids);
+
+    SavedSearchDB::new(&get_db_path())?.record_run(
+        saved.id,
+        chrono::Utc::now().timestamp(),
+        &known_ids,
+    )?;
+
+    Ok(new_manga)
+}
+
+/// Runs every saved search that is due and emits `saved-search-alert` for the
+/// ones that turned up something new.
+pub async fn run_due(window: &tauri::Window, orchestrator: &SearchOrchestrator) {
+    let due = match SavedSearchDB::new(&get_db_path())
+        .and_then(|db| Ok(db.get_due(chrono::Utc::now().timestamp())?))
+    {
+        Ok(due) => due,
+        Err(why) => {
+            log::warn!("unable to load saved searches: {why}");
+            return;
+        }
+    };
+
+    for saved in due {
+        match run(window, orchestrator, &saved).await {
+            Ok(new_manga) if !new_manga.is_empty() => {
+                if let Err(why) = window.emit(
+                    "saved-search-alert",
+                    SavedSearchAlert {
+                        saved_search_id: saved.id,
+                        name: saved.name,
+                        source: saved.source,
+                        new_manga,
+                    },
+                ) {
+                    log::warn!("unable to emit saved search alert: {why}");
+                }
+            }
+            Ok(_) => {}
+            Err(why) => log::warn!("saved search '{}' failed: {why}", saved.name),
+        }
+    }
+}
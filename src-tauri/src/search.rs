@@ -0,0 +1,357 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use rusqlite::{Connection, OptionalExtension};
+use serde::Serialize;
+use serde_json::Value;
+use tokio::sync::{oneshot, Semaphore};
+
+use crate::{
+    db::open,
+    errors::{AppError, Context},
+    get_db_path,
+};
+
+pub const MAX_CONCURRENT_SOURCES: usize = 4;
+pub const SOURCE_TIMEOUT: Duration = Duration::from_secs(20);
+pub const CACHE_TTL_SECONDS: i64 = 60 * 60;
+pub const RECENT_SEARCH_LIMIT: usize = 25;
+
+pub struct SearchDB {
+    db: Connection,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecentSearch {
+    pub query: String,
+    pub searched_at: i64,
+}
+
+fn normalize_query(query: &str) -> String {
+    query.trim().to_lowercase()
+}
+
+impl SearchDB {
+    pub fn new(path: &Option<std::path::PathBuf>) -> Result<Self, AppError> {
+        let db = open(path)?;
+
+        db.execute_batch(
+            "CREATE TABLE IF NOT EXISTS SearchCache
+            (
+               source TEXT NOT NULL,
+               query TEXT NOT NULL,
+               filters TEXT NOT NULL,
+               results TEXT NOT NULL,
+               cached_at INT NOT NULL,
+               PRIMARY KEY (source, query, filters)
+            );
+
+            CREATE TABLE IF NOT EXISTS RecentSearches
+            (
+               query TEXT NOT NULL PRIMARY KEY,
+               searched_at INT NOT NULL
+            );
+            ",
+        )
+        .context("unable to create search tables")?;
+
+        Ok(Self { db })
+    }
+
+    pub fn get_cached(
+        &self,
+        source: &str,
+        query: &str,
+        filters: &Value,
+    ) -> Result<Option<Value>, rusqlite::Error> {
+        let oldest = chrono::Utc::now().timestamp() - CACHE_TTL_SECONDS;
+        let cached: Option<String> = self
+            .db
+            .query_row(
+                "SELECT results FROM SearchCache
+                WHERE source = ?1 AND query = ?2 AND filters = ?3 AND cached_at >= ?4",
+                (source, normalize_query(query), filters.to_string(), oldest),
+                |row| row.get("results"),
+            )
+            .optional()?;
+
+        Ok(cached.and_then(|v| serde_json::from_str(&v).ok()))
+    }
+
+    pub fn cache(
+        &self,
+        source: &str,
+        query: &str,
+        filters: &Value,
+        results: &Value,
+    ) -> Result<usize, rusqlite::Error> {
+        self.db.execute(
+            "REPLACE INTO SearchCache
+                (source, query, filters, results, cached_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            (
+                source,
+                normalize_query(query),
+                filters.to_string(),
+                results.to_string(),
+                chrono::Utc::now().timestamp(),
+            ),
+        )
+    }
+
+    /// Drops every cache entry that has outlived `CACHE_TTL_SECONDS`.
+    pub fn prune_cache(&self) -> Result<usize, rusqlite::Error> {
+        self.db.execute(
+            "DELETE FROM SearchCache WHERE cached_at < ?1",
+            [chrono::Utc::now().timestamp() - CACHE_TTL_SECONDS],
+        )
+    }
+
+    pub fn clear_cache(&self) -> Result<(), rusqlite::Error> {
+        match self.db.execute("DELETE FROM SearchCache", []) {
+            Ok(..) => Ok(()),
+            Err(y) => Err(y),
+        }
+    }
+
+    pub fn push_recent(&self, query: &str) -> Result<(), rusqlite::Error> {
+        let query = query.trim();
+        if query.is_empty() {
+            return Ok(());
+        }
+
+        self.db.execute(
+            "REPLACE INTO RecentSearches (query, searched_at) VALUES (?1, ?2)",
+            (query, chrono::Utc::now().timestamp()),
+        )?;
+
+        self.db.execute(
+            "DELETE FROM RecentSearches WHERE query NOT IN
+                (SELECT query FROM RecentSearches ORDER BY searched_at DESC LIMIT ?1)",
+            [RECENT_SEARCH_LIMIT],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn get_recent(&self) -> Result<Vec<RecentSearch>, rusqlite::Error> {
+        let mut prepared_rows = self
+            .db
+            .prepare("SELECT * FROM RecentSearches ORDER BY searched_at DESC")?;
+        let iter = prepared_rows.query_map([], |row| {
+            Ok(RecentSearch {
+                query: row.get("query")?,
+                searched_at: row.get("searched_at")?,
+            })
+        })?;
+
+        iter.collect()
+    }
+
+    pub fn clear_recent(&self) -> Result<(), rusqlite::Error> {
+        match self.db.execute("DELETE FROM RecentSearches", []) {
+            Ok(..) => Ok(()),
+            Err(y) => Err(y),
+        }
+    }
+}
+
+/// Emitted to the window so the webview runs `Source::search` for one source.
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceSearchRequest {
+    pub search_id: u64,
+    pub source: String,
+    pub query: String,
+    pub filters: Value,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "kebab-case")]
+pub enum SourceSearchStatus {
+    Done { results: Value, cached: bool },
+    Failed { message: String },
+    TimedOut,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceSearchResult {
+    pub search_id: u64,
+    pub source: String,
+
+    #[serde(flatten)]
+    pub status: SourceSearchStatus,
+}
+
+type PendingSearches = HashMap<(u64, String), oneshot::Sender<Result<Value, String>>>;
+
+#[derive(Default, Clone)]
+pub struct SearchOrchestrator {
+    pending: Arc<Mutex<PendingSearches>>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl SearchOrchestrator {
+    /// Starts a search across `sources` and returns its id immediately.
+    ///
+    /// Each source emits a `search-result` event as it finishes, followed by a
+    /// single `search-complete` event carrying the search id.
+    pub fn start(
+        &self,
+        window: tauri::Window,
+        query: String,
+        sources: Vec<String>,
+        filters: Value,
+    ) -> u64 {
+        let search_id = self.next_search_id();
+        let orchestrator = self.clone();
+
+        tauri::async_runtime::spawn(async move {
+            match SearchDB::new(&get_db_path()) {
+                Ok(search_db) => {
+                    if let Err(why) = search_db.push_recent(&query) {
+                        log::warn!("unable to save recent search: {why}");
+                    }
+
+                    if let Err(why) = search_db.prune_cache() {
+                        log::warn!("unable to prune search cache: {why}");
+                    }
+                }
+                Err(why) => log::warn!("unable to open search database: {why}"),
+            }
+
+            let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT_SOURCES));
+            let handles: Vec<_> = sources
+                .into_iter()
+                .map(|source| {
+                    let orchestrator = orchestrator.clone();
+                    let semaphore = semaphore.clone();
+                    let window = window.clone();
+                    let query = query.clone();
+                    let filters = filters.clone();
+
+                    tauri::async_runtime::spawn(async move {
+                        let _permit = semaphore.acquire_owned().await;
+                        let status = orchestrator
+                            .search_source(&window, search_id, &source, &query, &filters)
+                            .await;
+
+                        if let Err(why) = window.emit(
+                            "search-result",
+                            SourceSearchResult {
+                                search_id,
+                                source,
+                                status,
+                            },
+                        ) {
+                            log::warn!("unable to emit search result: {why}");
+                        }
+                    })
+                })
+                .collect();
+
+            for handle in handles {
+                let _ = handle.await;
+            }
+
+            if let Err(why) = window.emit("search-complete", search_id) {
+                log::warn!("unable to emit search completion: {why}");
+            }
+        });
+
+        search_id
+    }
+
+    async fn search_source(
+        &self,
+        window: &tauri::Window,
+        search_id: u64,
+        source: &str,
+        query: &str,
+        filters: &Value,
+    ) -> SourceSearchStatus {
+        if let Ok(Some(results)) = SearchDB::new(&get_db_path())
+            .and_then(|search_db| Ok(search_db.get_cached(source, query, filters)?))
+        {
+            return SourceSearchStatus::Done {
+                results,
+                cached: true,
+            };
+        }
+
+        self.request_source(window, search_id, source, query, filters)
+            .await
+    }
+
+    #[must_use]
+    pub fn next_search_id(&self) -> u64 {
+        self.next_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Asks the webview to search `source`, bypassing the cache, and caches
+    /// whatever comes back.
+    pub async fn request_source(
+        &self,
+        window: &tauri::Window,
+        search_id: u64,
+        source: &str,
+        query: &str,
+        filters: &Value,
+    ) -> SourceSearchStatus {
+        let key = (search_id, source.to_string());
+        let (sender, receiver) = oneshot::channel();
+        self.pending.lock().unwrap().insert(key.clone(), sender);
+
+        if let Err(why) = window.emit(
+            "search-source-request",
+            SourceSearchRequest {
+                search_id,
+                source: source.to_string(),
+                query: query.to_string(),
+                filters: filters.clone(),
+            },
+        ) {
+            self.pending.lock().unwrap().remove(&key);
+            return SourceSearchStatus::Failed {
+                message: why.to_string(),
+            };
+        }
+
+        let response = tokio::time::timeout(SOURCE_TIMEOUT, receiver).await;
+        self.pending.lock().unwrap().remove(&key);
+
+        match response {
+            Ok(Ok(Ok(results))) => {
+                if let Err(why) = SearchDB::new(&get_db_path())
+                    .and_then(|search_db| Ok(search_db.cache(source, query, filters, &results)?))
+                {
+                    log::warn!("unable to cache search results: {why}");
+                }
+
+                SourceSearchStatus::Done {
+                    results,
+                    cached: false,
+                }
+            }
+            Ok(Ok(Err(message))) => SourceSearchStatus::Failed { message },
+            Ok(Err(_)) => SourceSearchStatus::Failed {
+                message: "search was abandoned".to_string(),
+            },
+            Err(_) => SourceSearchStatus::TimedOut,
+        }
+    }
+
+    /// Hands a source's results back to the search waiting on them. Returns
+    /// `false` if that search already timed out or never asked for them.
+    pub fn submit(&self, search_id: u64, source: String, results: Result<Value, String>) -> bool {
+        self.pending
+            .lock()
+            .unwrap()
+            .remove(&(search_id, source))
+            .is_some_and(|sender| sender.send(results).is_ok())
+    }
+}
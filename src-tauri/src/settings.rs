@@ -1,24 +1,596 @@
-use serde_json::{json, Value};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
 use std::{
-    fs::{read, write},
+    fs::{read, rename, write},
     path::{Path, PathBuf},
+    sync::Mutex,
 };
 use tauri::api::path::BaseDirectory;
 
-use crate::errors::InternalError;
+use crate::errors::{AppError, Context};
 
-pub struct Settings {}
+/// The version `AppSettings` is written as. Bump this and add an entry to
+/// `MIGRATIONS` whenever the shape of the settings file changes.
+pub const SETTINGS_VERSION: u32 = 1;
 
-impl Settings {
-    pub fn new(init: Option<Value>) -> Self {
-        if let Some(_initial_settings) = init {
-            return Self {};
-        };
+pub const SUPPORTED_LOCALES: [&str; 1] = ["en"];
+
+/// The longest scheduled task interval, in hours.
+pub const MAX_INTERVAL: i64 = 24 * 366;
+
+/// Held for the whole read-modify-write of the settings file so concurrent
+/// windows can't clobber each other's changes.
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+/// `MIGRATIONS[n]` upgrades a settings file from version `n` to `n + 1`.
+const MIGRATIONS: [fn(&mut Map<String, Value>); 1] = [
+    // Files written before versioning used the same layout, minus the version.
+    |_| {},
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ThemeType {
+    Light,
+    Dark,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UpdateFrequency {
+    Manual,
+    #[serde(rename = "Twice Daily")]
+    TwiceDaily,
+    Daily,
+    #[serde(rename = "Two Days")]
+    TwoDays,
+    #[serde(rename = "Three Days")]
+    ThreeDays,
+    Weekly,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PageType {
+    #[serde(rename = "Single Page")]
+    SinglePage,
+    #[serde(rename = "Double Page")]
+    DoublePage,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DateFormat {
+    #[serde(rename = "MM/DD/YYYY")]
+    MMDDYYYY,
+    #[serde(rename = "DD/MM/YYYY")]
+    DDMMYYYY,
+    #[serde(rename = "YYYY/MM/DD")]
+    YYYYMMDD,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReadingMode {
+    #[serde(rename = "Right To Left")]
+    RightToLeft,
+    #[serde(rename = "Left To Right")]
+    LeftToRight,
+    Vertical,
+    Webtoon,
+    #[serde(rename = "Continuous Vertical")]
+    ContinuousVertical,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NavigationLayout {
+    #[serde(rename = "Top And Bottom")]
+    TopAndBottom,
+    #[serde(rename = "Left And Right")]
+    LeftAndRight,
+    Kindle,
+    #[serde(rename = "L-Shaped")]
+    LShaped,
+    Edge,
+    None,
+}
 
-        Self {}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScaleType {
+    Comfortable,
+    #[serde(rename = "Fit Width")]
+    FitWidth,
+    #[serde(rename = "Fit Height")]
+    FitHeight,
+    #[serde(rename = "Fit Content")]
+    FitContent,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SidePadding {
+    None,
+    #[serde(rename = "25%")]
+    Quarter,
+    #[serde(rename = "50%")]
+    Half,
+    #[serde(rename = "75%")]
+    ThreeQuarters,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BlendMode {
+    Default,
+    Multiply,
+    Screen,
+    Overlay,
+    Dodge,
+    Burn,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct GeneralSettings {
+    pub locale: String,
+    pub date_format: DateFormat,
+    pub auto_update: bool,
+    #[serde(rename = "discordRPCIntegration")]
+    pub discord_rpc_integration: bool,
+    pub minimize_to_tray: bool,
+    pub close_to_tray: bool,
+}
+
+impl Default for GeneralSettings {
+    fn default() -> Self {
+        Self {
+            locale: "en".to_string(),
+            date_format: DateFormat::MMDDYYYY,
+            auto_update: true,
+            discord_rpc_integration: true,
+            minimize_to_tray: false,
+            close_to_tray: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct LibrarySettings {
+    pub update_on_key_press: bool,
+    pub refresh_covers: bool,
+    pub ignore_articles: bool,
+    pub search_suggestions: bool,
+    pub update_ongoing_manga_only: bool,
+    pub update_frequency: UpdateFrequency,
+}
+
+impl Default for LibrarySettings {
+    fn default() -> Self {
+        Self {
+            update_on_key_press: false,
+            refresh_covers: true,
+            ignore_articles: false,
+            search_suggestions: true,
+            update_ongoing_manga_only: false,
+            update_frequency: UpdateFrequency::Daily,
+        }
     }
+}
 
-    fn get_path(&self) -> PathBuf {
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct AppearanceSettings {
+    pub theme: ThemeType,
+    pub theme_style_dark: String,
+    pub theme_style_light: String,
+}
+
+impl Default for AppearanceSettings {
+    fn default() -> Self {
+        Self {
+            theme: ThemeType::Dark,
+            theme_style_dark: "default".to_string(),
+            theme_style_light: "default".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ReaderSettings {
+    pub lightbar_vertical: bool,
+    pub lightbar_right: bool,
+    pub lightbar_enabled: bool,
+    pub skip_chapters_of_different_group: bool,
+    pub skip_chapters_marked_read: bool,
+    /// How far into a chapter, as a fraction of its pages, it counts as read.
+    pub mark_read_threshold: f64,
+    pub reading_mode: ReadingMode,
+    pub nav_layout_paged: NavigationLayout,
+    pub invert_tapping_paged: bool,
+    pub scale_type: ScaleType,
+    pub crop_borders_paged: bool,
+    pub page_layout_paged: PageType,
+    pub nav_layout_webtoon: NavigationLayout,
+    pub invert_tapping_webtoon: bool,
+    pub side_padding_webtoon: SidePadding,
+    pub allow_zoom_out_webtoon: bool,
+
+    pub use_custom_color_filter: bool,
+    pub filter_r: u8,
+    pub filter_g: u8,
+    pub filter_b: u8,
+    pub filter_a: f64,
+    pub blend_mode: BlendMode,
+}
+
+impl Default for ReaderSettings {
+    fn default() -> Self {
+        Self {
+            lightbar_vertical: false,
+            lightbar_right: false,
+            lightbar_enabled: true,
+            skip_chapters_of_different_group: false,
+            skip_chapters_marked_read: false,
+            mark_read_threshold: 1.0,
+            reading_mode: ReadingMode::RightToLeft,
+            nav_layout_paged: NavigationLayout::LeftAndRight,
+            invert_tapping_paged: false,
+            scale_type: ScaleType::Comfortable,
+            crop_borders_paged: false,
+            page_layout_paged: PageType::SinglePage,
+            nav_layout_webtoon: NavigationLayout::TopAndBottom,
+            invert_tapping_webtoon: false,
+            side_padding_webtoon: SidePadding::None,
+            allow_zoom_out_webtoon: false,
+
+            use_custom_color_filter: false,
+            filter_r: 255,
+            filter_g: 255,
+            filter_b: 255,
+            filter_a: 1.0,
+            blend_mode: BlendMode::Default,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct DownloadSettings {
+    pub location: String,
+    #[serde(rename = "saveChaptersAsCBZ")]
+    pub save_chapters_as_cbz: bool,
+    pub remove_when_marked_read: bool,
+    pub remove_after_read: bool,
+    pub download_new_chapters: bool,
+    pub delete_removed_chapters: bool,
+}
+
+impl Default for DownloadSettings {
+    fn default() -> Self {
+        Self {
+            location: tauri::api::path::download_dir()
+                .map(|dir| dir.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            save_chapters_as_cbz: false,
+            remove_when_marked_read: false,
+            remove_after_read: false,
+            download_new_chapters: false,
+            delete_removed_chapters: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct BrowseSettings {
+    pub check_for_updates: bool,
+    pub only_search_pinned: bool,
+    #[serde(rename = "showNSFWSources")]
+    pub show_nsfw_sources: bool,
+}
+
+impl Default for BrowseSettings {
+    fn default() -> Self {
+        Self {
+            check_for_updates: true,
+            only_search_pinned: false,
+            show_nsfw_sources: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct TrackingSettings {
+    pub sync_chapters_after_reading: bool,
+    pub track_when_adding_to_library: bool,
+    pub update_when_marked_as_read: bool,
+}
+
+impl Default for TrackingSettings {
+    fn default() -> Self {
+        Self {
+            sync_chapters_after_reading: true,
+            track_when_adding_to_library: false,
+            update_when_marked_as_read: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct BackupSettings {
+    pub automatic_backups: bool,
+    /// Hours between automatic backups.
+    pub backup_interval: i64,
+    pub backups_to_keep: usize,
+}
+
+impl Default for BackupSettings {
+    fn default() -> Self {
+        Self {
+            automatic_backups: false,
+            backup_interval: 24,
+            backups_to_keep: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct AdvancedSettings {
+    pub send_crash_reports: bool,
+    pub automatic_maintenance: bool,
+    /// Hours between automatic maintenance runs.
+    pub maintenance_interval: i64,
+}
+
+impl Default for AdvancedSettings {
+    fn default() -> Self {
+        Self {
+            send_crash_reports: true,
+            automatic_maintenance: false,
+            maintenance_interval: 24 * 7,
+        }
+    }
+}
+
+/// Mirrors `Settings` in `src/util/settings.ts`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct AppSettings {
+    #[serde(rename = "version")]
+    pub version: u32,
+
+    pub general: GeneralSettings,
+    pub library: LibrarySettings,
+    pub appearance: AppearanceSettings,
+    pub reader: ReaderSettings,
+    pub downloads: DownloadSettings,
+    pub browse: BrowseSettings,
+    pub tracking: TrackingSettings,
+    pub backup: BackupSettings,
+    pub security: Map<String, Value>,
+    pub advanced: AdvancedSettings,
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            version: SETTINGS_VERSION,
+
+            general: GeneralSettings::default(),
+            library: LibrarySettings::default(),
+            appearance: AppearanceSettings::default(),
+            reader: ReaderSettings::default(),
+            downloads: DownloadSettings::default(),
+            browse: BrowseSettings::default(),
+            tracking: TrackingSettings::default(),
+            backup: BackupSettings::default(),
+            security: Map::new(),
+            advanced: AdvancedSettings::default(),
+        }
+    }
+}
+
+/// Runs every migration `object` hasn't had yet. Settings written by a newer
+/// build are refused rather than guessed at.
+fn upgrade(object: &mut Map<String, Value>) -> Result<(), AppError> {
+    let version = object
+        .get("version")
+        .and_then(Value::as_u64)
+        .map_or(0, |v| usize::try_from(v).unwrap_or(usize::MAX));
+
+    if version > MIGRATIONS.len() {
+        return Err(AppError::validation(format!(
+            "settings version {version} is newer than this build supports"
+        )));
+    }
+
+    for migration in &MIGRATIONS[version..] {
+        migration(object);
+    }
+    object.insert("version".to_string(), json!(SETTINGS_VERSION));
+
+    Ok(())
+}
+
+impl AppSettings {
+    fn parse(raw: Value) -> Result<Self, AppError> {
+        let settings: Self = serde_json::from_value(raw)?;
+        settings.validate()?;
+
+        Ok(settings)
+    }
+
+    /// Upgrades `raw` to the current version and parses it. Anything missing
+    /// is filled in from the defaults.
+    pub fn from_value(mut raw: Value) -> Result<Self, AppError> {
+        let object = raw
+            .as_object_mut()
+            .ok_or_else(|| AppError::validation("settings must be a JSON object"))?;
+        upgrade(object)?;
+
+        Self::parse(raw)
+    }
+
+    /// Like `from_value`, but a value that doesn't parse or validate falls
+    /// back to its default instead of failing the whole file. Returns the
+    /// settings along with the dotted paths of every value that was reset.
+    pub fn from_value_lenient(mut raw: Value) -> Result<(Self, Vec<String>), AppError> {
+        let object = raw
+            .as_object_mut()
+            .ok_or_else(|| AppError::validation("settings must be a JSON object"))?;
+        upgrade(object)?;
+
+        if let Ok(settings) = Self::parse(Value::Object(object.clone())) {
+            return Ok((settings, vec![]));
+        }
+
+        // Lay each stored value over the defaults one at a time, keeping the
+        // ones that still leave valid settings.
+        let mut settings = serde_json::to_value(Self::default())?;
+        let mut reset = vec![];
+        for (section, stored) in object.iter() {
+            let values = match (stored, settings.get(section)) {
+                (Value::Object(values), Some(Value::Object(_))) => values
+                    .iter()
+                    .map(|(key, value)| {
+                        (
+                            format!("{section}.{key}"),
+                            vec![section.as_str(), key.as_str()],
+                            value,
+                        )
+                    })
+                    .collect(),
+                _ => vec![(section.clone(), vec![section.as_str()], stored)],
+            };
+
+            for (path, keys, value) in values {
+                let mut candidate = settings.clone();
+                let mut target = &mut candidate;
+                for key in keys {
+                    target = &mut target[key];
+                }
+                *target = value.clone();
+
+                if Self::parse(candidate.clone()).is_ok() {
+                    settings = candidate;
+                } else {
+                    reset.push(path);
+                }
+            }
+        }
+
+        Ok((Self::parse(settings)?, reset))
+    }
+
+    pub fn validate(&self) -> Result<(), AppError> {
+        if !SUPPORTED_LOCALES.contains(&self.general.locale.as_str()) {
+            return Err(AppError::validation(format!(
+                "unsupported locale '{}'",
+                self.general.locale
+            )));
+        }
+
+        if !(self.reader.mark_read_threshold > 0.0 && self.reader.mark_read_threshold <= 1.0) {
+            return Err(AppError::validation(
+                "Reader.markReadThreshold must be above 0 and at most 1",
+            ));
+        }
+
+        if !(0.0..=1.0).contains(&self.reader.filter_a) {
+            return Err(AppError::validation(
+                "Reader.filterA must be between 0 and 1",
+            ));
+        }
+
+        for (name, style) in [
+            ("themeStyleDark", &self.appearance.theme_style_dark),
+            ("themeStyleLight", &self.appearance.theme_style_light),
+        ] {
+            if style.chars().count() > 100 {
+                return Err(AppError::validation(format!(
+                    "Appearance.{name} must be at most 100 characters"
+                )));
+            }
+        }
+
+        if !(1..=MAX_INTERVAL).contains(&self.backup.backup_interval) {
+            return Err(AppError::validation(format!(
+                "Backup.backupInterval must be between 1 and {MAX_INTERVAL} hours"
+            )));
+        }
+
+        if self.backup.backups_to_keep < 1 {
+            return Err(AppError::validation(
+                "Backup.backupsToKeep must be at least 1",
+            ));
+        }
+
+        if !(1..=MAX_INTERVAL).contains(&self.advanced.maintenance_interval) {
+            return Err(AppError::validation(format!(
+                "Advanced.maintenanceInterval must be between 1 and {MAX_INTERVAL} hours"
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// Emitted to every window as `settings-changed` after a successful write.
+#[derive(Debug, Clone, Serialize)]
+pub struct SettingsChanged {
+    /// Dotted paths of every value that changed, e.g. `Reader.readingMode`.
+    pub changed: Vec<String>,
+    pub settings: AppSettings,
+}
+
+/// Applies `patch` to `target` as a JSON merge patch (RFC 7396).
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+    if let Value::Object(patch) = patch {
+        if !target.is_object() {
+            *target = Value::Object(Map::new());
+        }
+
+        let target = target.as_object_mut().unwrap();
+        for (key, value) in patch {
+            if value.is_null() {
+                target.remove(key);
+            } else {
+                merge_patch(target.entry(key.as_str()).or_insert(Value::Null), value);
+            }
+        }
+    } else {
+        *target = patch.clone();
+    }
+}
+
+pub fn collect_changes(old: &Value, new: &Value, prefix: &str, changed: &mut Vec<String>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            for key in old
+                .keys()
+                .chain(new.keys().filter(|k| !old.contains_key(*k)))
+            {
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{prefix}.{key}")
+                };
+
+                collect_changes(
+                    old.get(key).unwrap_or(&Value::Null),
+                    new.get(key).unwrap_or(&Value::Null),
+                    &path,
+                    changed,
+                );
+            }
+        }
+        (old, new) if old != new => changed.push(prefix.to_string()),
+        _ => {}
+    }
+}
+
+pub struct Settings {}
+
+impl Settings {
+    fn get_path(&self) -> Result<PathBuf, AppError> {
         let app_context = tauri::generate_context!();
         tauri::api::path::resolve_path(
             app_context.config(),
@@ -30,32 +602,106 @@ impl Settings {
                 .unwrap(),
             Some(BaseDirectory::Config),
         )
-        .map_or_else(|_| panic!("unable to get settings path"), |v| v)
+        .map_err(|_| AppError::internal("unable to get settings path"))
     }
 
-    pub fn set<T>(&self, new_settings: Option<T>) -> bool
+    fn write(&self, settings: &AppSettings) -> Result<(), AppError> {
+        let path = self.get_path()?;
+        let temp_path = path.with_extension("json.tmp");
+        write(&temp_path, serde_json::to_string_pretty(settings)?)
+            .context("unable to write settings")?;
+        rename(&temp_path, &path).context("unable to replace settings")
+    }
+
+    /// Runs `modify` against the current settings as JSON, then validates and
+    /// writes the result through a temporary file. A rejected or interrupted
+    /// write never touches the existing settings.
+    pub fn update<F>(&self, modify: F) -> Result<SettingsChanged, AppError>
     where
-        std::string::String: From<T>,
-        T: From<std::string::String>,
+        F: FnOnce(&mut Value) -> Result<(), AppError>,
     {
-        write(
-            self.get_path(),
-            new_settings.map_or_else(
-                || json!({}).to_string(),
-                |val| std::string::String::from(val),
-            ),
-        )
-        .is_ok()
+        let _guard = WRITE_LOCK
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        let old = serde_json::to_value(self.read()?)?;
+        let mut new = old.clone();
+        modify(&mut new)?;
+
+        let settings = AppSettings::from_value(new)?;
+        self.write(&settings)?;
+
+        let mut changed = vec![];
+        collect_changes(&old, &serde_json::to_value(&settings)?, "", &mut changed);
+
+        Ok(SettingsChanged { changed, settings })
+    }
+
+    /// Replaces the settings wholesale.
+    pub fn set(&self, new_settings: Value) -> Result<SettingsChanged, AppError> {
+        self.update(|current| {
+            *current = new_settings;
+            Ok(())
+        })
+    }
+
+    /// Applies a JSON merge patch, leaving every key it doesn't mention alone.
+    pub fn patch(&self, patch: &Value) -> Result<SettingsChanged, AppError> {
+        self.update(|current| {
+            merge_patch(current, patch);
+            Ok(())
+        })
+    }
+
+    /// Sets the single value at a dotted path such as `Reader.readingMode`.
+    pub fn set_key(&self, key: &str, value: Value) -> Result<SettingsChanged, AppError> {
+        let patch = key.rsplit('.').fold(value, |inner, segment| {
+            let mut outer = Map::new();
+            outer.insert(segment.to_string(), inner);
+            Value::Object(outer)
+        });
+
+        self.patch(&patch)
     }
 
-    pub fn get(&self) -> Result<Option<Value>, InternalError> {
-        if !self.get_path().exists() {
-            return Ok(None);
+    /// Returns the stored settings, or the defaults if there are none.
+    /// Values that are invalid fall back to their defaults one by one, and a
+    /// file that isn't a JSON object at all is moved aside to
+    /// `settings.corrupt.json`. Settings from a newer build are an error, so
+    /// they're never overwritten.
+    pub fn get(&self) -> Result<AppSettings, AppError> {
+        let _guard = WRITE_LOCK
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        self.read()
+    }
+
+    /// `get` for callers already holding `WRITE_LOCK`.
+    fn read(&self) -> Result<AppSettings, AppError> {
+        let path = self.get_path()?;
+        if !path.exists() {
+            return Ok(AppSettings::default());
         };
 
-        read(self.get_path()).map_or_else(
-            |why| Err(InternalError::new(why)),
-            |v| serde_json::from_slice(&v).map_or_else(|_| Ok(None), |d| Ok(Some(d))),
-        )
+        let raw = match serde_json::from_slice::<Value>(&read(&path)?) {
+            Ok(raw @ Value::Object(_)) => raw,
+            _ => {
+                log::error!("settings file is unreadable, falling back to defaults");
+                rename(&path, path.with_extension("corrupt.json"))?;
+
+                return Ok(AppSettings::default());
+            }
+        };
+
+        let (settings, reset) = AppSettings::from_value_lenient(raw)?;
+        if !reset.is_empty() {
+            log::warn!(
+                "invalid settings fell back to their defaults: {}",
+                reset.join(", ")
+            );
+        }
+
+        Ok(settings)
     }
 }
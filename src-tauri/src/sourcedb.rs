@@ -0,0 +1,91 @@
+use rusqlite::{Connection, OptionalExtension};
+use serde_json::{Map, Value};
+
+use crate::{
+    db::open,
+    errors::{AppError, Context},
+    sources::SourceManifest,
+};
+
+pub struct SourcePreferencesDB {
+    db: Connection,
+}
+
+impl SourcePreferencesDB {
+    pub fn new(path: &Option<std::path::PathBuf>) -> Result<Self, AppError> {
+        let db = open(path)?;
+
+        db.execute(
+            "CREATE TABLE IF NOT EXISTS SourcePreferences
+            (
+               source TEXT NOT NULL,
+               key TEXT NOT NULL,
+               value TEXT NOT NULL,
+               PRIMARY KEY (source, key)
+            )
+            ",
+            (),
+        )
+        .context("unable to create SourcePreferences table")?;
+
+        Ok(Self { db })
+    }
+
+    pub fn set(&self, manifest: &SourceManifest, key: &str, value: &Value) -> Result<(), AppError> {
+        let schema = manifest.preference(key).ok_or_else(|| {
+            AppError::not_found(format!(
+                "source '{}' does not declare a preference named '{key}'",
+                manifest.id
+            ))
+        })?;
+        schema.validate(value)?;
+
+        self.db.execute(
+            "REPLACE INTO SourcePreferences
+                (source, key, value) VALUES (?1, ?2, ?3)
+            ",
+            (&manifest.id, key, value.to_string()),
+        )?;
+
+        Ok(())
+    }
+
+    /// Returns the stored value for `key`, falling back to the manifest default
+    /// when nothing is stored or the stored value no longer fits the schema.
+    pub fn get(&self, manifest: &SourceManifest, key: &str) -> Result<Value, AppError> {
+        let schema = manifest.preference(key).ok_or_else(|| {
+            AppError::not_found(format!(
+                "source '{}' does not declare a preference named '{key}'",
+                manifest.id
+            ))
+        })?;
+
+        let stored: Option<String> = self
+            .db
+            .query_row(
+                "SELECT value FROM SourcePreferences WHERE source = ?1 AND key = ?2",
+                (&manifest.id, key),
+                |row| row.get("value"),
+            )
+            .optional()?;
+
+        Ok(stored
+            .and_then(|v| serde_json::from_str::<Value>(&v).ok())
+            .filter(|v| schema.validate(v).is_ok())
+            .unwrap_or_else(|| schema.default_value()))
+    }
+
+    pub fn get_all(&self, manifest: &SourceManifest) -> Result<Map<String, Value>, AppError> {
+        let mut preferences = Map::new();
+        for schema in &manifest.preferences {
+            preferences.insert(schema.key.clone(), self.get(manifest, &schema.key)?);
+        }
+
+        Ok(preferences)
+    }
+
+    pub fn delete(&self, source: &str) -> Result<usize, rusqlite::Error> {
+        self.db
+            .execute("DELETE FROM SourcePreferences WHERE source = ?1", [source])
+    }
+}
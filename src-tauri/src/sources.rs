@@ -0,0 +1,195 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use tauri::api::path::BaseDirectory;
+
+use crate::{
+    errors::{AppError, Context},
+    matching::normalize_title,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum PreferenceKind {
+    Toggle {
+        default: bool,
+    },
+    Select {
+        options: Vec<String>,
+        default: String,
+    },
+    Text {
+        default: String,
+        #[serde(default)]
+        max_length: Option<usize>,
+    },
+    MultiSelect {
+        options: Vec<String>,
+        #[serde(default)]
+        default: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreferenceSchema {
+    pub key: String,
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+
+    #[serde(flatten)]
+    pub kind: PreferenceKind,
+}
+
+impl PreferenceSchema {
+    #[must_use]
+    pub fn default_value(&self) -> Value {
+        match &self.kind {
+            PreferenceKind::Toggle { default } => Value::from(*default),
+            PreferenceKind::Select { default, .. } | PreferenceKind::Text { default, .. } => {
+                Value::from(default.as_str())
+            }
+            PreferenceKind::MultiSelect { default, .. } => Value::from(default.clone()),
+        }
+    }
+
+    pub fn validate(&self, value: &Value) -> Result<(), AppError> {
+        let key = &self.key;
+        match &self.kind {
+            PreferenceKind::Toggle { .. } => {
+                if value.is_boolean() {
+                    return Ok(());
+                }
+
+                Err(AppError::validation(format!(
+                    "preference '{key}' expects a boolean"
+                )))
+            }
+            PreferenceKind::Select { options, .. } => match value.as_str() {
+                Some(choice) if options.iter().any(|o| o == choice) => Ok(()),
+                Some(choice) => Err(AppError::validation(format!(
+                    "'{choice}' is not a valid option for preference '{key}'"
+                ))),
+                None => Err(AppError::validation(format!(
+                    "preference '{key}' expects a string"
+                ))),
+            },
+            PreferenceKind::Text { max_length, .. } => match value.as_str() {
+                Some(text) if max_length.is_none_or(|max| text.chars().count() <= max) => Ok(()),
+                Some(_) => Err(AppError::validation(format!(
+                    "preference '{key}' is longer than {} characters",
+                    max_length.unwrap_or_default()
+                ))),
+                None => Err(AppError::validation(format!(
+                    "preference '{key}' expects a string"
+                ))),
+            },
+            PreferenceKind::MultiSelect { options, .. } => {
+                let choices = value.as_array().ok_or_else(|| {
+                    AppError::validation(format!("preference '{key}' expects an array of strings"))
+                })?;
+
+                for choice in choices {
+                    match choice.as_str() {
+                        Some(choice) if options.iter().any(|o| o == choice) => {}
+                        _ => {
+                            return Err(AppError::validation(format!(
+                                "{choice} is not a valid option for preference '{key}'"
+                            )))
+                        }
+                    }
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The `manifest.json` that sits next to a source's `main.js`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceManifest {
+    pub id: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub preferences: Vec<PreferenceSchema>,
+}
+
+impl SourceManifest {
+    pub fn load(source_dir: &Path) -> Result<Self, AppError> {
+        let manifest =
+            fs::read(source_dir.join("manifest.json")).context("unable to read manifest")?;
+
+        Ok(serde_json::from_slice(&manifest)?)
+    }
+
+    #[must_use]
+    pub fn preference(&self, key: &str) -> Option<&PreferenceSchema> {
+        self.preferences.iter().find(|p| p.key == key)
+    }
+}
+
+#[must_use]
+pub fn get_sources_path() -> Option<PathBuf> {
+    let app_context = tauri::generate_context!();
+    tauri::api::path::resolve_path(
+        app_context.config(),
+        app_context.package_info(),
+        &tauri::Env::default(),
+        Path::new("com.suwariyomirs.swrs\\sources")
+            .as_os_str()
+            .to_str()
+            .unwrap(),
+        Some(BaseDirectory::Config),
+    )
+    .ok()
+}
+
+/// Every installed source directory with a readable manifest.
+pub fn installed_sources() -> Result<Vec<(PathBuf, SourceManifest)>, AppError> {
+    let sources_path =
+        get_sources_path().ok_or_else(|| AppError::internal("unable to get sources dir"))?;
+
+    let entries = fs::read_dir(sources_path).context("unable to read sources directory")?;
+
+    Ok(entries
+        .filter_map(std::result::Result::ok)
+        .filter_map(|entry| {
+            SourceManifest::load(&entry.path())
+                .ok()
+                .map(|manifest| (entry.path(), manifest))
+        })
+        .collect())
+}
+
+/// Finds the directory of the installed source whose manifest declares `source_id`.
+pub fn find_source(source_id: &str) -> Result<Option<(PathBuf, SourceManifest)>, AppError> {
+    Ok(installed_sources()?
+        .into_iter()
+        .find(|(_, manifest)| manifest.id == source_id))
+}
+
+/// The id of the installed source whose name or id matches `name`, ignoring
+/// case, punctuation and a trailing language tag such as "MangaDex (EN)".
+#[must_use]
+pub fn find_source_by_name(name: &str) -> Option<String> {
+    let name = normalize_title(name.split(" (").next().unwrap_or(name));
+    installed_sources()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(_, manifest)| manifest)
+        .find(|manifest| {
+            normalize_title(&manifest.id) == name
+                || manifest
+                    .name
+                    .as_deref()
+                    .is_some_and(|installed| normalize_title(installed) == name)
+        })
+        .map(|manifest| manifest.id)
+}
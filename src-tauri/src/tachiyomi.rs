@@ -0,0 +1,913 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    io::Read,
+    path::Path,
+};
+
+use flate2::read::GzDecoder;
+use rusqlite::Connection;
+use serde::Serialize;
+
+use crate::{
+    categories::{add_to_category, CategoryDB},
+    db::{open, upsert_chapter, upsert_manga, Chapter, ChapterDB, Manga, MangaDB, MangaStatus},
+    errors::{AppError, Context},
+    get_db_path, sources,
+};
+
+/// A single decoded protobuf field.
+enum Field<'a> {
+    Varint(u64),
+    Fixed64(u64),
+    Bytes(&'a [u8]),
+    Fixed32(u32),
+}
+
+impl<'a> Field<'a> {
+    fn int(&self) -> i64 {
+        match self {
+            #[allow(clippy::cast_possible_wrap)]
+            Self::Varint(v) | Self::Fixed64(v) => *v as i64,
+            Self::Fixed32(v) => i64::from(*v),
+            Self::Bytes(_) => 0,
+        }
+    }
+
+    fn bool(&self) -> bool {
+        self.int() != 0
+    }
+
+    fn float(&self) -> f32 {
+        match self {
+            Self::Fixed32(v) => f32::from_bits(*v),
+            #[allow(clippy::cast_precision_loss)]
+            other => other.int() as f32,
+        }
+    }
+
+    fn string(&self) -> String {
+        match self {
+            Self::Bytes(b) => String::from_utf8_lossy(b).to_string(),
+            _ => String::new(),
+        }
+    }
+
+    fn message(&self) -> &'a [u8] {
+        match self {
+            Self::Bytes(b) => b,
+            _ => &[],
+        }
+    }
+
+    /// Repeated integers, whether or not the encoder packed them.
+    fn ints(&self) -> Result<Vec<i64>, AppError> {
+        match self {
+            Self::Bytes(b) => {
+                let mut reader = WireReader { buf: b, pos: 0 };
+                let mut values = vec![];
+                while reader.pos < b.len() {
+                    #[allow(clippy::cast_possible_wrap)]
+                    values.push(reader.varint()? as i64);
+                }
+
+                Ok(values)
+            }
+            other => Ok(vec![other.int()]),
+        }
+    }
+}
+
+/// Walks the fields of one protobuf message. Only the wire format is
+/// understood; the field numbers are interpreted by the `decode_*` functions.
+struct WireReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> WireReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], AppError> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.buf.len())
+            .ok_or_else(|| AppError::parse("truncated tachiyomi backup"))?;
+
+        let bytes = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn varint(&mut self) -> Result<u64, AppError> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(AppError::parse("malformed varint in tachiyomi backup"))
+    }
+
+    fn field(&mut self) -> Result<(u64, Field<'a>), AppError> {
+        let key = self.varint()?;
+        let field = match key & 0x7 {
+            0 => Field::Varint(self.varint()?),
+            1 => Field::Fixed64(u64::from_le_bytes(self.take(8)?.try_into().unwrap())),
+            2 => {
+                let len = usize::try_from(self.varint()?)
+                    .map_err(|_| AppError::parse("oversized field in tachiyomi backup"))?;
+                Field::Bytes(self.take(len)?)
+            }
+            5 => Field::Fixed32(u32::from_le_bytes(self.take(4)?.try_into().unwrap())),
+            wire_type => {
+                return Err(AppError::parse(format!(
+                    "unsupported wire type {wire_type} in tachiyomi backup"
+                )))
+            }
+        };
+
+        Ok((key >> 3, field))
+    }
+
+    fn fields(
+        buf: &'a [u8],
+        mut visit: impl FnMut(u64, Field<'a>) -> Result<(), AppError>,
+    ) -> Result<(), AppError> {
+        let mut reader = Self { buf, pos: 0 };
+        while reader.pos < buf.len() {
+            let (number, field) = reader.field()?;
+            visit(number, field)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct BackupChapter {
+    pub url: String,
+    pub name: String,
+    pub scanlator: String,
+    pub read: bool,
+    pub last_page_read: i64,
+    pub date_fetch: i64,
+    pub date_upload: i64,
+    pub chapter_number: f32,
+}
+
+#[derive(Debug, Default)]
+pub struct BackupHistory {
+    pub url: String,
+    pub last_read: i64,
+    pub read_duration: i64,
+}
+
+#[derive(Debug)]
+pub struct BackupManga {
+    pub source: i64,
+    pub url: String,
+    pub title: String,
+    pub artist: String,
+    pub author: String,
+    pub description: String,
+    pub genres: Vec<String>,
+    pub status: i64,
+    pub thumbnail_url: String,
+    pub date_added: i64,
+    pub chapters: Vec<BackupChapter>,
+    pub categories: Vec<i64>,
+    pub favorite: bool,
+    pub history: Vec<BackupHistory>,
+}
+
+#[derive(Debug, Default)]
+pub struct TachiyomiBackup {
+    pub manga: Vec<BackupManga>,
+    /// Category order to name.
+    pub categories: HashMap<i64, String>,
+    /// Tachiyomi source id to source name.
+    pub sources: HashMap<i64, String>,
+}
+
+fn decode_chapter(buf: &[u8]) -> Result<BackupChapter, AppError> {
+    let mut chapter = BackupChapter {
+        chapter_number: -1.0,
+        ..BackupChapter::default()
+    };
+
+    WireReader::fields(buf, |number, field| {
+        match number {
+            1 => chapter.url = field.string(),
+            2 => chapter.name = field.string(),
+            3 => chapter.scanlator = field.string(),
+            4 => chapter.read = field.bool(),
+            6 => chapter.last_page_read = field.int(),
+            7 => chapter.date_fetch = field.int(),
+            8 => chapter.date_upload = field.int(),
+            9 => chapter.chapter_number = field.float(),
+            _ => {}
+        }
+
+        Ok(())
+    })?;
+
+    Ok(chapter)
+}
+
+fn decode_history(buf: &[u8]) -> Result<BackupHistory, AppError> {
+    let mut history = BackupHistory::default();
+    WireReader::fields(buf, |number, field| {
+        match number {
+            1 => history.url = field.string(),
+            2 => history.last_read = field.int(),
+            3 => history.read_duration = field.int(),
+            _ => {}
+        }
+
+        Ok(())
+    })?;
+
+    Ok(history)
+}
+
+fn decode_manga(buf: &[u8]) -> Result<BackupManga, AppError> {
+    let mut manga = BackupManga {
+        source: 0,
+        url: String::new(),
+        title: String::new(),
+        artist: String::new(),
+        author: String::new(),
+        description: String::new(),
+        genres: vec![],
+        status: 0,
+        thumbnail_url: String::new(),
+        date_added: 0,
+        chapters: vec![],
+        categories: vec![],
+        favorite: true,
+        history: vec![],
+    };
+
+    WireReader::fields(buf, |number, field| {
+        match number {
+            1 => manga.source = field.int(),
+            2 => manga.url = field.string(),
+            3 => manga.title = field.string(),
+            4 => manga.artist = field.string(),
+            5 => manga.author = field.string(),
+            6 => manga.description = field.string(),
+            7 => manga.genres.push(field.string()),
+            8 => manga.status = field.int(),
+            9 => manga.thumbnail_url = field.string(),
+            13 => manga.date_added = field.int(),
+            16 => manga.chapters.push(decode_chapter(field.message())?),
+            17 => manga.categories.extend(field.ints()?),
+            100 => manga.favorite = field.bool(),
+            104 => manga.history.push(decode_history(field.message())?),
+            _ => {}
+        }
+
+        Ok(())
+    })?;
+
+    Ok(manga)
+}
+
+/// Decodes an uncompressed Tachiyomi `Backup` message.
+pub fn decode(buf: &[u8]) -> Result<TachiyomiBackup, AppError> {
+    let mut backup = TachiyomiBackup::default();
+    WireReader::fields(buf, |number, field| {
+        match number {
+            1 => backup.manga.push(decode_manga(field.message())?),
+            2 => {
+                let (mut name, mut order) = (String::new(), 0);
+                WireReader::fields(field.message(), |number, field| {
+                    match number {
+                        1 => name = field.string(),
+                        2 => order = field.int(),
+                        _ => {}
+                    }
+
+                    Ok(())
+                })?;
+                backup.categories.insert(order, name);
+            }
+            101 => {
+                let (mut name, mut id) = (String::new(), 0);
+                WireReader::fields(field.message(), |number, field| {
+                    match number {
+                        1 => name = field.string(),
+                        2 => id = field.int(),
+                        _ => {}
+                    }
+
+                    Ok(())
+                })?;
+                backup.sources.insert(id, name);
+            }
+            _ => {}
+        }
+
+        Ok(())
+    })?;
+
+    Ok(backup)
+}
+
+/// Reads a `.proto.gz` backup. Uncompressed `.proto` files are accepted too.
+pub fn read_file(path: &Path) -> Result<TachiyomiBackup, AppError> {
+    let contents = fs::read(path).context("unable to read tachiyomi backup")?;
+
+    if contents.starts_with(&[0x1f, 0x8b]) {
+        let mut decoded = vec![];
+        GzDecoder::new(contents.as_slice())
+            .read_to_end(&mut decoded)
+            .context("unable to decompress backup")?;
+
+        return decode(&decoded);
+    }
+
+    decode(&contents)
+}
+
+/// Tachiyomi source ids use all 64 bits, more than a JavaScript number holds,
+/// so they cross IPC as strings.
+fn serialize_id<S: serde::Serializer>(id: &i64, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(id)
+}
+
+/// Which installed source a Tachiyomi source id is imported as.
+#[derive(Debug, Serialize)]
+pub struct SourceMapping {
+    #[serde(serialize_with = "serialize_id")]
+    pub tachiyomi_id: i64,
+    pub source: String,
+}
+
+/// Saves which installed source a Tachiyomi source id is imported as.
+/// Doesn't start a transaction of its own, so an import can save its mappings
+/// along with the entries imported through them.
+fn set_mapping(db: &Connection, tachiyomi_id: i64, source: &str) -> Result<usize, rusqlite::Error> {
+    db.execute(
+        "REPLACE INTO TachiyomiSourceMap (tachiyomi_id, source) VALUES (?1, ?2)",
+        (tachiyomi_id, source),
+    )
+}
+
+pub struct TachiyomiSourceMapDB {
+    db: Connection,
+}
+
+impl TachiyomiSourceMapDB {
+    pub fn new(path: &Option<std::path::PathBuf>) -> Result<Self, AppError> {
+        let db = open(path)?;
+
+        db.execute(
+            "CREATE TABLE IF NOT EXISTS TachiyomiSourceMap
+            (
+               tachiyomi_id INT NOT NULL PRIMARY KEY,
+               source TEXT NOT NULL
+            )
+            ",
+            (),
+        )
+        .context("unable to create TachiyomiSourceMap table")?;
+
+        Ok(Self { db })
+    }
+
+    pub fn set(&self, tachiyomi_id: i64, source: String) -> Result<usize, rusqlite::Error> {
+        set_mapping(&self.db, tachiyomi_id, &source)
+    }
+
+    pub fn get_all(&self) -> Result<Vec<SourceMapping>, rusqlite::Error> {
+        let mut prepared_rows = self
+            .db
+            .prepare("SELECT * FROM TachiyomiSourceMap ORDER BY tachiyomi_id")?;
+        let iter = prepared_rows.query_map([], |row| {
+            Ok(SourceMapping {
+                tachiyomi_id: row.get("tachiyomi_id")?,
+                source: row.get("source")?,
+            })
+        })?;
+
+        iter.collect()
+    }
+
+    pub fn delete(&self, tachiyomi_id: i64) -> Result<usize, rusqlite::Error> {
+        self.db.execute(
+            "DELETE FROM TachiyomiSourceMap WHERE tachiyomi_id = ?1",
+            [tachiyomi_id],
+        )
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct UnmappedSource {
+    #[serde(serialize_with = "serialize_id")]
+    pub tachiyomi_id: i64,
+    pub name: String,
+    /// Titles of the library entries that were skipped because of it.
+    pub manga: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct TachiyomiImportReport {
+    pub dry_run: bool,
+    pub imported_manga: usize,
+    pub imported_chapters: usize,
+    pub categories: Vec<String>,
+    /// Sources matched to an installed source by name during this import.
+    pub auto_mapped: Vec<SourceMapping>,
+    pub unmapped_sources: Vec<UnmappedSource>,
+    /// Titles of entries in the backup that weren't in the Tachiyomi library.
+    pub skipped_manga: Vec<String>,
+}
+
+/// Tachiyomi identifies entries by their path on the source's website, where
+/// sources here use the site's own id. The last path segment is almost always
+/// that id.
+fn id_from_url(url: &str) -> String {
+    url.split(['?', '#'])
+        .next()
+        .unwrap_or(url)
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or(url)
+        .to_string()
+}
+
+/// Tachiyomi's `SManga` status codes.
+const fn convert_status(status: i64) -> MangaStatus {
+    match status {
+        1 => MangaStatus::Ongoing,
+        2 | 4 => MangaStatus::Completed,
+        5 => MangaStatus::Cancelled,
+        6 => MangaStatus::Hiatus,
+        _ => MangaStatus::Unknown,
+    }
+}
+
+fn split_names(names: &str) -> Vec<String> {
+    names
+        .split([',', '&'])
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+fn convert_chapter(
+    chapter: &BackupChapter,
+    history: &HashMap<&str, &BackupHistory>,
+    source: &str,
+    manga_id: &str,
+) -> Chapter {
+    let history = history.get(chapter.url.as_str());
+
+    // A read chapter counts as completed once `pages` reaches `total`; the
+    // real page count is filled in the next time the chapter is opened.
+    let (pages, total) = if chapter.read {
+        let pages = i32::try_from(chapter.last_page_read + 1)
+            .unwrap_or(1)
+            .max(1);
+        (pages, pages)
+    } else {
+        (i32::try_from(chapter.last_page_read).unwrap_or(0), 0)
+    };
+
+    Chapter {
+        id: id_from_url(&chapter.url),
+        manga_id: manga_id.to_string(),
+        source: source.to_string(),
+        // Tachiyomi marks unrecognised numbers as -1, and widening an f32
+        // turns 10.1 into 10.100000381, so round back to what it displayed.
+        chapter: Some(f64::from(chapter.chapter_number))
+            .filter(|number| *number >= 0.0)
+            .map(|number| (number * 1000.0).round() / 1000.0),
+        label: None,
+        volume: None,
+        title: chapter.name.clone(),
+        last_read: history.map_or(0, |h| h.last_read),
+        last_updated: chapter.date_fetch,
+        date_uploaded: chapter.date_upload,
+        time_spent_reading: history.map_or(0, |h| h.read_duration),
+        pages,
+        total,
+        lang: String::new(),
+        scanlators: split_names(&chapter.scanlator),
+    }
+}
+
+/// Maps any source missing from `mapping` to the installed source with the
+/// same name.
+fn auto_map(backup: &TachiyomiBackup, mapping: &mut HashMap<i64, String>) -> Vec<SourceMapping> {
+    let mut auto_mapped = vec![];
+    for (tachiyomi_id, name) in &backup.sources {
+        if mapping.contains_key(tachiyomi_id) {
+            continue;
+        }
+
+        if let Some(source) = sources::find_source_by_name(name) {
+            mapping.insert(*tachiyomi_id, source.clone());
+            auto_mapped.push(SourceMapping {
+                tachiyomi_id: *tachiyomi_id,
+                source,
+            });
+        }
+    }
+
+    auto_mapped
+}
+
+/// Imports the library of a Tachiyomi backup. Entries from sources with no
+/// mapping to an installed source are reported instead of imported. With
+/// `dry_run` nothing is written.
+pub fn import(path: &Path, dry_run: bool) -> Result<TachiyomiImportReport, AppError> {
+    import_backup(&get_db_path(), &read_file(path)?, dry_run)
+}
+
+/// Imports every entry, along with the source mappings made on the way, in
+/// one transaction, so a failed import leaves nothing half-written.
+fn import_backup(
+    path: &Option<std::path::PathBuf>,
+    backup: &TachiyomiBackup,
+    dry_run: bool,
+) -> Result<TachiyomiImportReport, AppError> {
+    let map_db = TachiyomiSourceMapDB::new(path)?;
+    MangaDB::new(path)?;
+    ChapterDB::new(path)?;
+    CategoryDB::new(path)?;
+
+    let mut mapping: HashMap<i64, String> = map_db
+        .get_all()?
+        .into_iter()
+        .map(|m| (m.tachiyomi_id, m.source))
+        .collect();
+    let auto_mapped = auto_map(backup, &mut mapping);
+
+    let mut db = open(path)?;
+    let transaction = db.transaction()?;
+    for mapping in &auto_mapped {
+        set_mapping(&transaction, mapping.tachiyomi_id, &mapping.source)?;
+    }
+
+    let mut report = TachiyomiImportReport {
+        dry_run,
+        ..TachiyomiImportReport::default()
+    };
+    let mut unmapped: BTreeMap<i64, Vec<String>> = BTreeMap::new();
+
+    for manga in &backup.manga {
+        if !manga.favorite {
+            report.skipped_manga.push(manga.title.clone());
+            continue;
+        }
+
+        let source = match mapping.get(&manga.source) {
+            Some(source) => source,
+            None => {
+                unmapped
+                    .entry(manga.source)
+                    .or_default()
+                    .push(manga.title.clone());
+                continue;
+            }
+        };
+
+        let id = id_from_url(&manga.url);
+        let history: HashMap<&str, &BackupHistory> =
+            manga.history.iter().map(|h| (h.url.as_str(), h)).collect();
+        let chapters: Vec<Chapter> = manga
+            .chapters
+            .iter()
+            .map(|chapter| convert_chapter(chapter, &history, source, &id))
+            .collect();
+
+        let categories: Vec<&String> = manga
+            .categories
+            .iter()
+            .filter_map(|order| backup.categories.get(order))
+            .collect();
+        for category in &categories {
+            if !report.categories.contains(category) {
+                report.categories.push((*category).clone());
+            }
+        }
+
+        report.imported_manga += 1;
+        report.imported_chapters += chapters.len();
+
+        upsert_manga(
+            &transaction,
+            Manga {
+                id: id.clone(),
+                name: manga.title.clone(),
+                description: manga.description.clone(),
+                source: source.clone(),
+                covers: Some(manga.thumbnail_url.clone())
+                    .filter(|url| !url.is_empty())
+                    .into_iter()
+                    .collect(),
+                authors: split_names(&manga.author),
+                chapters: chapters.iter().map(|chapter| chapter.id.clone()).collect(),
+                tags: manga.genres.clone(),
+                uploaded: 0,
+                added: manga.date_added,
+                status: convert_status(manga.status),
+                alt_titles: vec![],
+                artists: split_names(&manga.artist),
+                demographic: None,
+                content_rating: None,
+                original_language: None,
+                links: vec![],
+            },
+        )?;
+
+        for chapter in chapters {
+            upsert_chapter(&transaction, chapter)?;
+        }
+
+        for category in categories {
+            add_to_category(&transaction, source, &id, category)?;
+        }
+    }
+
+    if dry_run {
+        transaction.rollback()?;
+    } else {
+        transaction.commit()?;
+    }
+
+    report.auto_mapped = auto_mapped;
+    report.unmapped_sources = unmapped
+        .into_iter()
+        .map(|(tachiyomi_id, manga)| UnmappedSource {
+            tachiyomi_id,
+            name: backup
+                .sources
+                .get(&tachiyomi_id)
+                .cloned()
+                .unwrap_or_default(),
+            manga,
+        })
+        .collect();
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::TestDb;
+
+    fn varint(mut value: u64) -> Vec<u8> {
+        let mut bytes = vec![];
+        while value >= 0x80 {
+            bytes.push((value as u8 & 0x7f) | 0x80);
+            value >>= 7;
+        }
+        bytes.push(value as u8);
+        bytes
+    }
+
+    fn int_field(number: u64, value: i64) -> Vec<u8> {
+        let mut bytes = varint(number << 3);
+        bytes.extend(varint(value as u64));
+        bytes
+    }
+
+    fn bytes_field(number: u64, value: &[u8]) -> Vec<u8> {
+        let mut bytes = varint(number << 3 | 2);
+        bytes.extend(varint(value.len() as u64));
+        bytes.extend(value);
+        bytes
+    }
+
+    fn float_field(number: u64, value: f32) -> Vec<u8> {
+        let mut bytes = varint(number << 3 | 5);
+        bytes.extend(value.to_bits().to_le_bytes());
+        bytes
+    }
+
+    fn message(fields: &[Vec<u8>]) -> Vec<u8> {
+        fields.concat()
+    }
+
+    /// A `Backup` with one entry per case the importer handles: categories
+    /// packed and unpacked, read history, an entry that isn't in the library
+    /// and one from a source without a mapping.
+    fn fixture() -> Vec<u8> {
+        let packed = message(&[
+            int_field(1, 100),
+            bytes_field(2, b"/manga/packed/"),
+            bytes_field(3, b"Packed"),
+            bytes_field(4, b"Artist"),
+            bytes_field(5, b"Writer, Co-Writer"),
+            bytes_field(6, b"Description"),
+            bytes_field(7, b"Action"),
+            bytes_field(7, b"Drama"),
+            int_field(8, 2),
+            bytes_field(9, b"https://example.com/cover.jpg"),
+            int_field(13, 1_000),
+            bytes_field(
+                16,
+                &message(&[
+                    bytes_field(1, b"/chapter/1"),
+                    bytes_field(2, b"Chapter 10.1"),
+                    bytes_field(3, b"Group One & Group Two"),
+                    int_field(4, 1),
+                    int_field(6, 9),
+                    int_field(7, 2_000),
+                    int_field(8, 3_000),
+                    float_field(9, 10.1),
+                ]),
+            ),
+            bytes_field(
+                16,
+                &message(&[
+                    bytes_field(1, b"/chapter/2?lang=en"),
+                    bytes_field(2, b"Extra"),
+                    int_field(6, 3),
+                    float_field(9, -1.0),
+                ]),
+            ),
+            bytes_field(17, &message(&[varint(0), varint(1)])),
+            bytes_field(
+                104,
+                &message(&[
+                    bytes_field(1, b"/chapter/1"),
+                    int_field(2, 5_000),
+                    int_field(3, 60),
+                ]),
+            ),
+        ]);
+        let unpacked = message(&[
+            int_field(1, 100),
+            bytes_field(2, b"/manga/unpacked"),
+            bytes_field(3, b"Unpacked"),
+            int_field(17, 0),
+            int_field(17, 1),
+        ]);
+        let not_favorite = message(&[
+            int_field(1, 100),
+            bytes_field(2, b"/manga/browsed"),
+            bytes_field(3, b"Browsed"),
+            int_field(100, 0),
+        ]);
+        let unmapped = message(&[
+            int_field(1, 200),
+            bytes_field(2, b"/manga/unmapped"),
+            bytes_field(3, b"Unmapped"),
+        ]);
+
+        message(&[
+            bytes_field(1, &packed),
+            bytes_field(1, &unpacked),
+            bytes_field(1, &not_favorite),
+            bytes_field(1, &unmapped),
+            bytes_field(2, &message(&[bytes_field(1, b"Reading"), int_field(2, 0)])),
+            bytes_field(2, &message(&[bytes_field(1, b"Later"), int_field(2, 1)])),
+            bytes_field(
+                101,
+                &message(&[bytes_field(1, b"Source"), int_field(2, 100)]),
+            ),
+        ])
+    }
+
+    #[test]
+    fn decodes_every_field() {
+        let backup = decode(&fixture()).unwrap();
+        assert_eq!(backup.manga.len(), 4);
+        assert_eq!(backup.categories[&0], "Reading");
+        assert_eq!(backup.categories[&1], "Later");
+        assert_eq!(backup.sources[&100], "Source");
+
+        let packed = &backup.manga[0];
+        assert_eq!(packed.source, 100);
+        assert_eq!(packed.title, "Packed");
+        assert_eq!(packed.artist, "Artist");
+        assert_eq!(packed.author, "Writer, Co-Writer");
+        assert_eq!(packed.genres, ["Action", "Drama"]);
+        assert_eq!(packed.status, 2);
+        assert_eq!(packed.date_added, 1_000);
+        assert_eq!(packed.categories, [0, 1]);
+        assert!(packed.favorite);
+
+        let chapter = &packed.chapters[0];
+        assert_eq!(chapter.url, "/chapter/1");
+        assert!(chapter.read);
+        assert_eq!(chapter.last_page_read, 9);
+        assert_eq!(chapter.date_fetch, 2_000);
+        assert_eq!(chapter.date_upload, 3_000);
+        assert!((chapter.chapter_number - 10.1).abs() < f32::EPSILON);
+
+        assert_eq!(packed.history[0].url, "/chapter/1");
+        assert_eq!(packed.history[0].last_read, 5_000);
+        assert_eq!(packed.history[0].read_duration, 60);
+
+        assert_eq!(backup.manga[1].categories, [0, 1]);
+        assert!(!backup.manga[2].favorite);
+    }
+
+    #[test]
+    fn imports_the_library() {
+        let test_db = TestDb::default();
+        let path = test_db.path.clone();
+        TachiyomiSourceMapDB::new(&path)
+            .unwrap()
+            .set(100, "source".to_string())
+            .unwrap();
+
+        let report = import_backup(&path, &decode(&fixture()).unwrap(), false).unwrap();
+        assert_eq!(report.imported_manga, 2);
+        assert_eq!(report.imported_chapters, 2);
+        assert_eq!(report.categories, ["Reading", "Later"]);
+        assert_eq!(report.skipped_manga, ["Browsed"]);
+        assert_eq!(report.unmapped_sources.len(), 1);
+        assert_eq!(report.unmapped_sources[0].tachiyomi_id, 200);
+        assert_eq!(report.unmapped_sources[0].manga, ["Unmapped"]);
+
+        let manga = MangaDB::new(&path)
+            .unwrap()
+            .get("packed".to_string(), "source".to_string())
+            .unwrap()
+            .unwrap();
+        assert_eq!(manga.authors, ["Writer", "Co-Writer"]);
+        assert_eq!(manga.artists, ["Artist"]);
+        assert_eq!(manga.status, MangaStatus::Completed);
+        assert_eq!(manga.covers, ["https://example.com/cover.jpg"]);
+
+        let db = open(&path).unwrap();
+        let read: (Option<f64>, i32, i32, i64, i64, String) = db
+            .query_row(
+                "SELECT chapter, pages, total, last_read, time_spent_reading, scanlators
+                FROM Chapters WHERE manga_id = 'packed' AND id = '1'",
+                [],
+                |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                        row.get(5)?,
+                    ))
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            read,
+            (
+                Some(10.1),
+                10,
+                10,
+                5_000,
+                60,
+                r#"["Group One","Group Two"]"#.to_string()
+            )
+        );
+
+        let unread: (Option<f64>, i32, i32) = db
+            .query_row(
+                "SELECT chapter, pages, total FROM Chapters WHERE manga_id = 'packed' AND id = '2'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(unread, (None, 3, 0));
+
+        let category_db = CategoryDB::new(&path).unwrap();
+        for id in ["packed", "unpacked"] {
+            assert_eq!(
+                category_db
+                    .get_for_manga("source".to_string(), id.to_string())
+                    .unwrap(),
+                ["Later", "Reading"]
+            );
+        }
+    }
+
+    #[test]
+    fn dry_runs_write_nothing() {
+        let test_db = TestDb::default();
+        let path = test_db.path.clone();
+        TachiyomiSourceMapDB::new(&path)
+            .unwrap()
+            .set(100, "source".to_string())
+            .unwrap();
+
+        let report = import_backup(&path, &decode(&fixture()).unwrap(), true).unwrap();
+        assert_eq!(report.imported_manga, 2);
+
+        let manga: i64 = open(&path)
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM Library", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(manga, 0);
+    }
+}
@@ -2,7 +2,7 @@ import { BrowserHistory } from "history";
 import { Router, BrowserRouterProps } from "react-router-dom";
 import { useState, useLayoutEffect, useEffect } from "react";
 
-import SearchCache from "util/searchcache";
+import { SCROLL_KEY } from "pages/search";
 
 const ReaderRouter = ({
     history,
@@ -20,10 +20,8 @@ const ReaderRouter = ({
             case "Search":
                 break;
 
-            default: {
-                SearchCache.set();
-                SearchCache.scroll = undefined;
-            }
+            default:
+                sessionStorage.removeItem(SCROLL_KEY);
         }
     });
 
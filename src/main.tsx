@@ -7,6 +7,7 @@ import _ from "lodash";
 import * as logApi from "tauri-plugin-log-api";
 import { format as prettyFormat } from "pretty-format";
 import uninterfacedConsole from "util/console";
+import listenForSearchRequests from "util/searchrequests";
 
 const mappedLogApi = _.mapValues(
     _.omit(logApi, "default"),
@@ -38,6 +39,8 @@ globalThis.console = {
     log: mappedLogApi.trace,
 };
 
+listenForSearchRequests().catch(console.error);
+
 ReactDOM.createRoot(document.getElementById("root") as HTMLElement).render(
     <React.StrictMode>
         <App />
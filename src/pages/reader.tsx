@@ -287,15 +287,14 @@ const Reader = () => {
             ];
         const currentChapter = mangaData.chapters[currentChapterIdx];
 
+        // Unnumbered chapters (extras, oneshots) never count as skipping ahead.
+        const currentNumber = currentChapter.chapter ?? 0;
+        const targetNumber = targetChapter.chapter ?? currentNumber;
+
         const skippedSubchapters = // This will probably flag incorrectly due to floating point errors
-            Math.round(
-                ((targetChapter.chapter % 1) - (currentChapter.chapter % 1)) *
-                    10
-            );
+            Math.round(((targetNumber % 1) - (currentNumber % 1)) * 10);
 
-        const skippedChapters = Math.floor(
-            targetChapter.chapter - currentChapter.chapter
-        );
+        const skippedChapters = Math.floor(targetNumber - currentNumber);
 
         const subchaptersAreSkipped =
             skippedSubchapters > 1 && skippedSubchapters !== 5 ? (
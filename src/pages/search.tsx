@@ -24,17 +24,18 @@ import {
     useState,
 } from "react";
 
-import SourceHandler, { Source } from "util/sources";
+import SourceHandler from "util/sources";
 import SearchSource, { Status } from "components/searchsource";
 import { useNavigate, useSearchParams } from "react-router-dom";
 import { LazyLoadComponent } from "react-lazy-load-image-component";
+import { listen } from "@tauri-apps/api/event";
 import { generateTree } from "util/search";
 import { MdFilterList } from "react-icons/md";
 import type { Manga } from "types/manga";
 
 import CircularProgress from "components/circularprogress";
 import InfiniteScroll from "react-infinite-scroll-component";
-import SearchCache, { Search } from "util/searchcache";
+import ipc, { SourceSearchResult } from "util/ipc";
 import MangaComponent from "components/manga";
 import BackButton from "components/button";
 import Filters from "components/filters";
@@ -42,10 +43,13 @@ import PageEnd from "components/pageend";
 import Card from "components/card";
 import _ from "lodash";
 
-type Cache = {
-    [searchQuery: string]: {
-        [sourceId: string]: Array<Manga>;
-    };
+// Kept across navigation so coming back from a manga restores the scroll.
+export const SCROLL_KEY = "search-scroll";
+
+type SourceResults = {
+    status: Status;
+    manga: Array<Manga>;
+    total: number;
 };
 
 const SearchPage = () => {
@@ -54,7 +58,7 @@ const SearchPage = () => {
         onOpen: onFiltersOpen,
         onClose: onFiltersClose,
     } = useDisclosure();
-    const [queryParams] = useSearchParams();
+    const [queryParams, setQueryParams] = useSearchParams();
     const Navigate = useNavigate();
 
     const mainRef = useRef<HTMLDivElement>(null);
@@ -144,164 +148,137 @@ const SearchPage = () => {
         []
     );
 
-    const [hasMore, setMore] = useState<boolean>(true);
-    const searchCache = useRef<Cache>({});
-
-    const [currentSearch, setSearch_RAW] = useState<Search>(
-        SearchCache.get() ?? {
-            query: queryParams.get("search") ?? "",
-            results: {},
-        }
+    // The query and scope live in the URL so going back to the search keeps
+    // them, and the backend's cache serves the results again.
+    const query = queryParams.get("search") ?? "";
+    const scope = queryParams.get("scope") ?? undefined;
+    const setSearch = useCallback(
+        (newQuery: string, newScope?: string) =>
+            setQueryParams(
+                newScope
+                    ? { search: newQuery, scope: newScope }
+                    : { search: newQuery },
+                { replace: true }
+            ),
+        [setQueryParams]
     );
 
-    const setSearch = useCallback(
-        (newValue: ((oldSearch: Search) => Search) | Search) => {
-            const handleCache = (toBeCached: Search) =>
-                Object.keys(toBeCached.results).forEach((sourceId) => {
-                    const currentCache = searchCache.current;
-                    const currentQuery = currentSearch.query;
-                    const { manga: sourceManga } = toBeCached.results[sourceId];
-
-                    if (!currentCache[currentQuery])
-                        return (currentCache[currentQuery] = {
-                            [sourceId]: sourceManga,
-                        });
-
-                    currentCache[currentQuery][sourceId] = sourceManga;
-                });
-
-            if (typeof newValue === "function") {
-                const functionReturn = newValue({
-                    ...currentSearch,
-                });
-
-                handleCache(functionReturn);
-                setSearch_RAW(functionReturn);
-                SearchCache.set(functionReturn);
-                return;
-            }
+    // Bumped to search again with the same query, e.g. when filters change.
+    const [searchCount, setSearchCount] = useState(0);
+    const [results, setResults] = useState<Record<string, SourceResults>>({});
+
+    // Results of older searches are ignored. A source can finish before
+    // `ipc.search.start` returns the id, so results of ids not known yet are
+    // held until it does.
+    const searchIds = useRef<Set<number>>(new Set());
+    const earlyResults = useRef<Array<SourceSearchResult>>([]);
+
+    const applyResult = useCallback((result: SourceSearchResult) => {
+        if (result.status !== "done")
+            console.error(
+                `searching ${result.source} failed:`,
+                result.status === "failed" ? result.message : "timed out"
+            );
+
+        setResults((oldResults) => ({
+            ...oldResults,
+            [result.source]:
+                result.status === "done"
+                    ? {
+                          status: Status.completed,
+                          manga: result.results.data,
+                          total: result.results.total,
+                      }
+                    : { status: Status.error, manga: [], total: 0 },
+        }));
+    }, []);
 
-            SearchCache.set(newValue);
-            handleCache(newValue as Search);
-            setSearch_RAW(newValue as Search);
-        },
-        [setSearch_RAW, currentSearch]
-    );
+    useEffect(() => {
+        const unlisten = listen<SourceSearchResult>(
+            "search-result",
+            ({ payload }) => {
+                if (searchIds.current.has(payload.search_id))
+                    applyResult(payload);
+                else earlyResults.current.push(payload);
+            }
+        );
 
-    const trySearch = useCallback(
-        async (
-            handler: Source
-        ): Promise<{ total: number; data: Array<Manga> }> => {
-            const { query, scope, results } = currentSearch;
-
-            return new Promise((resolve, reject) => {
-                handler
-                    .search(
-                        query,
-                        scope ? results[scope]?.manga.length : 0,
-                        generateTree(
-                            scope
-                                ? handler.filters
-                                : SourceHandler.defaultFilters(handler.id)
-                        )
-                    )
-                    .then(resolve)
-                    .catch(reject);
-            });
+        return () => {
+            unlisten.then((unlistenFn) => unlistenFn());
+        };
+    }, [applyResult]);
+
+    const startSearch = useCallback(
+        async (sources: Array<string>) => {
+            setResults((oldResults) => ({
+                ...oldResults,
+                ..._.fromPairs(
+                    sources.map((sourceId) => [
+                        sourceId,
+                        { status: Status.searching, manga: [], total: 0 },
+                    ])
+                ),
+            }));
+
+            const searchId = await ipc.search.start(
+                query,
+                sources,
+                scope
+                    ? generateTree(SourceHandler.getSource(scope).filters)
+                    : undefined
+            );
+
+            searchIds.current.add(searchId);
+            earlyResults.current
+                .filter((result) => result.search_id === searchId)
+                .forEach(applyResult);
+            earlyResults.current = earlyResults.current.filter(
+                (result) => result.search_id > searchId
+            );
         },
-        [currentSearch]
+        [query, scope, applyResult]
     );
 
     useEffect(() => {
-        SourceHandler.sourcesArray.forEach(async (sourceHandler) => {
-            const handler = await sourceHandler;
-            const {
-                query: oldQuery,
-                scope: oldScope,
-                results: oldResults,
-            } = currentSearch;
-
-            if (oldResults[handler.id]) return;
-            if (filtersIsOpen) return;
-
-            const cachedData = searchCache.current[oldQuery]?.[handler.id];
-            setSearch_RAW((oldSearch) => {
-                // needs raw search since cache shouldn't be set in this function
-                const { results } = oldSearch;
-
-                return {
-                    ...oldSearch,
-                    results: {
-                        [handler.id]: cachedData
-                            ? { status: Status.completed, manga: cachedData }
-                            : {
-                                  status: Status.searching,
-                                  manga: [],
-                              },
-                        ...results,
-                    },
-                };
-            });
-
-            if (!cachedData) {
-                trySearch(handler)
-                    .then((searchResults) => {
-                        if (oldQuery !== currentSearch.query) return;
-                        if (oldScope !== currentSearch.scope) return;
-                        const { data, total } = searchResults;
-
-                        setMore(data.length < total);
-                        setSearch((oldSearch) => {
-                            oldSearch.results[handler.id] = {
-                                status: Status.completed,
-                                manga: searchResults.data,
-                            };
-
-                            return oldSearch;
-                        });
-                    })
-                    .catch((err) => {
-                        if (oldQuery !== currentSearch.query) return;
-                        if (oldScope !== currentSearch.scope) return;
-
-                        console.error(err);
-                        setMore(false);
-                        setSearch((oldSearch) => {
-                            oldSearch.results[handler.id] = {
-                                status: Status.error,
-                                manga: [],
-                            };
-
-                            return oldSearch;
-                        });
-                    });
-            } else setMore(true); // if cached, allow loader to see if there's more
-        });
-    }, [currentSearch, filtersIsOpen, setSearch, trySearch]);
+        searchIds.current.clear();
+        setResults({});
+
+        Promise.all(SourceHandler.sourcesArray)
+            .then((sources) =>
+                startSearch(scope ? [scope] : sources.map(({ id }) => id))
+            )
+            .catch(console.error);
+    }, [scope, searchCount, startSearch]);
 
     useLayoutEffect(() => {
+        const scroll = sessionStorage.getItem(SCROLL_KEY);
         mainRef.current?.scrollTo(
             0,
-            SearchCache.scroll ?? mainRef.current.scrollTop
+            scroll ? Number(scroll) : mainRef.current.scrollTop
         );
 
-        SearchCache.scroll = undefined;
+        sessionStorage.removeItem(SCROLL_KEY);
     }, []);
 
     const scrollHandler = useCallback(
         _.throttle(() => {
-            SearchCache.scroll = mainRef.current?.scrollTop;
+            sessionStorage.setItem(
+                SCROLL_KEY,
+                `${mainRef.current?.scrollTop ?? 0}`
+            );
         }, 100),
         []
     );
 
     const searchBar = useRef<HTMLInputElement | null>(null);
-    const currentScopedSearch = currentSearch.scope
-        ? currentSearch.results[currentSearch.scope]
-        : null;
+    const currentScopedSearch = scope ? results[scope] : null;
 
     const hasManga = (currentScopedSearch?.manga.length ?? 0) > 0;
     const scrollLoading = !!(currentScopedSearch?.status !== "completed");
+    const hasMore =
+        currentScopedSearch?.status === Status.searching ||
+        (currentScopedSearch?.status === Status.completed &&
+            currentScopedSearch.manga.length < currentScopedSearch.total);
 
     return (
         <div
@@ -314,38 +291,21 @@ const SearchPage = () => {
                 className={css(styles.hiddenSearchForm)}
                 id="searchbar"
                 onSubmit={(e) => {
-                    if (searchBar.current) {
-                        setSearch((oldSearch) => {
-                            oldSearch.results = {};
-                            oldSearch.query = (
-                                searchBar.current?.value ?? oldSearch.query
-                            ).trim();
-
-                            return oldSearch;
-                        });
-                    }
+                    if (searchBar.current)
+                        setSearch(searchBar.current.value.trim(), scope);
                     e.stopPropagation();
                     e.preventDefault();
                 }}
             />
-            {currentSearch.scope ? (
+            {scope ? (
                 <Filters
-                    handler={SourceHandler.getSource(currentSearch.scope)}
+                    handler={SourceHandler.getSource(scope)}
                     isOpen={filtersIsOpen}
                     onSubmit={(newFilters) => {
-                        if (!currentSearch.scope) return;
-
-                        SourceHandler.getSource(
-                            currentSearch.scope
-                        )?.setFilters(newFilters);
+                        if (!scope) return;
 
-                        searchCache.current = {};
-
-                        setMore(true);
-                        setSearch((oldSearch) => {
-                            oldSearch.results = {};
-                            return oldSearch;
-                        });
+                        SourceHandler.getSource(scope)?.setFilters(newFilters);
+                        setSearchCount((count) => count + 1);
                     }}
                     onClose={onFiltersClose}
                 />
@@ -353,7 +313,7 @@ const SearchPage = () => {
             <HStack padding="8px" spacing="25%" margin="8px">
                 <BackButton
                     onClick={async () => {
-                        if (!currentSearch.scope) Navigate("/library");
+                        if (!scope) Navigate("/library");
                         await Promise.all(
                             SourceHandler.sourcesArray.map(async (promise) => {
                                 const source = await promise;
@@ -363,13 +323,7 @@ const SearchPage = () => {
                             })
                         );
 
-                        setMore(true);
-                        searchCache.current = {};
-                        setSearch((oldSearch) => {
-                            oldSearch.scope = undefined;
-                            oldSearch.results = {};
-                            return oldSearch;
-                        });
+                        setSearch(query);
                     }}
                 >
                     Back
@@ -383,7 +337,7 @@ const SearchPage = () => {
                         placeholder="Search here..."
                         form="searchbar"
                         ref={searchBar}
-                        defaultValue={currentSearch.query}
+                        defaultValue={query}
                     />
                 </InputGroup>
             </HStack>
@@ -443,39 +397,49 @@ const SearchPage = () => {
                             if (
                                 filtersIsOpen ||
                                 !hasMore ||
-                                !currentSearch.scope ||
+                                !scope ||
                                 currentScopedSearch.manga.length === 0 // initial search is done elsewhere
                             )
                                 return;
 
-                            const handler = SourceHandler.getSource(
-                                currentSearch.scope
-                            );
-
-                            trySearch(handler)
-                                .then((data) => {
-                                    const { data: resultingManga, total } =
-                                        data;
-                                    setSearch((oldSearch) => {
-                                        const oldResults =
-                                            oldSearch.results[handler.id].manga;
+                            // Only the first page is searched through the
+                            // backend, the rest come from the source itself.
+                            const handler = SourceHandler.getSource(scope);
+                            handler
+                                .search(
+                                    query,
+                                    currentScopedSearch.manga.length,
+                                    generateTree(handler.filters)
+                                )
+                                .then(({ data: resultingManga, total }) => {
+                                    setResults((oldResults) => {
+                                        const oldScoped = oldResults[scope];
+                                        if (!oldScoped) return oldResults;
 
                                         const pushedManga =
                                             resultingManga.filter(
                                                 (y) =>
-                                                    !oldResults.find(
+                                                    !oldScoped.manga.find(
                                                         (v) => v.id === y.id
                                                     )
                                             );
 
-                                        setMore(
-                                            oldResults.length +
-                                                pushedManga.length <
-                                                total
-                                        );
-
-                                        oldResults.push(...pushedManga);
-                                        return oldSearch;
+                                        const manga = [
+                                            ...oldScoped.manga,
+                                            ...pushedManga,
+                                        ];
+
+                                        return {
+                                            ...oldResults,
+                                            [scope]: {
+                                                ...oldScoped,
+                                                manga,
+                                                // stop when a page adds nothing new
+                                                total: pushedManga.length
+                                                    ? total
+                                                    : manga.length,
+                                            },
+                                        };
                                     });
                                 })
                                 .catch(console.error);
@@ -528,14 +492,14 @@ const SearchPage = () => {
                 </div>
             ) : (
                 <div className={css(styles.sources)}>
-                    {Object.keys(currentSearch.results ?? {})
+                    {Object.keys(results)
                         .map((sourceId) => {
                             const sourceHandler =
                                 SourceHandler.getSource(sourceId);
                             if (!sourceHandler) return;
 
                             const storedManga =
-                                currentSearch.results[sourceId]?.manga ?? [];
+                                results[sourceId]?.manga ?? [];
 
                             return (
                                 <SearchSource
@@ -544,38 +508,16 @@ const SearchPage = () => {
                                     sourceName={sourceHandler.id}
                                     sourceManga={storedManga}
                                     onScopeChange={(_, id) => {
-                                        if (currentSearch.scope === id) return;
-
-                                        setSearch((oldSearch) => {
-                                            oldSearch.scope = id;
-                                            return oldSearch;
-                                        });
+                                        if (scope === id) return;
+                                        setSearch(query, id);
                                     }}
                                     onRetry={(_, id) => {
-                                        setSearch((oldSearch) => {
-                                            oldSearch.results[id].status =
-                                                Status.searching;
-                                            return oldSearch;
-                                        });
-
-                                        trySearch(sourceHandler).then(
-                                            (searchResults) => {
-                                                setSearch((oldSearch) => {
-                                                    const newSearch = {
-                                                        ...oldSearch,
-                                                    };
-                                                    newSearch.results[id] = {
-                                                        status: Status.completed,
-                                                        manga: searchResults.data,
-                                                    };
-
-                                                    return newSearch;
-                                                });
-                                            }
+                                        startSearch([id]).catch(
+                                            console.error
                                         );
                                     }}
                                     status={
-                                        currentSearch.results[sourceId]?.status
+                                        results[sourceId]?.status
                                     }
                                 />
                             );
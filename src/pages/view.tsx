@@ -23,6 +23,7 @@ import {
 import { useNavigate, useSearchParams } from "react-router-dom";
 import { stripHtml } from "string-strip-html";
 import {
+    compareChapters,
     compileChapterTitle,
     formatDate,
     isChapterCompleted,
@@ -422,13 +423,12 @@ const View = () => {
     const firstUnreadChapter = useMemo(() => {
         return rawChapterData
             ?.filter((x) => !isChapterCompleted(x))
-            .map((y) => ({ i: y, ch: y.chapter }))
-            .sort((a, b) => a.ch - b.ch)[0];
+            .sort(compareChapters)[0];
     }, [rawChapterData]);
 
     useEffect(() => {
         if (!firstUnreadChapter) return;
-        const foundChapter = document.getElementById(firstUnreadChapter.i.id);
+        const foundChapter = document.getElementById(firstUnreadChapter.id);
 
         if (foundChapter)
             foundChapter.scrollIntoView({
@@ -449,12 +449,12 @@ const View = () => {
 
     if (mangaData && Array.isArray(rawChapterData)) {
         const chapterPre =
-            (firstUnreadChapter?.i.pages ?? 0) > 0
+            (firstUnreadChapter?.pages ?? 0) > 0
                 ? "Continue Reading"
                 : "Start Reading";
         const chapterDisplay = firstUnreadChapter
             ? `${chapterPre} ${compileChapterTitle(
-                  firstUnreadChapter.i,
+                  firstUnreadChapter,
                   false,
                   true
               )}`
@@ -575,8 +575,8 @@ const View = () => {
                                 <Tooltip
                                     label={
                                         firstUnreadChapter &&
-                                        firstUnreadChapter.i.pages > 0
-                                            ? `Page ${firstUnreadChapter.i.pages}/${firstUnreadChapter.i.total}`
+                                        firstUnreadChapter.pages > 0
+                                            ? `Page ${firstUnreadChapter.pages}/${firstUnreadChapter.total}`
                                             : undefined
                                     }
                                     placement="top"
@@ -592,7 +592,7 @@ const View = () => {
                                         onClick={() => {
                                             if (firstUnreadChapter)
                                                 return Navigate(
-                                                    `/reader?source=${mangaData.source}&manga=${mangaData.id}&chapter=${firstUnreadChapter.i.id}`
+                                                    `/reader?source=${mangaData.source}&manga=${mangaData.id}&chapter=${firstUnreadChapter.id}`
                                                 );
                                         }}
                                         disabled={!firstUnreadChapter}
@@ -616,7 +616,11 @@ const View = () => {
                                                     : Date.now(),
                                             };
 
-                                            MangaDB.insert(newManga)
+                                            MangaDB.setAdded(
+                                                newManga.source,
+                                                newManga.id,
+                                                newManga.added
+                                            )
                                                 .then(() =>
                                                     console.log("updated")
                                                 )
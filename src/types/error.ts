@@ -0,0 +1,28 @@
+// Mirrors `AppError` in `src-tauri/src/errors/mod.rs`. Every command rejects
+// with one of these.
+export enum ErrorCode {
+    NotFound = "NotFound",
+    Database = "Database",
+    Io = "Io",
+    Network = "Network",
+    Parse = "Parse",
+    Source = "Source",
+    Validation = "Validation",
+    Internal = "Internal",
+}
+
+export type AppError = {
+    code: ErrorCode;
+    message: string;
+    // What was being done when the error happened, outermost first.
+    context: Array<string>;
+};
+
+export const isAppError = (error: unknown): error is AppError =>
+    typeof error === "object" &&
+    error !== null &&
+    "code" in error &&
+    "message" in error;
+
+export const formatAppError = (error: AppError) =>
+    [...error.context, error.message].join(": ");
@@ -10,8 +10,9 @@ export type Chapter = {
     id: string,
     manga_id: string,
     source: string,
-    chapter: int,
-    volume: int,
+    chapter: number | null, // null for extras, oneshots and other unnumbered releases
+    label?: string | null,
+    volume: int | null,
 
     title?: string,
 
@@ -26,6 +27,18 @@ export type Chapter = {
     scanlators: Scanlators,
 };
 
+export type MangaStatus =
+    | "unknown"
+    | "ongoing"
+    | "completed"
+    | "hiatus"
+    | "cancelled";
+
+export type MangaLink = {
+    name: string,
+    url: string,
+};
+
 export type Manga = {
     id: string;
     name: string;
@@ -39,6 +52,14 @@ export type Manga = {
 
     uploaded: number;
     added: number;
+
+    status?: MangaStatus;
+    alt_titles?: Array<string>;
+    artists?: Array<Author>;
+    demographic?: string | null;
+    content_rating?: string | null;
+    original_language?: string | null; // e.g. "ja"
+    links?: Array<MangaLink>;
 };
 /*
  *
@@ -1,8 +1,139 @@
 import { invoke } from "@tauri-apps/api/tauri";
-import { Chapter, Manga } from "types/manga";
+import { Chapter, Manga, MangaStatus } from "types/manga";
 import { MangaValidator } from "./sources";
-import { DefaultSettings, LoadedSettings, Settings } from "./settings";
-import format from "pretty-format";
+import { LoadedSettings } from "./settings";
+
+/** Emitted as `corrupt-rows` by listings called with `skipCorrupt`. */
+export type CorruptRow = {
+    table: string;
+    rowid: number;
+    column: string | null;
+    reason: string;
+};
+
+export type ChapterProgress = {
+    source: string;
+    manga_id: string;
+    chapter_id: string;
+    page: number; // Starts at 1
+    scroll_offset: number; // 0 to 1, for long-strip mode
+    updated_at: number;
+};
+
+export type NameCount = { name: string; count: number };
+
+// Either specific chapters, or every numbered chapter in an inclusive range.
+export type ChapterSelection =
+    | { ids: Array<string> }
+    | { range: { from: number; to: number } };
+
+export type Bookmark = {
+    id: number;
+    source: string;
+    manga_id: string;
+    chapter_id: string;
+    page: number; // Starts at 1
+    note: string | null;
+    tag: string | null;
+    created_at: number;
+    manga_name: string;
+    chapter: number | null;
+    label: string | null;
+};
+
+// Metadata set by the user in place of the source's. Unset fields show the
+// source's value.
+export type MangaOverride = {
+    name?: string | null;
+    description?: string | null;
+    authors?: Array<string> | null;
+    tags?: Array<string> | null;
+    cover?: string | null;
+    status?: MangaStatus | null;
+};
+
+// A library entry with the chapter counts shown on its badges.
+export type LibraryEntry = Manga & {
+    total_chapters: number;
+    unread: number;
+    downloaded: number;
+    new_chapters: number; // Stored since the manga was last checked
+    last_read: number;
+    last_updated: number;
+};
+
+export type LibrarySort =
+    | "alphabetical"
+    | "dateAdded"
+    | "lastRead"
+    | "lastUpdated"
+    | "unreadCount"
+    | "totalChapters";
+
+// Every filter that's set has to match.
+export type LibraryQuery = {
+    sort?: LibrarySort;
+    descending?: boolean;
+    downloadedOnly?: boolean;
+    unreadOnly?: boolean;
+    completed?: boolean; // Every chapter read
+    status?: MangaStatus;
+    skipCompleted?: boolean; // Leave out series that finished publishing
+    source?: string;
+    tag?: string;
+    category?: string;
+    offset?: number;
+    limit?: number;
+};
+
+export type LibraryPage = {
+    entries: Array<LibraryEntry>;
+    total: number; // Matching entries across all pages
+};
+
+export type QueuedDownload = {
+    source: string;
+    manga_id: string;
+    chapter_id: string;
+    queued_at: number;
+};
+
+// Emitted as `search-source-request` when a search needs a source run.
+export type SourceSearchRequest = {
+    search_id: number;
+    source: string;
+    query: string;
+    filters: Record<string, unknown> | null;
+};
+
+export type SourceSearchResults = { total: number; data: Array<Manga> };
+
+// Emitted as `search-result` as each source of a search finishes.
+export type SourceSearchResult = {
+    search_id: number;
+    source: string;
+} & (
+    | { status: "done"; results: SourceSearchResults; cached: boolean }
+    | { status: "failed"; message: string }
+    | { status: "timed-out" }
+);
+
+export type MaintenanceReport = {
+    dry_run: boolean;
+    ran_at: number;
+    integrity_errors: Array<string>;
+    foreign_key_violations: Array<{
+        table: string;
+        rowid: number | null;
+        parent: string;
+    }>;
+    orphaned_chapters: number;
+    orphaned_reader_settings: number;
+    unused_names: number;
+    stale_downloads: Array<string>;
+    vacuumed: boolean;
+    freed_bytes: number;
+};
 
 const ipcFunctions = {
     path: {
@@ -11,20 +142,90 @@ const ipcFunctions = {
         },
     },
     manga: {
-        getAll: async (source?: string): Promise<Array<Manga>> => {
-            return invoke("get_all_manga", { source });
+        getAll: async (
+            source?: string,
+            skipCorrupt?: boolean
+        ): Promise<Array<Manga>> => {
+            return invoke("get_all_manga", { source, skipCorrupt });
+        },
+
+        getLibrary: async (
+            query?: LibraryQuery,
+            skipCorrupt?: boolean
+        ): Promise<LibraryPage> => {
+            return invoke("get_library", { query, skipCorrupt });
+        },
+
+        getUpdateTargets: async (
+            skipCorrupt?: boolean
+        ): Promise<Array<LibraryEntry>> => {
+            return invoke("get_update_targets", { skipCorrupt });
+        },
+
+        setAdded: async (
+            source: string,
+            id: string,
+            added: number
+        ): Promise<number> => {
+            return invoke("set_manga_added", { source, id, added });
+        },
+
+        markChecked: async (source: string, id: string): Promise<number> => {
+            return invoke("mark_manga_checked", { source, id });
+        },
+
+        getOverride: async (
+            source: string,
+            id: string
+        ): Promise<MangaOverride | null> => {
+            return invoke("get_manga_override", { source, id });
+        },
+
+        setOverride: async (
+            source: string,
+            id: string,
+            overrides: MangaOverride
+        ): Promise<never> => {
+            return invoke("set_manga_override", { source, id, overrides });
+        },
+
+        resetOverride: async (source: string, id: string): Promise<never> => {
+            return invoke("reset_manga_override", { source, id });
         },
 
         getMultiple: async (
             source: string,
-            ids: Array<string>
+            ids: Array<string>,
+            skipCorrupt?: boolean
         ): Promise<Array<Manga>> => {
-            return invoke("get_mangas", { source, ids });
+            return invoke("get_mangas", { source, ids, skipCorrupt });
         },
         get: async (id: string, source: string): Promise<Manga | undefined> => {
             return invoke("get_manga", { id, source });
         },
 
+        getTags: async (): Promise<Array<NameCount>> => {
+            return invoke("get_tags");
+        },
+
+        getAuthors: async (): Promise<Array<NameCount>> => {
+            return invoke("get_authors");
+        },
+
+        getByTag: async (
+            tag: string,
+            skipCorrupt?: boolean
+        ): Promise<Array<Manga>> => {
+            return invoke("get_manga_by_tag", { tag, skipCorrupt });
+        },
+
+        getByAuthor: async (
+            author: string,
+            skipCorrupt?: boolean
+        ): Promise<Array<Manga>> => {
+            return invoke("get_manga_by_author", { author, skipCorrupt });
+        },
+
         insert: async (manga: Manga): Promise<never> => {
             return invoke("insert_manga", { manga: MangaValidator(manga) });
         },
@@ -36,22 +237,208 @@ const ipcFunctions = {
         clear: async (): Promise<never> => {
             return invoke("clear_manga", {});
         },
+
+        findDuplicates: async (
+            threshold?: number
+        ): Promise<Array<Record<string, unknown>>> => {
+            return invoke("find_duplicate_manga", { threshold });
+        },
+
+        merge: async (
+            source: string,
+            id: string,
+            duplicates: Array<{ source: string; id: string }>
+        ): Promise<Manga> => {
+            return invoke("merge_manga", { source, id, duplicates });
+        },
+
+        getLinkedSources: async (
+            source: string,
+            id: string
+        ): Promise<Array<Record<string, unknown>>> => {
+            return invoke("get_linked_sources", { source, id });
+        },
+
+        setPreferredSource: async (
+            source: string,
+            id: string,
+            linkedSource: string,
+            linkedId: string
+        ): Promise<never> => {
+            return invoke("set_preferred_source", {
+                source,
+                id,
+                linkedSource,
+                linkedId,
+            });
+        },
+
+        unlinkSource: async (
+            linkedSource: string,
+            linkedId: string
+        ): Promise<never> => {
+            return invoke("unlink_source", { linkedSource, linkedId });
+        },
+
+        findMigrationCandidates: async (
+            source: string,
+            id: string,
+            targets: Array<string>
+        ): Promise<
+            Array<{ source: string; manga: Manga; score: number }>
+        > => {
+            return invoke("find_migration_candidates", { source, id, targets });
+        },
+
+        migrate: async (
+            source: string,
+            id: string,
+            target: Manga,
+            targetChapters: Array<Chapter>,
+            keepOld: boolean
+        ): Promise<Record<string, unknown>> => {
+            return invoke("migrate_manga", {
+                source,
+                id,
+                target: MangaValidator(target),
+                targetChapters,
+                keepOld,
+            });
+        },
     },
     chapters: {
         getAll: async (
             source?: string,
             id?: string,
-            manga_id?: string
+            manga_id?: string,
+            skipCorrupt?: boolean
         ): Promise<Array<Chapter>> => {
-            return invoke("get_all_chapters", { source, id, manga_id });
+            return invoke("get_all_chapters", {
+                source,
+                id,
+                manga_id,
+                skipCorrupt,
+            });
         },
 
         getMultiple: async (
             source: string,
             manga_id: string,
-            ids: Array<string>
+            ids: Array<string>,
+            skipCorrupt?: boolean
         ): Promise<Array<Chapter>> => {
-            return invoke("get_chapters", { source, manga_id, ids });
+            return invoke("get_chapters", {
+                source,
+                manga_id,
+                ids,
+                skipCorrupt,
+            });
+        },
+
+        setProgress: async (
+            source: string,
+            mangaId: string,
+            chapterId: string,
+            page: number,
+            total?: number,
+            scrollOffset?: number
+        ): Promise<ChapterProgress & { marked_read: boolean }> => {
+            return invoke("set_reading_progress", {
+                source,
+                mangaId,
+                chapterId,
+                page,
+                total,
+                scrollOffset,
+            });
+        },
+
+        getProgress: async (
+            source: string,
+            mangaId: string,
+            chapterId: string
+        ): Promise<ChapterProgress | null> => {
+            return invoke("get_chapter_progress", {
+                source,
+                mangaId,
+                chapterId,
+            });
+        },
+
+        getResumePosition: async (
+            source: string,
+            mangaId: string
+        ): Promise<ChapterProgress | null> => {
+            return invoke("get_resume_position", { source, mangaId });
+        },
+
+        getScanlators: async (
+            source?: string,
+            mangaId?: string
+        ): Promise<Array<NameCount>> => {
+            return invoke("get_scanlators", { source, mangaId });
+        },
+
+        setRead: async (
+            source: string,
+            mangaId: string,
+            selection: ChapterSelection,
+            read: boolean
+        ): Promise<Array<string>> => {
+            return invoke("set_chapters_read", {
+                source,
+                mangaId,
+                selection,
+                read,
+            });
+        },
+
+        markPreviousRead: async (
+            source: string,
+            mangaId: string,
+            chapterId: string
+        ): Promise<Array<string>> => {
+            return invoke("mark_previous_chapters_read", {
+                source,
+                mangaId,
+                chapterId,
+            });
+        },
+
+        resetProgress: async (
+            source: string,
+            mangaId: string,
+            selection: ChapterSelection
+        ): Promise<Array<string>> => {
+            return invoke("reset_chapter_progress", {
+                source,
+                mangaId,
+                selection,
+            });
+        },
+
+        queueDownloads: async (
+            source: string,
+            mangaId: string,
+            selection: ChapterSelection
+        ): Promise<Array<string>> => {
+            return invoke("queue_chapter_downloads", {
+                source,
+                mangaId,
+                selection,
+            });
+        },
+
+        deleteDownloads: async (
+            source: string,
+            mangaId: string,
+            selection: ChapterSelection
+        ): Promise<Array<string>> => {
+            return invoke("delete_chapter_downloads", {
+                source,
+                mangaId,
+                selection,
+            });
         },
 
         get: async (
@@ -73,31 +460,415 @@ const ipcFunctions = {
         clear: async (): Promise<never> => {
             return invoke("clear_chapters", {});
         },
+
+        getUnified: async (
+            source: string,
+            id: string
+        ): Promise<Array<Chapter>> => {
+            return invoke("get_unified_chapters", { source, id });
+        },
     },
     app: {
         getAppSettings: async (): Promise<LoadedSettings> => {
-            return invoke("get_app_settings", {}).then(
-                async (res) =>
-                    (res as LoadedSettings) ?? (await DefaultSettings)
-            );
+            return invoke("get_app_settings", {});
         },
         setAppSettings: async (
-            new_settings?: LoadedSettings
+            newSettings: LoadedSettings
+        ): Promise<LoadedSettings> => {
+            return invoke("set_app_settings", { newSettings });
+        },
+        patchAppSettings: async (
+            patch: Record<string, unknown>
+        ): Promise<LoadedSettings> => {
+            return invoke("patch_app_settings", { patch });
+        },
+        setAppSetting: async (
+            key: string,
+            value: unknown
+        ): Promise<LoadedSettings> => {
+            return invoke("set_app_setting", { key, value });
+        },
+        profiles: {
+            save: async (name: string): Promise<never> => {
+                return invoke("save_settings_profile", { name });
+            },
+            getAll: async (): Promise<
+                Array<{ name: string; active: boolean; updated_at: number }>
+            > => {
+                return invoke("get_settings_profiles", {});
+            },
+            switch: async (name: string): Promise<LoadedSettings> => {
+                return invoke("switch_settings_profile", { name });
+            },
+            remove: async (name: string): Promise<never> => {
+                return invoke("delete_settings_profile", { name });
+            },
+        },
+        exportSettings: async (
+            path: string,
+            format?: "json" | "toml"
+        ): Promise<never> => {
+            return invoke("export_settings", { path, format });
+        },
+        importSettings: async (
+            path: string,
+            format?: "json" | "toml"
+        ): Promise<LoadedSettings> => {
+            return invoke("import_settings", { path, format });
+        },
+    },
+    backup: {
+        create: async (path?: string): Promise<string> => {
+            return invoke("create_backup", { path });
+        },
+        list: async (): Promise<
+            Array<{ path: string; created_at: number; size: number }>
+        > => {
+            return invoke("get_backups");
+        },
+        restore: async (
+            path: string,
+            mode: "merge" | "replace",
+            dryRun: boolean
+        ): Promise<{
+            dry_run: boolean;
+            manifest: {
+                app_version: string;
+                schema_version: number;
+                created_at: number;
+            };
+            tables: Record<
+                string,
+                { added: number; replaced: number; removed: number }
+            >;
+            settings_changed: Array<string>;
+        }> => {
+            return invoke("restore_backup", { path, mode, dryRun });
+        },
+    },
+    repair: {
+        run: async (
+            dryRun: boolean
+        ): Promise<{
+            dry_run: boolean;
+            scanned: number;
+            repaired: Array<CorruptRow>;
+            quarantined: Array<CorruptRow>;
+        }> => {
+            return invoke("repair_database", { dryRun });
+        },
+        getQuarantined: async (): Promise<
+            Array<{
+                id: number;
+                table: string;
+                rowid: number;
+                data: Record<string, unknown>;
+                reason: string;
+                quarantined_at: number;
+            }>
+        > => {
+            return invoke("get_quarantined_rows");
+        },
+        removeQuarantined: async (id: number): Promise<never> => {
+            return invoke("remove_quarantined_row", { id });
+        },
+    },
+    maintenance: {
+        run: async (dryRun: boolean): Promise<MaintenanceReport> => {
+            return invoke("run_maintenance", { dryRun });
+        },
+        getHistory: async (): Promise<Array<MaintenanceReport>> => {
+            return invoke("get_maintenance_history");
+        },
+    },
+    bookmarks: {
+        add: async (
+            source: string,
+            mangaId: string,
+            chapterId: string,
+            page: number,
+            note?: string,
+            tag?: string
+        ): Promise<Bookmark> => {
+            return invoke("add_bookmark", {
+                source,
+                mangaId,
+                chapterId,
+                page,
+                note,
+                tag,
+            });
+        },
+        update: async (
+            id: number,
+            note?: string,
+            tag?: string
+        ): Promise<number> => {
+            return invoke("update_bookmark", { id, note, tag });
+        },
+        remove: async (id: number): Promise<number> => {
+            return invoke("remove_bookmark", { id });
+        },
+        getForManga: async (
+            source: string,
+            mangaId: string
+        ): Promise<Array<Bookmark>> => {
+            return invoke("get_manga_bookmarks", { source, mangaId });
+        },
+        getAll: async (tag?: string): Promise<Array<Bookmark>> => {
+            return invoke("get_all_bookmarks", { tag });
+        },
+        getTags: async (): Promise<Array<NameCount>> => {
+            return invoke("get_bookmark_tags");
+        },
+        jumpTo: async (id: number): Promise<ChapterProgress> => {
+            return invoke("jump_to_bookmark", { id });
+        },
+    },
+    downloads: {
+        getQueue: async (): Promise<Array<QueuedDownload>> => {
+            return invoke("get_download_queue");
+        },
+        removeFromQueue: async (
+            source: string,
+            mangaId: string,
+            chapterId: string
+        ): Promise<number> => {
+            return invoke("remove_from_download_queue", {
+                source,
+                mangaId,
+                chapterId,
+            });
+        },
+        markDownloaded: async (
+            source: string,
+            mangaId: string,
+            chapterId: string
+        ): Promise<never> => {
+            return invoke("mark_chapter_downloaded", {
+                source,
+                mangaId,
+                chapterId,
+            });
+        },
+        clearQueue: async (): Promise<number> => {
+            return invoke("clear_download_queue");
+        },
+    },
+    legacy: {
+        import: async (
+            dryRun: boolean,
+            path?: string
+        ): Promise<{
+            dry_run: boolean;
+            imported_manga: number;
+            imported_chapters: number;
+            imported_settings: Array<string>;
+            unsupported_settings: Array<string>;
+            settings_error: string | null;
+            unmapped_sources: Array<{ name: string; manga: Array<string> }>;
+            skipped: Array<{ source: string; id: string; reason: string }>;
+        }> => {
+            return invoke("import_legacy_library", { path, dryRun });
+        },
+    },
+    tachiyomi: {
+        import: async (
+            path: string,
+            dryRun: boolean
+        ): Promise<{
+            dry_run: boolean;
+            imported_manga: number;
+            imported_chapters: number;
+            categories: Array<string>;
+            auto_mapped: Array<{ tachiyomi_id: string; source: string }>;
+            unmapped_sources: Array<{
+                tachiyomi_id: string;
+                name: string;
+                manga: Array<string>;
+            }>;
+            skipped_manga: Array<string>;
+        }> => {
+            return invoke("import_tachiyomi_backup", { path, dryRun });
+        },
+        getSourceMap: async (): Promise<
+            Array<{ tachiyomi_id: string; source: string }>
+        > => {
+            return invoke("get_tachiyomi_source_map");
+        },
+        setSourceMap: async (
+            tachiyomiId: string,
+            source: string
+        ): Promise<never> => {
+            return invoke("set_tachiyomi_source_map", { tachiyomiId, source });
+        },
+        removeSourceMap: async (tachiyomiId: string): Promise<never> => {
+            return invoke("remove_tachiyomi_source_map", { tachiyomiId });
+        },
+    },
+    search: {
+        start: async (
+            query: string,
+            sources: Array<string>,
+            filters?: Record<string, unknown>
+        ): Promise<number> => {
+            return invoke("start_global_search", { query, sources, filters });
+        },
+
+        submit: async (
+            searchId: number,
+            source: string,
+            results?: SourceSearchResults,
+            error?: string
         ): Promise<boolean> => {
-            // TODO: Figure out why newSettings is required instead of the snake_case new_settings
-            return invoke("set_app_settings", {
-                newSettings: format(new_settings, {
-                    printFunctionName: false,
-                    printBasicPrototype: false,
-                    callToJSON: true,
-                }),
+            return invoke("submit_search_result", {
+                searchId,
+                source,
+                results,
+                error,
             });
         },
+
+        getCached: async (
+            source: string,
+            query: string,
+            filters?: Record<string, unknown>
+        ): Promise<SourceSearchResults | null> => {
+            return invoke("get_cached_search", { source, query, filters });
+        },
+
+        clearCache: async (): Promise<never> => {
+            return invoke("clear_search_cache", {});
+        },
+
+        getRecent: async (): Promise<
+            Array<{ query: string; searched_at: number }>
+        > => {
+            return invoke("get_recent_searches", {});
+        },
+
+        clearRecent: async (): Promise<never> => {
+            return invoke("clear_recent_searches", {});
+        },
+
+        saved: {
+            create: async (
+                name: string,
+                source: string,
+                query: string,
+                filters?: Record<string, unknown>,
+                interval?: number
+            ): Promise<number> => {
+                return invoke("create_saved_search", {
+                    name,
+                    source,
+                    query,
+                    filters,
+                    interval,
+                });
+            },
+
+            getAll: async (): Promise<Array<Record<string, unknown>>> => {
+                return invoke("get_saved_searches", {});
+            },
+
+            setInterval: async (id: number, interval: number): Promise<never> => {
+                return invoke("set_saved_search_interval", { id, interval });
+            },
+
+            remove: async (id: number): Promise<never> => {
+                return invoke("remove_saved_search", { id });
+            },
+
+            run: async (id: number): Promise<Array<Manga>> => {
+                return invoke("run_saved_search", { id });
+            },
+        },
+    },
+    reader: {
+        getEffective: async (
+            source: string,
+            id: string
+        ): Promise<{
+            settings: Record<string, unknown>;
+            origins: Record<string, { layer: string; scope?: string }>;
+        }> => {
+            return invoke("get_effective_reader_settings", { source, id });
+        },
+
+        setOverride: async (
+            layer: "source" | "category",
+            scope: string,
+            data: Record<string, unknown>
+        ): Promise<never> => {
+            return invoke("set_reader_override", { layer, scope, data });
+        },
+
+        resetLayer: async (
+            layer: "global" | "source" | "category" | "manga",
+            scope?: string,
+            id?: string
+        ): Promise<never> => {
+            return invoke("reset_reader_layer", { layer, scope, id });
+        },
+    },
+    categories: {
+        getAll: async (): Promise<Array<string>> => {
+            return invoke("get_categories", {});
+        },
+
+        getForManga: async (source: string, id: string): Promise<Array<string>> => {
+            return invoke("get_manga_categories", { source, id });
+        },
+
+        add: async (
+            source: string,
+            id: string,
+            category: string
+        ): Promise<never> => {
+            return invoke("add_manga_to_category", { source, id, category });
+        },
+
+        remove: async (
+            source: string,
+            id: string,
+            category: string
+        ): Promise<never> => {
+            return invoke("remove_manga_from_category", { source, id, category });
+        },
     },
     sources: {
         get: async (): Promise<Array<string>> => {
             return invoke("get_sources", {});
         },
+
+        getPreferenceSchema: async (
+            source: string
+        ): Promise<Array<Record<string, unknown>>> => {
+            return invoke("get_source_preference_schema", { source });
+        },
+
+        getPreferences: async (
+            source: string
+        ): Promise<Record<string, unknown>> => {
+            return invoke("get_source_preferences", { source });
+        },
+
+        getPreference: async (source: string, key: string): Promise<unknown> => {
+            return invoke("get_source_preference", { source, key });
+        },
+
+        setPreference: async (
+            source: string,
+            key: string,
+            value: unknown
+        ): Promise<never> => {
+            return invoke("set_source_preference", { source, key, value });
+        },
+
+        uninstall: async (source: string): Promise<never> => {
+            return invoke("uninstall_source", { source });
+        },
     },
 };
 
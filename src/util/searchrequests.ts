@@ -0,0 +1,35 @@
+import { listen } from "@tauri-apps/api/event";
+
+import ipc, { SourceSearchRequest } from "util/ipc";
+import SourceHandler from "util/sources";
+import { generateTree } from "util/search";
+
+/**
+ * Sources only run in the webview, so the backend asks for every source a
+ * search needs with a `search-source-request` event. This runs the source and
+ * hands its results, or why it failed, back to the waiting search.
+ */
+const listenForSearchRequests = () =>
+    listen<SourceSearchRequest>(
+        "search-source-request",
+        async ({ payload }) => {
+            const { search_id: searchId, source, query, filters } = payload;
+
+            try {
+                const handler = await SourceHandler.querySource(source);
+                if (!handler) throw new Error(`${source} is not installed`);
+
+                const results = await handler.search(
+                    query,
+                    0,
+                    filters ?? generateTree(SourceHandler.defaultFilters(source))
+                );
+
+                await ipc.search.submit(searchId, source, results);
+            } catch (err) {
+                await ipc.search.submit(searchId, source, undefined, `${err}`);
+            }
+        }
+    );
+
+export default listenForSearchRequests;
@@ -106,6 +106,7 @@ export type Settings = Readonly<{
         lightbarEnabled: boolean;
         skipChaptersOfDifferentGroup: boolean;
         skipChaptersMarkedRead: boolean;
+        markReadThreshold: number; // Fraction of a chapter's pages after which it counts as read
         readingMode: ReadingMode;
         navLayoutPaged: NavigationLayout;
         invertTappingPaged: boolean;
@@ -144,10 +145,16 @@ export type Settings = Readonly<{
         trackWhenAddingToLibrary: boolean;
         updateWhenMarkedAsRead: boolean;
     };
-    Backup: Unimplemented;
+    Backup: {
+        automaticBackups: boolean;
+        backupInterval: number; // Hours between automatic backups
+        backupsToKeep: number;
+    };
     Security: Unimplemented;
     Advanced: {
         sendCrashReports: boolean;
+        automaticMaintenance: boolean;
+        maintenanceInterval: number; // Hours between automatic maintenance runs
     };
 }>;
 
@@ -192,6 +199,7 @@ export const DefaultSettings: Promise<LoadedSettings> = downloadDir().then(
             lightbarEnabled: true,
             skipChaptersOfDifferentGroup: false,
             skipChaptersMarkedRead: false,
+            markReadThreshold: 1,
             readingMode: ReadingMode.RightToLeft,
             navLayoutPaged: NavigationLayout.LeftAndRight,
             invertTappingPaged: false,
@@ -230,10 +238,16 @@ export const DefaultSettings: Promise<LoadedSettings> = downloadDir().then(
             trackWhenAddingToLibrary: false,
             updateWhenMarkedAsRead: false,
         },
-        Backup: {},
+        Backup: {
+            automaticBackups: false,
+            backupInterval: 24,
+            backupsToKeep: 5,
+        },
         Security: {},
         Advanced: {
             sendCrashReports: true,
+            automaticMaintenance: false,
+            maintenanceInterval: 168,
         },
     })
 );
@@ -388,6 +402,14 @@ export const SettingsSchema = {
                         type: "boolean",
                         description: "Skip chapters marked as read.",
                     },
+                    markReadThreshold: {
+                        title: "Mark as Read At",
+                        type: "number",
+                        exclusiveMinimum: 0,
+                        maximum: 1,
+                        description:
+                            "How far into a chapter it gets marked as read. 1 marks it on the last page.",
+                    },
                     readingMode: {
                         title: "Reading Mode",
                         type: "number",
@@ -602,8 +624,27 @@ export const SettingsSchema = {
             },
             Backup: {
                 type: "object",
-                properties: {},
-                maxProperties: 0,
+                properties: {
+                    automaticBackups: {
+                        title: "Automatic Backups",
+                        type: "boolean",
+                        description:
+                            "Periodically back up the library and settings.",
+                    },
+                    backupInterval: {
+                        title: "Backup Interval",
+                        type: "number",
+                        minimum: 1,
+                        description: "Hours between automatic backups.",
+                    },
+                    backupsToKeep: {
+                        title: "Backups to Keep",
+                        type: "number",
+                        minimum: 1,
+                        description:
+                            "Older automatic backups are deleted past this many.",
+                    },
+                },
             },
             Security: {
                 type: "object",
@@ -619,6 +660,18 @@ export const SettingsSchema = {
                         description:
                             "Whether to send crash reports when the application exits unexpectedly.",
                     },
+                    automaticMaintenance: {
+                        title: "Automatic Maintenance",
+                        type: "boolean",
+                        description:
+                            "Periodically check the database, remove orphaned data and compact it.",
+                    },
+                    maintenanceInterval: {
+                        title: "Maintenance Interval",
+                        type: "number",
+                        minimum: 1,
+                        description: "Hours between automatic maintenance runs.",
+                    },
                 },
             },
         },
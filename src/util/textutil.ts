@@ -4,7 +4,30 @@ import format from "date-fns/format";
 export const compileChapterText = (chapter: Chapter, short?: boolean) => {
     const ch = short ? "Ch." : "Chapter";
 
-    return `${ch} ${chapter.chapter}`;
+    if (chapter.chapter === null) return chapter.label ?? ch;
+    return chapter.label
+        ? `${ch} ${chapter.chapter} (${chapter.label})`
+        : `${ch} ${chapter.chapter}`;
+};
+
+// Same order the backend lists chapters in: by number, then volume, with
+// unnumbered chapters last by label.
+export const compareChapters = (a: Chapter, b: Chapter) => {
+    if (a.chapter !== b.chapter) {
+        if (a.chapter === null) return 1;
+        if (b.chapter === null) return -1;
+        return a.chapter - b.chapter;
+    }
+
+    if (a.volume !== b.volume) {
+        if (a.volume === null) return 1;
+        if (b.volume === null) return -1;
+        return a.volume - b.volume;
+    }
+
+    return (a.label ?? "").localeCompare(b.label ?? "", undefined, {
+        sensitivity: "base",
+    });
 };
 
 export const compileVolumeText = (